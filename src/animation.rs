@@ -0,0 +1,534 @@
+// Tweens and keyframe animations over `IrSignal`s. Nothing here reads a
+// clock: `Animator::update` is handed the current time, so the same code runs
+// from `requestAnimationFrame` or from a fixed-step export.
+
+use std::f64::consts::PI;
+
+use crate::signal::IrSignal;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Easing {
+    Linear,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    ElasticIn,
+    ElasticOut,
+    // CSS-style cubic-bezier(x1, y1, x2, y2)
+    CubicBezier(f64, f64, f64, f64),
+}
+
+impl Easing {
+    /// Parse CSS-like names: `linear`, `ease-in`, `ease-out`, `ease-in-out`,
+    /// `elastic-in`, `elastic-out` (or `elastic`) and `cubic-bezier(a, b, c, d)`.
+    pub fn parse(spec: &str) -> Option<Self> {
+        let spec = spec.trim();
+        match spec {
+            "linear" => Some(Easing::Linear),
+            "ease-in" | "cubic-in" => Some(Easing::CubicIn),
+            "ease-out" | "cubic-out" => Some(Easing::CubicOut),
+            "ease-in-out" | "cubic-in-out" | "ease" => Some(Easing::CubicInOut),
+            "elastic-in" => Some(Easing::ElasticIn),
+            "elastic-out" | "elastic" => Some(Easing::ElasticOut),
+            _ => {
+                let args = spec.strip_prefix("cubic-bezier(")?.strip_suffix(')')?;
+                let values: Vec<f64> = args
+                    .split(',')
+                    .map(|v| v.trim().parse())
+                    .collect::<Result<_, _>>()
+                    .ok()?;
+                match values.as_slice() {
+                    [x1, y1, x2, y2] => Some(Easing::CubicBezier(*x1, *y1, *x2, *y2)),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    /// Map linear progress `t` in 0..1 to eased progress (which may overshoot).
+    pub fn apply(&self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+        match *self {
+            Easing::Linear => t,
+            Easing::CubicIn => t * t * t,
+            Easing::CubicOut => 1.0 - (1.0 - t).powi(3),
+            Easing::CubicInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+            Easing::ElasticIn => 1.0 - Easing::ElasticOut.apply(1.0 - t),
+            Easing::ElasticOut => {
+                if t == 0.0 || t == 1.0 {
+                    t
+                } else {
+                    2f64.powf(-10.0 * t) * ((t * 10.0 - 0.75) * (2.0 * PI / 3.0)).sin() + 1.0
+                }
+            }
+            Easing::CubicBezier(x1, y1, x2, y2) => {
+                let s = solve_bezier_x(t, x1, x2);
+                bezier(s, y1, y2)
+            }
+        }
+    }
+}
+
+// One coordinate of a cubic Bézier from 0 to 1 with control values a and b.
+fn bezier(s: f64, a: f64, b: f64) -> f64 {
+    let inv = 1.0 - s;
+    3.0 * inv * inv * s * a + 3.0 * inv * s * s * b + s * s * s
+}
+
+// Find the curve parameter whose x is `x`: Newton steps, falling back to
+// bisection where the slope is too flat.
+fn solve_bezier_x(x: f64, x1: f64, x2: f64) -> f64 {
+    let mut s = x;
+    for _ in 0..8 {
+        let error = bezier(s, x1, x2) - x;
+        if error.abs() < 1e-7 {
+            return s;
+        }
+        let slope = 3.0 * (1.0 - s) * (1.0 - s) * x1
+            + 6.0 * (1.0 - s) * s * (x2 - x1)
+            + 3.0 * s * s * (1.0 - x2);
+        if slope.abs() < 1e-6 {
+            break;
+        }
+        s -= error / slope;
+    }
+
+    let (mut lo, mut hi) = (0.0, 1.0);
+    s = x;
+    for _ in 0..32 {
+        let value = bezier(s, x1, x2);
+        if (value - x).abs() < 1e-7 {
+            break;
+        }
+        if value < x {
+            lo = s;
+        } else {
+            hi = s;
+        }
+        s = (lo + hi) / 2.0;
+    }
+    s
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Keyframe {
+    // seconds from the start of the animation
+    pub time: f64,
+    pub value: f64,
+    // easing of the segment that ends at this keyframe
+    pub easing: Easing,
+}
+
+/// Keyframes driving one signal. Before the first keyframe the track holds
+/// its first value, after the last one its last value.
+#[derive(Clone)]
+pub struct Track {
+    pub signal: IrSignal,
+    pub keyframes: Vec<Keyframe>,
+}
+
+impl Track {
+    pub fn new(signal: IrSignal, mut keyframes: Vec<Keyframe>) -> Self {
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Self { signal, keyframes }
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.keyframes.last().map_or(0.0, |k| k.time)
+    }
+
+    pub fn value_at(&self, time: f64) -> Option<f64> {
+        let first = self.keyframes.first()?;
+        if time <= first.time {
+            return Some(first.value);
+        }
+        for pair in self.keyframes.windows(2) {
+            let (from, to) = (&pair[0], &pair[1]);
+            if time <= to.time {
+                let span = to.time - from.time;
+                let t = if span > 0.0 {
+                    (time - from.time) / span
+                } else {
+                    1.0
+                };
+                return Some(from.value + (to.value - from.value) * to.easing.apply(t));
+            }
+        }
+        self.keyframes.last().map(|k| k.value)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Repeat {
+    Once,
+    // restart from the beginning, `None` meaning forever
+    Loop(Option<u32>),
+    // play forwards then backwards, `None` meaning forever
+    PingPong(Option<u32>),
+}
+
+impl Repeat {
+    /// `once`, `loop`, `ping-pong`, optionally with a count: `loop:3`.
+    pub fn parse(spec: &str) -> Option<Self> {
+        let (name, count) = match spec.split_once(':') {
+            Some((name, count)) => (name, Some(count.trim().parse().ok()?)),
+            None => (spec, None),
+        };
+        match name.trim() {
+            "once" => Some(Repeat::Once),
+            "loop" => Some(Repeat::Loop(count)),
+            "ping-pong" | "pingpong" => Some(Repeat::PingPong(count)),
+            _ => None,
+        }
+    }
+}
+
+/// A set of tracks played together.
+#[derive(Clone)]
+pub struct Animation {
+    pub tracks: Vec<Track>,
+    pub repeat: Repeat,
+}
+
+impl Animation {
+    pub fn new(tracks: Vec<Track>) -> Self {
+        Self {
+            tracks,
+            repeat: Repeat::Once,
+        }
+    }
+
+    /// Animate `signal` from its current value to `to`.
+    pub fn tween(signal: IrSignal, to: f64, duration: f64, easing: Easing) -> Self {
+        Self::new(vec![Track::new(
+            signal,
+            vec![
+                Keyframe {
+                    time: 0.0,
+                    value: signal.get(),
+                    easing: Easing::Linear,
+                },
+                Keyframe {
+                    time: duration.max(0.0),
+                    value: to,
+                    easing,
+                },
+            ],
+        )])
+    }
+
+    pub fn with_repeat(mut self, repeat: Repeat) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    /// Length of one pass.
+    pub fn duration(&self) -> f64 {
+        self.tracks.iter().map(Track::duration).fold(0.0, f64::max)
+    }
+
+    /// Total length including repeats, `None` if it repeats forever.
+    pub fn total_duration(&self) -> Option<f64> {
+        let duration = self.duration();
+        match self.repeat {
+            Repeat::Once => Some(duration),
+            Repeat::Loop(count) | Repeat::PingPong(count) => {
+                count.map(|count| duration * count.max(1) as f64)
+            }
+        }
+    }
+
+    /// Local time within one pass for `elapsed` seconds since the start.
+    pub fn local_time(&self, elapsed: f64) -> f64 {
+        let duration = self.duration();
+        if duration <= 0.0 {
+            return 0.0;
+        }
+        let elapsed = match self.total_duration() {
+            Some(total) => elapsed.clamp(0.0, total),
+            None => elapsed.max(0.0),
+        };
+        match self.repeat {
+            Repeat::Once => elapsed,
+            Repeat::Loop(_) => {
+                // land on the end, not the start, when the last pass finishes
+                if elapsed > 0.0 && elapsed % duration == 0.0 {
+                    duration
+                } else {
+                    elapsed % duration
+                }
+            }
+            Repeat::PingPong(_) => {
+                let pass = (elapsed / duration).floor();
+                let within = elapsed - pass * duration;
+                let backwards = pass as u64 % 2 == 1;
+                if elapsed > 0.0 && within == 0.0 {
+                    // the end of a pass: forwards ends at the end, backwards at the start
+                    if backwards {
+                        duration
+                    } else {
+                        0.0
+                    }
+                } else if backwards {
+                    duration - within
+                } else {
+                    within
+                }
+            }
+        }
+    }
+
+    /// Write every track's value for `elapsed` seconds into its signal.
+    pub fn apply(&self, elapsed: f64) {
+        let time = self.local_time(elapsed);
+        for track in &self.tracks {
+            if let Some(value) = track.value_at(time) {
+                track.signal.set(value);
+            }
+        }
+    }
+
+    pub fn is_finished(&self, elapsed: f64) -> bool {
+        self.total_duration().is_some_and(|total| elapsed >= total)
+    }
+}
+
+pub type AnimationId = u32;
+
+struct Running {
+    id: AnimationId,
+    animation: Animation,
+    // set by the first `update` after the animation was added
+    start: Option<f64>,
+}
+
+/// Plays animations against a clock supplied by the caller.
+#[derive(Default)]
+pub struct Animator {
+    running: Vec<Running>,
+    next_id: AnimationId,
+}
+
+impl Animator {
+    /// Start `animation` at the time of the next `update`. Animations already
+    /// driving any of its signals are stopped, so a new tween takes over
+    /// from wherever the old one left the value.
+    pub fn play(&mut self, animation: Animation) -> AnimationId {
+        for track in &animation.tracks {
+            self.stop_signal(track.signal);
+        }
+        self.next_id += 1;
+        self.running.push(Running {
+            id: self.next_id,
+            animation,
+            start: None,
+        });
+        self.next_id
+    }
+
+    pub fn stop(&mut self, id: AnimationId) -> bool {
+        let before = self.running.len();
+        self.running.retain(|running| running.id != id);
+        self.running.len() != before
+    }
+
+    /// Remove `signal` from every running animation, dropping animations that
+    /// have no tracks left.
+    pub fn stop_signal(&mut self, signal: IrSignal) {
+        for running in &mut self.running {
+            running
+                .animation
+                .tracks
+                .retain(|track| track.signal.set != signal.set);
+        }
+        self.running
+            .retain(|running| !running.animation.tracks.is_empty());
+    }
+
+    pub fn stop_all(&mut self) {
+        self.running.clear();
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.running.is_empty()
+    }
+
+    /// Advance everything to `now` (seconds, any epoch). Returns true while
+    /// animations remain, i.e. while another frame should be scheduled.
+    pub fn update(&mut self, now: f64) -> bool {
+        for running in &mut self.running {
+            let start = *running.start.get_or_insert(now);
+            running.animation.apply(now - start);
+        }
+        self.running.retain(|running| {
+            !running
+                .animation
+                .is_finished(now - running.start.unwrap_or(now))
+        });
+        self.is_active()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyframe(time: f64, value: f64, easing: Easing) -> Keyframe {
+        Keyframe {
+            time,
+            value,
+            easing,
+        }
+    }
+
+    // a two-second track from 0 to 10
+    fn ramp(repeat: Repeat) -> Animation {
+        let track = Track::new(
+            IrSignal::new(0.0),
+            vec![
+                keyframe(0.0, 0.0, Easing::Linear),
+                keyframe(2.0, 10.0, Easing::Linear),
+            ],
+        );
+        Animation::new(vec![track]).with_repeat(repeat)
+    }
+
+    #[test]
+    fn parse_names_and_cubic_bezier() {
+        assert_eq!(Easing::parse(" ease-in "), Some(Easing::CubicIn));
+        assert_eq!(Easing::parse("ease"), Some(Easing::CubicInOut));
+        assert_eq!(Easing::parse("elastic"), Some(Easing::ElasticOut));
+        assert_eq!(
+            Easing::parse("cubic-bezier(0.25, 0.1,0.25 , 1)"),
+            Some(Easing::CubicBezier(0.25, 0.1, 0.25, 1.0))
+        );
+
+        assert_eq!(Easing::parse("bounce"), None);
+        assert_eq!(Easing::parse("cubic-bezier(0.25, 0.1, 0.25)"), None);
+        assert_eq!(Easing::parse("cubic-bezier(0.25, 0.1, 0.25, 1, 0)"), None);
+        assert_eq!(Easing::parse("cubic-bezier(a, 0.1, 0.25, 1)"), None);
+        assert_eq!(Easing::parse("cubic-bezier(0.25, 0.1, 0.25, 1"), None);
+        assert_eq!(Easing::parse("cubic-bezier()"), None);
+    }
+
+    #[test]
+    fn every_easing_starts_at_zero_and_ends_at_one() {
+        let easings = [
+            Easing::Linear,
+            Easing::CubicIn,
+            Easing::CubicOut,
+            Easing::CubicInOut,
+            Easing::ElasticIn,
+            Easing::ElasticOut,
+            Easing::CubicBezier(0.25, 0.1, 0.25, 1.0),
+            Easing::CubicBezier(0.68, -0.55, 0.27, 1.55),
+        ];
+        for easing in easings.iter() {
+            assert!(easing.apply(0.0).abs() < 1e-9, "{:?}", easing);
+            assert!((easing.apply(1.0) - 1.0).abs() < 1e-9, "{:?}", easing);
+            // progress outside 0..1 is clamped
+            assert_eq!(easing.apply(-0.5), easing.apply(0.0), "{:?}", easing);
+            assert_eq!(easing.apply(1.5), easing.apply(1.0), "{:?}", easing);
+        }
+        assert_eq!(Easing::CubicIn.apply(0.5), 0.125);
+        assert_eq!(Easing::CubicOut.apply(0.5), 0.875);
+    }
+
+    #[test]
+    fn solve_bezier_x_inverts_the_curve() {
+        // Newton converges on an ordinary curve
+        for x in [0.1, 0.5, 0.9].iter() {
+            let s = solve_bezier_x(*x, 0.25, 0.25);
+            assert!((bezier(s, 0.25, 0.25) - x).abs() < 1e-7);
+        }
+        // x(s) = 0.5 + 4 (s - 0.5)³ is flat in the middle, so just off it
+        // Newton gives up and bisection finds the parameter
+        let s = solve_bezier_x(0.5001, 1.0, 0.0);
+        assert!((bezier(s, 1.0, 0.0) - 0.5001).abs() < 1e-7);
+        assert!((s - (0.5 + (0.0001f64 / 4.0).cbrt())).abs() < 1e-4);
+    }
+
+    #[test]
+    fn track_holds_its_ends_and_eases_between_keyframes() {
+        let track = Track::new(
+            IrSignal::new(0.0),
+            vec![
+                keyframe(3.0, 30.0, Easing::CubicIn),
+                keyframe(1.0, 10.0, Easing::Linear),
+            ],
+        );
+        assert_eq!(track.duration(), 3.0);
+        assert_eq!(track.value_at(0.0), Some(10.0));
+        assert_eq!(track.value_at(1.0), Some(10.0));
+        // the segment uses the easing of the keyframe it ends at
+        assert_eq!(track.value_at(2.0), Some(12.5));
+        assert_eq!(track.value_at(3.0), Some(30.0));
+        assert_eq!(track.value_at(5.0), Some(30.0));
+
+        assert_eq!(
+            Track::new(IrSignal::new(0.0), Vec::new()).value_at(1.0),
+            None
+        );
+    }
+
+    #[test]
+    fn loops_end_each_pass_at_the_end() {
+        let animation = ramp(Repeat::Loop(Some(2)));
+        assert_eq!(animation.total_duration(), Some(4.0));
+        assert_eq!(animation.local_time(0.0), 0.0);
+        assert_eq!(animation.local_time(1.0), 1.0);
+        assert_eq!(animation.local_time(2.0), 2.0);
+        assert_eq!(animation.local_time(2.5), 0.5);
+        assert_eq!(animation.local_time(4.0), 2.0);
+        assert_eq!(animation.local_time(9.0), 2.0);
+        assert_eq!(ramp(Repeat::Loop(None)).local_time(9.0), 1.0);
+    }
+
+    #[test]
+    fn ping_pong_turns_around_at_each_pass() {
+        let animation = ramp(Repeat::PingPong(Some(3)));
+        assert_eq!(animation.total_duration(), Some(6.0));
+        assert_eq!(animation.local_time(1.0), 1.0);
+        assert_eq!(animation.local_time(2.0), 2.0);
+        assert_eq!(animation.local_time(3.0), 1.0);
+        assert_eq!(animation.local_time(4.0), 0.0);
+        assert_eq!(animation.local_time(5.0), 1.0);
+        // three passes end going forwards
+        assert_eq!(animation.local_time(6.0), 2.0);
+        assert_eq!(animation.local_time(7.0), 2.0);
+        assert!(animation.is_finished(6.0));
+        assert!(!animation.is_finished(5.9));
+    }
+
+    #[test]
+    fn a_new_tween_takes_over_its_signal() {
+        let (x, y) = (IrSignal::new(0.0), IrSignal::new(0.0));
+        let mut both = Animation::tween(x, 100.0, 1.0, Easing::Linear);
+        both.tracks
+            .extend(Animation::tween(y, 100.0, 1.0, Easing::Linear).tracks);
+
+        let mut animator = Animator::default();
+        let first = animator.play(both);
+        assert!(animator.update(0.0));
+        assert!(animator.update(0.5));
+        assert_eq!((x.get(), y.get()), (50.0, 50.0));
+
+        // x turns back from where it is; y carries on with the first animation
+        let second = animator.play(Animation::tween(x, 0.0, 1.0, Easing::Linear));
+        assert_ne!(first, second);
+        assert!(animator.update(1.0));
+        assert_eq!((x.get(), y.get()), (50.0, 100.0));
+        assert!(!animator.stop(first));
+
+        assert!(animator.update(1.5));
+        assert_eq!(x.get(), 25.0);
+        assert!(!animator.update(2.0));
+        assert_eq!(x.get(), 0.0);
+    }
+}
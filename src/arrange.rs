@@ -0,0 +1,83 @@
+// Lining shapes up and spacing them out by their bounding boxes. These only
+// work out how far each box should move; the editor moves the shapes.
+
+use vello::kurbo::{Rect, Vec2};
+
+use crate::constraints::Axis;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Alignment {
+    Left,
+    Center,
+    Right,
+    Top,
+    Middle,
+    Bottom,
+}
+
+impl Alignment {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "left" => Some(Alignment::Left),
+            "center" => Some(Alignment::Center),
+            "right" => Some(Alignment::Right),
+            "top" => Some(Alignment::Top),
+            "middle" => Some(Alignment::Middle),
+            "bottom" => Some(Alignment::Bottom),
+            _ => None,
+        }
+    }
+}
+
+/// How far to move each box to line it up with the outermost edge, or the
+/// middle, of them all.
+pub fn align(bounds: &[Rect], alignment: Alignment) -> Vec<Vec2> {
+    let all = match bounds.iter().copied().reduce(|a, b| a.union(b)) {
+        Some(all) => all,
+        None => return Vec::new(),
+    };
+    bounds
+        .iter()
+        .map(|rect| match alignment {
+            Alignment::Left => Vec2::new(all.x0 - rect.x0, 0.0),
+            Alignment::Center => Vec2::new(all.center().x - rect.center().x, 0.0),
+            Alignment::Right => Vec2::new(all.x1 - rect.x1, 0.0),
+            Alignment::Top => Vec2::new(0.0, all.y0 - rect.y0),
+            Alignment::Middle => Vec2::new(0.0, all.center().y - rect.center().y),
+            Alignment::Bottom => Vec2::new(0.0, all.y1 - rect.y1),
+        })
+        .collect()
+}
+
+/// How far to move each box to space them out along `axis`, keeping the
+/// order they already come in. With a `spacing` the boxes follow the first
+/// one that far apart; without, the first and last stay put and the gaps
+/// between them are made equal.
+pub fn distribute(bounds: &[Rect], axis: Axis, spacing: Option<f64>) -> Vec<Vec2> {
+    let span = |rect: &Rect| match axis {
+        Axis::Horizontal => (rect.x0, rect.width()),
+        Axis::Vertical => (rect.y0, rect.height()),
+    };
+    let mut order: Vec<usize> = (0..bounds.len()).collect();
+    order.sort_by(|a, b| span(&bounds[*a]).0.total_cmp(&span(&bounds[*b]).0));
+    let mut offsets = vec![Vec2::ZERO; bounds.len()];
+    let (first, last) = match (order.first(), order.last()) {
+        (Some(first), Some(last)) => (span(&bounds[*first]), span(&bounds[*last])),
+        _ => return offsets,
+    };
+    let gap = spacing.unwrap_or_else(|| {
+        let total: f64 = bounds.iter().map(|rect| span(rect).1).sum();
+        (last.0 + last.1 - first.0 - total) / (bounds.len() - 1).max(1) as f64
+    });
+
+    let mut next = first.0;
+    for index in order {
+        let (start, size) = span(&bounds[index]);
+        offsets[index] = match axis {
+            Axis::Horizontal => Vec2::new(next - start, 0.0),
+            Axis::Vertical => Vec2::new(0.0, next - start),
+        };
+        next += size + gap;
+    }
+    offsets
+}
@@ -0,0 +1,483 @@
+// Boolean operations on filled outlines. Both operands are flattened to
+// polygons, every edge is split wherever it touches another, and each piece
+// is kept if the result of the operation differs on its two sides. The kept
+// pieces link up into closed loops with the filled side on their left, so
+// the result fills the same under either fill rule. Pieces remember which
+// curve they were flattened from, so runs of them along one curve go back
+// to being a single piece of that curve.
+
+use std::collections::{HashMap, HashSet};
+
+use vello::kurbo::{BezPath, CubicBez, ParamCurve, PathEl, Point, QuadBez, Rect, Vec2};
+use vello::peniko::Fill;
+
+// how far flattened curves may stray from the originals
+pub const FLATTEN_TOLERANCE: f64 = 0.05;
+
+// parametric slack when intersecting edges
+const EPSILON: f64 = 1e-9;
+// points closer than this are the same vertex
+const SNAP_DISTANCE: f64 = 1e-7;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BooleanOp {
+    Union,
+    Intersect,
+    // the second operand cut out of the first
+    Subtract,
+    // areas covered by exactly one operand
+    Xor,
+}
+
+impl BooleanOp {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "union" => Some(BooleanOp::Union),
+            "intersect" => Some(BooleanOp::Intersect),
+            "subtract" => Some(BooleanOp::Subtract),
+            "xor" | "exclude" => Some(BooleanOp::Xor),
+            _ => None,
+        }
+    }
+
+    pub fn apply(self, a: bool, b: bool) -> bool {
+        match self {
+            BooleanOp::Union => a || b,
+            BooleanOp::Intersect => a && b,
+            BooleanOp::Subtract => a && !b,
+            BooleanOp::Xor => a != b,
+        }
+    }
+}
+
+/// Combine two filled outlines. The result may contain holes. Its lines
+/// and curves are pieces of the inputs' own, except that where outlines
+/// cross, the ends may be moved by up to `FLATTEN_TOLERANCE`, as the
+/// crossings are found between flattened copies.
+pub fn combine(a: &BezPath, a_fill: Fill, b: &BezPath, b_fill: Fill, op: BooleanOp) -> BezPath {
+    let mut curves = Vec::new();
+    let a = flatten(a, &mut curves);
+    let b = flatten(b, &mut curves);
+
+    let mut segments = Vec::new();
+    let mut spans = Vec::new();
+    for ring in a.iter().chain(&b) {
+        let n = ring.points.len();
+        for i in 0..n {
+            segments.push((ring.points[i], ring.points[(i + 1) % n]));
+            spans.push(ring.spans[i]);
+        }
+    }
+    let mut vertices = VertexPool::default();
+    let edges = split_edges(&segments, &spans, &mut vertices);
+
+    // probes either side of an edge sit this far from it
+    let extent = segments
+        .iter()
+        .fold(0.0f64, |m, (p, _)| m.max(p.x.abs()).max(p.y.abs()));
+    let probe = (extent + 1.0) * 1e-7;
+
+    let mut kept = Vec::new();
+    for edge in edges {
+        let (p, q) = (vertices.points[edge.from], vertices.points[edge.to]);
+        let d = q - p;
+        let length = d.hypot();
+        let offset = probe.min(length * 0.25);
+        let left = Vec2::new(-d.y, d.x) / length * offset;
+        let mid = p.midpoint(q);
+        let filled =
+            |point: Point| op.apply(is_inside(&a, a_fill, point), is_inside(&b, b_fill, point));
+        match (filled(mid + left), filled(mid - left)) {
+            (true, false) => kept.push(edge),
+            (false, true) => kept.push(edge.reversed()),
+            _ => {}
+        }
+    }
+
+    let mut path = BezPath::new();
+    for ring in link_rings(&kept) {
+        let ring: Vec<Edge> = ring.into_iter().map(|index| kept[index]).collect();
+        trace(&mut path, &ring, &vertices.points, &curves);
+    }
+    path
+}
+
+// Where an edge lies along one of the curves that were flattened: it starts
+// at `t0` on `curves[curve]` and ends at `t1`.
+#[derive(Clone, Copy, Debug)]
+struct Span {
+    curve: usize,
+    t0: f64,
+    t1: f64,
+}
+
+impl Span {
+    // the part from `u0` to `u1` of the way along
+    fn part(self, u0: f64, u1: f64) -> Span {
+        let t = |u: f64| self.t0 + (self.t1 - self.t0) * u;
+        Span {
+            t0: t(u0),
+            t1: t(u1),
+            ..self
+        }
+    }
+}
+
+// A closed polygon. Edge `i` runs from `points[i]` to the next point, along
+// `spans[i]` if it was flattened from a curve.
+#[derive(Default)]
+struct Ring {
+    points: Vec<Point>,
+    spans: Vec<Option<Span>>,
+}
+
+// One polygon per subpath, curves cut into straight edges evenly spaced in
+// their parameter and added to `curves`.
+fn flatten(path: &BezPath, curves: &mut Vec<CubicBez>) -> Vec<Ring> {
+    fn finish(rings: &mut Vec<Ring>, ring: &mut Ring, last: Point, start: Point) {
+        if last != start {
+            ring.points.push(last);
+            ring.spans.push(None);
+        }
+        if ring.points.len() >= 3 {
+            rings.push(std::mem::take(ring));
+        } else {
+            *ring = Ring::default();
+        }
+    }
+
+    let mut rings = Vec::new();
+    let mut ring = Ring::default();
+    let (mut start, mut last) = (Point::ZERO, Point::ZERO);
+    for el in path.elements() {
+        let curve = match *el {
+            PathEl::MoveTo(p) => {
+                finish(&mut rings, &mut ring, last, start);
+                start = p;
+                last = p;
+                continue;
+            }
+            PathEl::LineTo(p) => {
+                ring.points.push(last);
+                ring.spans.push(None);
+                last = p;
+                continue;
+            }
+            PathEl::QuadTo(p1, p2) => QuadBez::new(last, p1, p2).raise(),
+            PathEl::CurveTo(p1, p2, p3) => CubicBez::new(last, p1, p2, p3),
+            PathEl::ClosePath => {
+                finish(&mut rings, &mut ring, last, start);
+                last = start;
+                continue;
+            }
+        };
+        // uniform steps stray at most 3/4 of the largest second difference
+        // over the square of their number
+        let bend = ((curve.p0 - curve.p1) - (curve.p1 - curve.p2))
+            .hypot()
+            .max(((curve.p1 - curve.p2) - (curve.p2 - curve.p3)).hypot());
+        let steps = ((0.75 * bend / FLATTEN_TOLERANCE).sqrt().ceil() as usize).max(1);
+        let index = curves.len();
+        curves.push(curve);
+        for step in 0..steps {
+            let (t0, t1) = (step as f64 / steps as f64, (step + 1) as f64 / steps as f64);
+            ring.points
+                .push(if step == 0 { last } else { curve.eval(t0) });
+            ring.spans.push(Some(Span {
+                curve: index,
+                t0,
+                t1,
+            }));
+        }
+        last = curve.p3;
+    }
+    finish(&mut rings, &mut ring, last, start);
+    rings
+}
+
+fn winding(rings: &[Ring], point: Point) -> i32 {
+    let mut winding = 0;
+    for ring in rings {
+        let ring = &ring.points;
+        for i in 0..ring.len() {
+            let (a, b) = (ring[i], ring[(i + 1) % ring.len()]);
+            let side = (b - a).cross(point - a);
+            if a.y <= point.y {
+                if b.y > point.y && side > 0.0 {
+                    winding += 1;
+                }
+            } else if b.y <= point.y && side < 0.0 {
+                winding -= 1;
+            }
+        }
+    }
+    winding
+}
+
+fn is_inside(rings: &[Ring], fill: Fill, point: Point) -> bool {
+    let winding = winding(rings, point);
+    match fill {
+        Fill::NonZero => winding != 0,
+        Fill::EvenOdd => winding % 2 != 0,
+    }
+}
+
+// Merges points that land within `SNAP_DISTANCE` of each other, so pieces
+// computed from different edges still meet.
+#[derive(Default)]
+struct VertexPool {
+    points: Vec<Point>,
+    cells: HashMap<(i64, i64), Vec<usize>>,
+}
+
+impl VertexPool {
+    fn insert(&mut self, point: Point) -> usize {
+        let cell = (
+            (point.x / SNAP_DISTANCE).floor() as i64,
+            (point.y / SNAP_DISTANCE).floor() as i64,
+        );
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                if let Some(indices) = self.cells.get(&(cell.0 + dx, cell.1 + dy)) {
+                    for index in indices {
+                        if (self.points[*index] - point).hypot() <= SNAP_DISTANCE {
+                            return *index;
+                        }
+                    }
+                }
+            }
+        }
+        self.points.push(point);
+        let index = self.points.len() - 1;
+        self.cells.entry(cell).or_default().push(index);
+        index
+    }
+}
+
+// A piece of a split segment, between two vertices.
+#[derive(Clone, Copy, Debug)]
+struct Edge {
+    from: usize,
+    to: usize,
+    span: Option<Span>,
+}
+
+impl Edge {
+    fn reversed(self) -> Edge {
+        Edge {
+            from: self.to,
+            to: self.from,
+            span: self.span.map(|span| Span {
+                t0: span.t1,
+                t1: span.t0,
+                ..span
+            }),
+        }
+    }
+}
+
+// Split segments wherever they cross or overlap, returning each distinct
+// piece once. Segments are swept left to right, so only those whose
+// bounds overlap are compared.
+fn split_edges(
+    segments: &[(Point, Point)],
+    spans: &[Option<Span>],
+    vertices: &mut VertexPool,
+) -> Vec<Edge> {
+    let mut splits: Vec<Vec<(f64, Point)>> = vec![Vec::new(); segments.len()];
+    let bounds: Vec<Rect> = segments
+        .iter()
+        .map(|(p, q)| Rect::from_points(*p, *q).inflate(SNAP_DISTANCE, SNAP_DISTANCE))
+        .collect();
+    let mut order: Vec<usize> = (0..segments.len()).collect();
+    order.sort_by(|a, b| bounds[*a].x0.total_cmp(&bounds[*b].x0));
+
+    for (k, &i) in order.iter().enumerate() {
+        for &j in &order[k + 1..] {
+            if bounds[j].x0 > bounds[i].x1 {
+                break;
+            }
+            if !overlaps(bounds[i], bounds[j]) {
+                continue;
+            }
+            intersect(segments[i], segments[j], |on_first, t, point| {
+                let index = if on_first { i } else { j };
+                splits[index].push((t, point));
+            });
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let mut edges = Vec::new();
+    for (((p, q), span), mut cuts) in segments.iter().zip(spans).zip(splits) {
+        cuts.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let (mut from, mut u0) = (vertices.insert(*p), 0.0);
+        for (u1, point) in cuts.into_iter().chain(Some((1.0, *q))) {
+            let to = vertices.insert(point);
+            if to != from && seen.insert((from.min(to), from.max(to))) {
+                edges.push(Edge {
+                    from,
+                    to,
+                    span: span.map(|span| span.part(u0, u1)),
+                });
+            }
+            from = to;
+            u0 = u1;
+        }
+    }
+    edges
+}
+
+fn overlaps(a: Rect, b: Rect) -> bool {
+    a.x0 <= b.x1 && b.x0 <= a.x1 && a.y0 <= b.y1 && b.y0 <= a.y1
+}
+
+// Report where `s` and `u` touch as (on `s`?, parameter, point), for every
+// point strictly inside one of them. Points near an endpoint snap to it.
+fn intersect(s: (Point, Point), u: (Point, Point), mut report: impl FnMut(bool, f64, Point)) {
+    let (p, r) = (s.0, s.1 - s.0);
+    let (q, v) = (u.0, u.1 - u.0);
+    let denom = r.cross(v);
+    let qp = q - p;
+    let interior = |t: f64| t > EPSILON && t < 1.0 - EPSILON;
+
+    if denom.abs() <= EPSILON * r.hypot() * v.hypot() {
+        // parallel: only collinear overlaps matter
+        if qp.cross(r).abs() > EPSILON * r.hypot2().max(v.hypot2()) {
+            return;
+        }
+        for (point, on_first, origin, dir) in [
+            (u.0, true, p, r),
+            (u.1, true, p, r),
+            (s.0, false, q, v),
+            (s.1, false, q, v),
+        ] {
+            let length = dir.hypot2();
+            if length > 0.0 {
+                let t = (point - origin).dot(dir) / length;
+                if interior(t) {
+                    report(on_first, t, point);
+                }
+            }
+        }
+        return;
+    }
+
+    let t = qp.cross(v) / denom;
+    let w = qp.cross(r) / denom;
+    if !(-EPSILON..=1.0 + EPSILON).contains(&t) || !(-EPSILON..=1.0 + EPSILON).contains(&w) {
+        return;
+    }
+    let point = if t <= EPSILON {
+        s.0
+    } else if t >= 1.0 - EPSILON {
+        s.1
+    } else if w <= EPSILON {
+        u.0
+    } else if w >= 1.0 - EPSILON {
+        u.1
+    } else {
+        p + r * t
+    };
+    if interior(t) {
+        report(true, t, point);
+    }
+    if interior(w) {
+        report(false, w, point);
+    }
+}
+
+// Chain directed edges into closed loops of edge indices.
+fn link_rings(edges: &[Edge]) -> Vec<Vec<usize>> {
+    let mut outgoing: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (index, edge) in edges.iter().enumerate() {
+        outgoing.entry(edge.from).or_default().push(index);
+    }
+
+    let mut used = vec![false; edges.len()];
+    let mut rings = Vec::new();
+    for start in 0..edges.len() {
+        if used[start] {
+            continue;
+        }
+        let mut ring = Vec::new();
+        let mut edge = start;
+        loop {
+            used[edge] = true;
+            ring.push(edge);
+            let to = edges[edge].to;
+            if to == edges[start].from {
+                break;
+            }
+            match outgoing
+                .get(&to)
+                .and_then(|next| next.iter().copied().find(|next| !used[*next]))
+            {
+                Some(next) => edge = next,
+                // a dangling piece; close the loop where it stops
+                None => break,
+            }
+        }
+        rings.push(ring);
+    }
+    rings
+}
+
+// Add a loop of edges to `path`, each run of them along one curve as a
+// single piece of it, and each run of lines in a straight line as one line.
+// Loops of fewer than three lines enclose nothing and are left out.
+fn trace(path: &mut BezPath, ring: &[Edge], points: &[Point], curves: &[CubicBez]) {
+    let continues = |prev: &Edge, next: &Edge| match (prev.span, next.span) {
+        (Some(a), Some(b)) => {
+            a.curve == b.curve && (a.t1 - b.t0).abs() < 1e-9 && (a.t1 > a.t0) == (b.t1 > b.t0)
+        }
+        (None, None) => {
+            let (a, b) = (
+                points[prev.to] - points[prev.from],
+                points[next.to] - points[next.from],
+            );
+            a.cross(b).abs() <= 1e-9 * a.hypot() * b.hypot() && a.dot(b) >= 0.0
+        }
+        _ => false,
+    };
+    let n = ring.len();
+    let start = (0..n)
+        .find(|i| !continues(&ring[(i + n - 1) % n], &ring[*i]))
+        .unwrap_or(0);
+
+    // (first edge, last edge) of each run
+    let mut runs: Vec<(Edge, Edge)> = Vec::new();
+    for i in 0..n {
+        let edge = ring[(start + i) % n];
+        match runs.last_mut() {
+            Some(run) if continues(&run.1, &edge) => run.1 = edge,
+            _ => runs.push((edge, edge)),
+        }
+    }
+    if runs.len() < 3 && runs.iter().all(|(first, _)| first.span.is_none()) {
+        return;
+    }
+
+    path.move_to(points[runs[0].0.from]);
+    for (i, (first, last)) in runs.iter().enumerate() {
+        let (from, to) = (points[first.from], points[last.to]);
+        match (first.span, last.span) {
+            (Some(a), Some(b)) => {
+                let curve = curves[a.curve];
+                let piece = if a.t0 <= b.t1 {
+                    curve.subsegment(a.t0..b.t1)
+                } else {
+                    let piece = curve.subsegment(b.t1..a.t0);
+                    CubicBez::new(piece.p3, piece.p2, piece.p1, piece.p0)
+                };
+                // the ends are where the flattened copies met; keep the
+                // tangents
+                path.curve_to(piece.p1 + (from - piece.p0), piece.p2 + (to - piece.p3), to);
+            }
+            // closing the path draws the last line
+            _ if i + 1 == runs.len() => {}
+            _ => path.line_to(to),
+        }
+    }
+    path.close_path();
+}
@@ -0,0 +1,600 @@
+// An incremental Cassowary solver, after the Kiwi implementation. Linear
+// equalities and inequalities, each with a strength, live in a simplex
+// tableau that is re-optimized after every change rather than solved from
+// scratch. Required constraints always hold; the others are satisfied by
+// order of strength, as far as they can be. Edit variables take suggested
+// values and the dual simplex moves everything else to suit.
+//
+// Variables are plain indices; `constraints` maps them to signals.
+
+use std::collections::BTreeMap;
+
+pub const REQUIRED: f64 = 1_001_001_000.0;
+pub const STRONG: f64 = 1_000_000.0;
+pub const MEDIUM: f64 = 1_000.0;
+pub const WEAK: f64 = 1.0;
+
+const EPSILON: f64 = 1e-8;
+
+/// How an expression compares with zero.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Relation {
+    Equal,
+    AtMost,
+    AtLeast,
+}
+
+/// `sum(coefficient * variable) + constant`, related to zero.
+#[derive(Clone, Debug)]
+pub struct Linear {
+    pub terms: Vec<(usize, f64)>,
+    pub constant: f64,
+    pub relation: Relation,
+    pub strength: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Kind {
+    External,
+    Slack,
+    Error,
+    Dummy,
+}
+
+// Ordered by id, so the tableau is walked the same way every time and equal
+// inputs always give equal answers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Symbol {
+    id: usize,
+    kind: Kind,
+}
+
+// `constant + sum(coefficient * symbol)`; as a tableau row, the value of
+// its basic symbol.
+#[derive(Clone, Debug, Default)]
+struct Row {
+    constant: f64,
+    cells: BTreeMap<Symbol, f64>,
+}
+
+impl Row {
+    fn new(constant: f64) -> Self {
+        Self {
+            constant,
+            cells: BTreeMap::new(),
+        }
+    }
+
+    fn add(&mut self, value: f64) -> f64 {
+        self.constant += value;
+        self.constant
+    }
+
+    fn insert_symbol(&mut self, symbol: Symbol, coefficient: f64) {
+        let cell = self.cells.entry(symbol).or_insert(0.0);
+        *cell += coefficient;
+        if near_zero(*cell) {
+            self.cells.remove(&symbol);
+        }
+    }
+
+    fn insert_row(&mut self, other: &Row, coefficient: f64) {
+        self.constant += other.constant * coefficient;
+        for (symbol, c) in &other.cells {
+            self.insert_symbol(*symbol, c * coefficient);
+        }
+    }
+
+    fn reverse_sign(&mut self) {
+        self.constant = -self.constant;
+        for c in self.cells.values_mut() {
+            *c = -*c;
+        }
+    }
+
+    // rearrange `0 = row` into `symbol = row'`
+    fn solve_for(&mut self, symbol: Symbol) {
+        let coefficient = -1.0 / self.cells.remove(&symbol).unwrap_or(1.0);
+        self.constant *= coefficient;
+        for c in self.cells.values_mut() {
+            *c *= coefficient;
+        }
+    }
+
+    // rearrange `lhs = row` into `rhs = row'`
+    fn solve_for_pair(&mut self, lhs: Symbol, rhs: Symbol) {
+        self.insert_symbol(lhs, -1.0);
+        self.solve_for(rhs);
+    }
+
+    fn coefficient_for(&self, symbol: Symbol) -> f64 {
+        self.cells.get(&symbol).copied().unwrap_or(0.0)
+    }
+
+    fn substitute(&mut self, symbol: Symbol, row: &Row) {
+        if let Some(coefficient) = self.cells.remove(&symbol) {
+            self.insert_row(row, coefficient);
+        }
+    }
+}
+
+// The symbols a constraint added, to find its row again on removal.
+#[derive(Clone, Copy, Debug)]
+struct Tag {
+    marker: Symbol,
+    other: Option<Symbol>,
+}
+
+struct Edit {
+    constraint: usize,
+    constant: f64,
+}
+
+#[derive(Default)]
+pub struct Solver {
+    constraints: BTreeMap<usize, (Tag, f64)>,
+    rows: BTreeMap<Symbol, Row>,
+    variables: BTreeMap<usize, Symbol>,
+    edits: BTreeMap<usize, Edit>,
+    infeasible: Vec<Symbol>,
+    objective: Row,
+    // only while a constraint is added through an artificial variable
+    artificial: Option<Row>,
+    next_symbol: usize,
+    next_constraint: usize,
+}
+
+impl Solver {
+    /// Add a constraint and re-optimize. `None`, with the constraint left
+    /// out, if it is required and conflicts with required ones already in.
+    pub fn add_constraint(&mut self, linear: &Linear) -> Option<usize> {
+        let strength = linear.strength.clamp(0.0, REQUIRED);
+        let (mut row, tag) = self.create_row(linear, strength);
+        match choose_subject(&row, tag) {
+            Some(subject) => {
+                row.solve_for(subject);
+                self.substitute(subject, &row);
+                self.rows.insert(subject, row);
+            }
+            None if row.cells.keys().all(|s| s.kind == Kind::Dummy) => {
+                // nothing but other required constraints: true or not
+                if !near_zero(row.constant) {
+                    return None;
+                }
+                row.solve_for(tag.marker);
+                self.substitute(tag.marker, &row);
+                self.rows.insert(tag.marker, row);
+            }
+            None => {
+                if !self.add_with_artificial_variable(row) {
+                    return None;
+                }
+            }
+        }
+        let id = self.next_constraint;
+        self.next_constraint += 1;
+        self.constraints.insert(id, (tag, strength));
+        self.optimize(false);
+        Some(id)
+    }
+
+    pub fn remove_constraint(&mut self, id: usize) -> bool {
+        let (tag, strength) = match self.constraints.remove(&id) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        // its errors stop counting against the objective
+        for symbol in [Some(tag.marker), tag.other].iter().flatten().copied() {
+            if symbol.kind == Kind::Error {
+                match self.rows.get(&symbol) {
+                    Some(row) => self.objective.insert_row(row, -strength),
+                    None => self.objective.insert_symbol(symbol, -strength),
+                }
+            }
+        }
+        if self.rows.remove(&tag.marker).is_none() {
+            if let Some(leaving) = self.marker_leaving_row(tag.marker) {
+                let mut row = self.rows.remove(&leaving).unwrap();
+                row.solve_for_pair(leaving, tag.marker);
+                self.substitute(tag.marker, &row);
+            }
+        }
+        self.optimize(false);
+        true
+    }
+
+    /// Let `suggest_value` steer `variable`, at a strength below required.
+    pub fn add_edit_variable(&mut self, variable: usize, strength: f64) -> bool {
+        if self.edits.contains_key(&variable) || strength >= REQUIRED {
+            return false;
+        }
+        let linear = Linear {
+            terms: vec![(variable, 1.0)],
+            constant: 0.0,
+            relation: Relation::Equal,
+            strength,
+        };
+        match self.add_constraint(&linear) {
+            Some(constraint) => {
+                self.edits.insert(
+                    variable,
+                    Edit {
+                        constraint,
+                        constant: 0.0,
+                    },
+                );
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn remove_edit_variable(&mut self, variable: usize) -> bool {
+        match self.edits.remove(&variable) {
+            Some(edit) => self.remove_constraint(edit.constraint),
+            None => false,
+        }
+    }
+
+    /// Pull an edit variable towards `value`, moving the others as the
+    /// constraints demand.
+    pub fn suggest_value(&mut self, variable: usize, value: f64) -> bool {
+        let edit = match self.edits.get_mut(&variable) {
+            Some(edit) => edit,
+            None => return false,
+        };
+        let delta = value - edit.constant;
+        edit.constant = value;
+        let (tag, _) = self.constraints[&edit.constraint];
+        let other = tag.other.unwrap_or(tag.marker);
+
+        if let Some(row) = self.rows.get_mut(&tag.marker) {
+            if row.add(-delta) < 0.0 {
+                self.infeasible.push(tag.marker);
+            }
+        } else if let Some(row) = self.rows.get_mut(&other) {
+            if row.add(delta) < 0.0 {
+                self.infeasible.push(other);
+            }
+        } else {
+            for (symbol, row) in self.rows.iter_mut() {
+                let coefficient = row.coefficient_for(tag.marker);
+                if coefficient != 0.0
+                    && row.add(delta * coefficient) < 0.0
+                    && symbol.kind != Kind::External
+                {
+                    self.infeasible.push(*symbol);
+                }
+            }
+        }
+        self.dual_optimize();
+        true
+    }
+
+    /// A variable's value in the current solution, or `None` if no
+    /// constraint mentions it.
+    pub fn value(&self, variable: usize) -> Option<f64> {
+        let symbol = self.variables.get(&variable)?;
+        Some(self.rows.get(symbol).map_or(0.0, |row| row.constant))
+    }
+
+    fn symbol(&mut self, kind: Kind) -> Symbol {
+        self.next_symbol += 1;
+        Symbol {
+            id: self.next_symbol,
+            kind,
+        }
+    }
+
+    // The constraint as a row over the current non-basic symbols, with the
+    // slack, error or dummy symbols its relation and strength call for.
+    fn create_row(&mut self, linear: &Linear, strength: f64) -> (Row, Tag) {
+        let mut row = Row::new(linear.constant);
+        for &(variable, coefficient) in &linear.terms {
+            if near_zero(coefficient) {
+                continue;
+            }
+            let symbol = match self.variables.get(&variable) {
+                Some(symbol) => *symbol,
+                None => {
+                    let symbol = self.symbol(Kind::External);
+                    self.variables.insert(variable, symbol);
+                    symbol
+                }
+            };
+            match self.rows.get(&symbol) {
+                Some(basic) => row.insert_row(basic, coefficient),
+                None => row.insert_symbol(symbol, coefficient),
+            }
+        }
+
+        let tag = match linear.relation {
+            Relation::AtMost | Relation::AtLeast => {
+                let coefficient = if linear.relation == Relation::AtMost {
+                    1.0
+                } else {
+                    -1.0
+                };
+                let slack = self.symbol(Kind::Slack);
+                row.insert_symbol(slack, coefficient);
+                let other = (strength < REQUIRED).then(|| {
+                    let error = self.symbol(Kind::Error);
+                    row.insert_symbol(error, -coefficient);
+                    self.objective.insert_symbol(error, strength);
+                    error
+                });
+                Tag {
+                    marker: slack,
+                    other,
+                }
+            }
+            Relation::Equal if strength < REQUIRED => {
+                let plus = self.symbol(Kind::Error);
+                let minus = self.symbol(Kind::Error);
+                row.insert_symbol(plus, -1.0);
+                row.insert_symbol(minus, 1.0);
+                self.objective.insert_symbol(plus, strength);
+                self.objective.insert_symbol(minus, strength);
+                Tag {
+                    marker: plus,
+                    other: Some(minus),
+                }
+            }
+            Relation::Equal => {
+                let dummy = self.symbol(Kind::Dummy);
+                row.insert_symbol(dummy, 1.0);
+                Tag {
+                    marker: dummy,
+                    other: None,
+                }
+            }
+        };
+        if row.constant < 0.0 {
+            row.reverse_sign();
+        }
+        (row, tag)
+    }
+
+    // Find a feasible basis for a row with no obvious subject by
+    // minimizing an artificial variable standing in for it.
+    fn add_with_artificial_variable(&mut self, row: Row) -> bool {
+        let artificial = self.symbol(Kind::Slack);
+        self.artificial = Some(row.clone());
+        self.rows.insert(artificial, row);
+        self.optimize(true);
+        let success = self
+            .artificial
+            .take()
+            .is_some_and(|row| near_zero(row.constant));
+
+        if let Some(mut row) = self.rows.remove(&artificial) {
+            if row.cells.is_empty() {
+                return success;
+            }
+            let entering = match row
+                .cells
+                .keys()
+                .find(|s| matches!(s.kind, Kind::Slack | Kind::Error))
+            {
+                Some(entering) => *entering,
+                None => return false,
+            };
+            row.solve_for_pair(artificial, entering);
+            self.substitute(entering, &row);
+            self.rows.insert(entering, row);
+        }
+        for row in self.rows.values_mut() {
+            row.cells.remove(&artificial);
+        }
+        self.objective.cells.remove(&artificial);
+        success
+    }
+
+    // Primal simplex on the objective (or the artificial one).
+    fn optimize(&mut self, artificial: bool) -> bool {
+        loop {
+            let objective = match &self.artificial {
+                Some(row) if artificial => row,
+                _ => &self.objective,
+            };
+            let entering = match objective
+                .cells
+                .iter()
+                .find(|(s, c)| s.kind != Kind::Dummy && **c < 0.0)
+            {
+                Some((symbol, _)) => *symbol,
+                None => return true,
+            };
+            // the row that hits zero first as `entering` grows
+            let leaving = self
+                .rows
+                .iter()
+                .filter(|(s, _)| s.kind != Kind::External)
+                .filter_map(|(s, row)| {
+                    let c = row.coefficient_for(entering);
+                    (c < 0.0).then(|| (*s, -row.constant / c))
+                })
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            let leaving = match leaving {
+                Some((symbol, _)) => symbol,
+                // unbounded, which a well-formed objective never is
+                None => return false,
+            };
+            let mut row = self.rows.remove(&leaving).unwrap();
+            row.solve_for_pair(leaving, entering);
+            self.substitute(entering, &row);
+            self.rows.insert(entering, row);
+        }
+    }
+
+    // Dual simplex, restoring feasibility after edits made rows negative.
+    fn dual_optimize(&mut self) -> bool {
+        while let Some(leaving) = self.infeasible.pop() {
+            let entering = match self.rows.get(&leaving) {
+                Some(row) if row.constant < 0.0 && !near_zero(row.constant) => row
+                    .cells
+                    .iter()
+                    .filter(|(s, c)| **c > 0.0 && s.kind != Kind::Dummy)
+                    .map(|(s, c)| (*s, self.objective.coefficient_for(*s) / c))
+                    .min_by(|a, b| a.1.total_cmp(&b.1)),
+                _ => continue,
+            };
+            let entering = match entering {
+                Some((symbol, _)) => symbol,
+                None => return false,
+            };
+            let mut row = self.rows.remove(&leaving).unwrap();
+            row.solve_for_pair(leaving, entering);
+            self.substitute(entering, &row);
+            self.rows.insert(entering, row);
+        }
+        true
+    }
+
+    // Replace `symbol` by `row` everywhere, noting rows that turn negative.
+    fn substitute(&mut self, symbol: Symbol, row: &Row) {
+        for (basic, other) in self.rows.iter_mut() {
+            other.substitute(symbol, row);
+            if basic.kind != Kind::External && other.constant < 0.0 {
+                self.infeasible.push(*basic);
+            }
+        }
+        self.objective.substitute(symbol, row);
+        if let Some(artificial) = &mut self.artificial {
+            artificial.substitute(symbol, row);
+        }
+    }
+
+    // The row to pivot a non-basic marker into: the most restrictive one
+    // that keeps the tableau feasible, else any that mentions it.
+    fn marker_leaving_row(&self, marker: Symbol) -> Option<Symbol> {
+        let mut negative: Option<(Symbol, f64)> = None;
+        let mut positive: Option<(Symbol, f64)> = None;
+        let mut external = None;
+        for (symbol, row) in &self.rows {
+            let c = row.coefficient_for(marker);
+            if c == 0.0 {
+                continue;
+            }
+            if symbol.kind == Kind::External {
+                external = Some(*symbol);
+            } else if c < 0.0 {
+                let ratio = -row.constant / c;
+                if negative.is_none_or(|(_, best)| ratio < best) {
+                    negative = Some((*symbol, ratio));
+                }
+            } else {
+                let ratio = row.constant / c;
+                if positive.is_none_or(|(_, best)| ratio < best) {
+                    positive = Some((*symbol, ratio));
+                }
+            }
+        }
+        negative.or(positive).map(|(symbol, _)| symbol).or(external)
+    }
+}
+
+// The symbol to make basic in a new row: an external variable, else a
+// slack or error of the constraint's own with a negative coefficient.
+fn choose_subject(row: &Row, tag: Tag) -> Option<Symbol> {
+    if let Some(symbol) = row.cells.keys().find(|s| s.kind == Kind::External) {
+        return Some(*symbol);
+    }
+    [Some(tag.marker), tag.other]
+        .iter()
+        .flatten()
+        .copied()
+        .find(|s| matches!(s.kind, Kind::Slack | Kind::Error) && row.coefficient_for(*s) < 0.0)
+}
+
+fn near_zero(value: f64) -> bool {
+    value.abs() < EPSILON
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn linear(terms: &[(usize, f64)], constant: f64, relation: Relation, strength: f64) -> Linear {
+        Linear {
+            terms: terms.to_vec(),
+            constant,
+            relation,
+            strength,
+        }
+    }
+
+    fn close(a: Option<f64>, b: f64) -> bool {
+        a.is_some_and(|a| (a - b).abs() < 1e-6)
+    }
+
+    #[test]
+    fn conflicting_required_constraints_are_refused() {
+        let mut solver = Solver::default();
+        // x == 10 and x == 20
+        let first = linear(&[(0, 1.0)], -10.0, Relation::Equal, REQUIRED);
+        assert!(solver.add_constraint(&first).is_some());
+        let second = linear(&[(0, 1.0)], -20.0, Relation::Equal, REQUIRED);
+        assert!(solver.add_constraint(&second).is_none());
+        assert!(close(solver.value(0), 10.0));
+
+        // x >= 15 can't hold either, but a strong one just loses
+        let at_least = linear(&[(0, 1.0)], -15.0, Relation::AtLeast, REQUIRED);
+        assert!(solver.add_constraint(&at_least).is_none());
+        let strong = linear(&[(0, 1.0)], -15.0, Relation::AtLeast, STRONG);
+        assert!(solver.add_constraint(&strong).is_some());
+        assert!(close(solver.value(0), 10.0));
+    }
+
+    #[test]
+    fn removing_a_constraint_lets_weaker_ones_win() {
+        let mut solver = Solver::default();
+        // x == y + 5, with y pinned strongly at 0 and x weakly at 20
+        let offset = linear(&[(0, 1.0), (1, -1.0)], -5.0, Relation::Equal, REQUIRED);
+        let pin = linear(&[(1, 1.0)], 0.0, Relation::Equal, STRONG);
+        let rest = linear(&[(0, 1.0)], -20.0, Relation::Equal, WEAK);
+        assert!(solver.add_constraint(&offset).is_some());
+        let pin = solver.add_constraint(&pin).unwrap();
+        let rest = solver.add_constraint(&rest).unwrap();
+        assert!(close(solver.value(0), 5.0));
+        assert!(close(solver.value(1), 0.0));
+
+        assert!(solver.remove_constraint(pin));
+        assert!(close(solver.value(0), 20.0));
+        assert!(close(solver.value(1), 15.0));
+        // only once
+        assert!(!solver.remove_constraint(pin));
+
+        assert!(solver.remove_constraint(rest));
+        let x = solver.value(0).unwrap();
+        assert!(close(solver.value(1), x - 5.0));
+    }
+
+    #[test]
+    fn suggested_values_move_dependent_variables() {
+        let mut solver = Solver::default();
+        // y == x + 5, x <= 100
+        let offset = linear(&[(1, 1.0), (0, -1.0)], -5.0, Relation::Equal, REQUIRED);
+        let bound = linear(&[(0, 1.0)], -100.0, Relation::AtMost, REQUIRED);
+        assert!(solver.add_constraint(&offset).is_some());
+        assert!(solver.add_constraint(&bound).is_some());
+
+        assert!(!solver.suggest_value(0, 30.0));
+        assert!(solver.add_edit_variable(0, STRONG));
+        assert!(!solver.add_edit_variable(0, STRONG));
+        assert!(!solver.add_edit_variable(1, REQUIRED));
+
+        assert!(solver.suggest_value(0, 30.0));
+        assert!(close(solver.value(0), 30.0));
+        assert!(close(solver.value(1), 35.0));
+        assert!(solver.suggest_value(0, 40.0));
+        assert!(close(solver.value(1), 45.0));
+        // the required bound beats the suggestion
+        assert!(solver.suggest_value(0, 150.0));
+        assert!(close(solver.value(0), 100.0));
+        assert!(close(solver.value(1), 105.0));
+
+        assert!(solver.remove_edit_variable(0));
+        assert!(!solver.suggest_value(0, 30.0));
+    }
+}
@@ -0,0 +1,570 @@
+// Connectors: lines linking two shapes that stay attached as the shapes
+// move. The editor re-routes every connector from the bounds of the shapes
+// at its ends whenever anything may have moved; the connector itself only
+// turns those rectangles, and any obstacles, into a path.
+
+use vello::kurbo::{
+    self, Affine, BezPath, Circle, ParamCurve, ParamCurveArclen, ParamCurveNearest, Point, Rect,
+    Shape as _, Stroke, StrokeOpts, Vec2,
+};
+use vello::peniko::{Color, Fill};
+use vello::Scene;
+
+use crate::history::ShapeId;
+use crate::node::Node;
+use crate::router;
+use crate::shapes::{IrText, Shape};
+
+const TOLERANCE: f64 = 0.1;
+
+// arrowhead length per unit of line width, and the smallest it gets
+const ARROW_SCALE: f64 = 4.0;
+const MIN_ARROW_LENGTH: f64 = 8.0;
+
+// how far orthogonal routes keep from other shapes, in world units
+const ROUTE_CLEARANCE: f64 = 10.0;
+
+// how far, in world units, a click may miss the line and still hit it
+const HIT_SLOP: f64 = 3.0;
+
+/// Where a connector meets the shape at one of its ends.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Attachment {
+    /// Wherever the connector leaves the shape's bounds on its way to the
+    /// other end.
+    Border,
+    /// A fixed point on the shape's bounds, as fractions of its width and
+    /// height: `Port(0.5, 0.0)` is the middle of the top edge.
+    Port(f64, f64),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Endpoint {
+    pub shape: ShapeId,
+    pub attachment: Attachment,
+}
+
+impl Endpoint {
+    pub fn border(shape: ShapeId) -> Self {
+        Self {
+            shape,
+            attachment: Attachment::Border,
+        }
+    }
+
+    pub fn port(shape: ShapeId, fx: f64, fy: f64) -> Self {
+        Self {
+            shape,
+            attachment: Attachment::Port(fx, fy),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Routing {
+    Straight,
+    // a cubic leaving and entering each shape square to its edge
+    Curved,
+    // horizontal and vertical runs with elbows between
+    Orthogonal,
+}
+
+impl Routing {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "straight" => Some(Routing::Straight),
+            "curved" => Some(Routing::Curved),
+            "orthogonal" | "elbow" => Some(Routing::Orthogonal),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Arrowhead {
+    None,
+    Triangle,
+    Open,
+    Circle,
+}
+
+impl Arrowhead {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "none" => Some(Arrowhead::None),
+            "triangle" | "arrow" => Some(Arrowhead::Triangle),
+            "open" => Some(Arrowhead::Open),
+            "circle" | "dot" => Some(Arrowhead::Circle),
+            _ => None,
+        }
+    }
+
+    // how far the line stops short of the tip, so it doesn't poke through
+    fn inset(self, length: f64) -> f64 {
+        match self {
+            Arrowhead::Triangle => length,
+            Arrowhead::Circle => length / 2.0,
+            Arrowhead::None | Arrowhead::Open => 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Side {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+impl Side {
+    fn normal(self) -> Vec2 {
+        match self {
+            Side::Left => Vec2::new(-1.0, 0.0),
+            Side::Right => Vec2::new(1.0, 0.0),
+            Side::Top => Vec2::new(0.0, -1.0),
+            Side::Bottom => Vec2::new(0.0, 1.0),
+        }
+    }
+
+    // the side of `rect` that `point` is closest to
+    fn nearest(rect: Rect, point: Point) -> Self {
+        [
+            ((point.x - rect.x0).abs(), Side::Left),
+            ((rect.x1 - point.x).abs(), Side::Right),
+            ((point.y - rect.y0).abs(), Side::Top),
+            ((rect.y1 - point.y).abs(), Side::Bottom),
+        ]
+        .iter()
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .unwrap()
+        .1
+    }
+
+    // the side of `rect` facing `toward`, judged relative to its size
+    fn facing(rect: Rect, toward: Point) -> Self {
+        let d = toward - rect.center();
+        if d.x.abs() * rect.height() >= d.y.abs() * rect.width() {
+            if d.x >= 0.0 {
+                Side::Right
+            } else {
+                Side::Left
+            }
+        } else if d.y >= 0.0 {
+            Side::Bottom
+        } else {
+            Side::Top
+        }
+    }
+
+    fn midpoint(self, rect: Rect) -> Point {
+        let c = rect.center();
+        match self {
+            Side::Left => Point::new(rect.x0, c.y),
+            Side::Right => Point::new(rect.x1, c.y),
+            Side::Top => Point::new(c.x, rect.y0),
+            Side::Bottom => Point::new(c.x, rect.y1),
+        }
+    }
+}
+
+// One end of a route: where it touches the shape, and which way it leaves.
+#[derive(Clone, Copy, Debug)]
+struct End {
+    point: Point,
+    direction: Vec2,
+}
+
+/// A line between two shapes, with optional arrowheads and a label halfway
+/// along.
+pub struct IrConnector {
+    // unused for placement, which comes from the shapes at either end
+    pub node: Node,
+    pub source: Endpoint,
+    pub target: Endpoint,
+    pub routing: Routing,
+    pub start_arrow: Arrowhead,
+    pub end_arrow: Arrowhead,
+    pub width: f64,
+    pub color: Color,
+    pub label: Option<IrText>,
+    // the route from the last `reroute`, in the parent's coordinates
+    path: BezPath,
+    // each arrow's tip and the direction it points in
+    tips: Option<[(Point, Vec2); 2]>,
+    // the end shapes' bounds when last routed, to skip unneeded reroutes
+    routed_between: Option<(Rect, Rect)>,
+}
+
+impl IrConnector {
+    pub fn new(source: Endpoint, target: Endpoint, routing: Routing) -> Self {
+        Self {
+            node: Node::new(0.0, 0.0),
+            source,
+            target,
+            routing,
+            start_arrow: Arrowhead::None,
+            end_arrow: Arrowhead::Triangle,
+            width: 2.0,
+            color: Color::BLACK,
+            label: None,
+            path: BezPath::new(),
+            tips: None,
+            routed_between: None,
+        }
+    }
+
+    pub fn path(&self) -> &BezPath {
+        &self.path
+    }
+
+    fn arrow_length(&self) -> f64 {
+        (self.width * ARROW_SCALE).max(MIN_ARROW_LENGTH)
+    }
+
+    /// Route between the bounds of the source and target shapes, given in
+    /// the connector's parent coordinates.
+    pub fn reroute(&mut self, source: Rect, target: Rect) {
+        self.reroute_around(source, target, &[]);
+    }
+
+    /// Like `reroute`, but orthogonal routes also keep clear of
+    /// `obstacles`, which may include the two end shapes.
+    pub fn reroute_around(&mut self, source: Rect, target: Rect, obstacles: &[Rect]) {
+        let (start, end) = self.ends(source, target);
+        let length = self.arrow_length();
+        let from = start.point + start.direction * self.start_arrow.inset(length);
+        let to = end.point + end.direction * self.end_arrow.inset(length);
+
+        let mut path = BezPath::new();
+        path.move_to(from);
+        match self.routing {
+            Routing::Straight => path.line_to(to),
+            Routing::Curved => {
+                let reach = (to - from).hypot() / 2.0;
+                path.curve_to(
+                    from + start.direction * reach,
+                    to + end.direction * reach,
+                    to,
+                );
+            }
+            Routing::Orthogonal if !obstacles.is_empty() => {
+                let points = router::route(
+                    from,
+                    start.direction,
+                    to,
+                    end.direction,
+                    obstacles,
+                    ROUTE_CLEARANCE,
+                );
+                // boxed in: cut straight through rather than vanish
+                let points = points.unwrap_or_else(|| {
+                    let mut points = vec![from];
+                    points.extend(elbows(from, start.direction, to, end.direction));
+                    points.push(to);
+                    points
+                });
+                for point in &points[1..] {
+                    path.line_to(*point);
+                }
+            }
+            Routing::Orthogonal => {
+                for point in elbows(from, start.direction, to, end.direction) {
+                    path.line_to(point);
+                }
+                path.line_to(to);
+            }
+        }
+        self.set_route(path, start, end);
+        self.routed_between = Some((source, target));
+    }
+
+    /// The end shapes' bounds the current route was made for, if any.
+    pub fn routed_between(&self) -> Option<(Rect, Rect)> {
+        self.routed_between
+    }
+
+    /// Whether an obstacle at `rect` could have shaped the current route.
+    pub fn route_passes_near(&self, rect: Rect) -> bool {
+        let points: Vec<Point> = self
+            .path
+            .elements()
+            .iter()
+            .filter_map(|el| el.end_point())
+            .collect();
+        router::passes_near(&points, rect, ROUTE_CLEARANCE * 2.0)
+    }
+
+    // Take a freshly computed route, and move the label to its middle.
+    fn set_route(&mut self, path: BezPath, start: End, end: End) {
+        self.tips = Some([(start.point, -start.direction), (end.point, -end.direction)]);
+        if let (Some(label), Some(middle)) = (self.label.as_mut(), halfway(&path)) {
+            let size = label.layout().bounds.size();
+            label.node.x.set(middle.x - size.width / 2.0);
+            label.node.y.set(middle.y - size.height / 2.0);
+        }
+        self.path = path;
+    }
+
+    /// Forget the route, e.g. because a shape at one end is gone; nothing is
+    /// drawn until the next `reroute`.
+    pub fn clear_route(&mut self) {
+        self.path = BezPath::new();
+        self.tips = None;
+        self.routed_between = None;
+    }
+
+    fn ends(&self, source: Rect, target: Rect) -> (End, End) {
+        // a port is where it is; a border attachment aims at the other end's
+        // port, or its middle
+        let aim = |endpoint: &Endpoint, rect: Rect| match endpoint.attachment {
+            Attachment::Port(fx, fy) => port_point(rect, fx, fy),
+            Attachment::Border => rect.center(),
+        };
+        let source_aim = aim(&self.source, source);
+        let target_aim = aim(&self.target, target);
+        let end = |endpoint: &Endpoint, rect: Rect, toward: Point| match endpoint.attachment {
+            Attachment::Port(fx, fy) => {
+                let point = port_point(rect, fx, fy);
+                let direction = match self.routing {
+                    Routing::Straight => unit(toward - point),
+                    _ => Side::nearest(rect, point).normal(),
+                };
+                End { point, direction }
+            }
+            Attachment::Border if self.routing == Routing::Orthogonal => {
+                let side = Side::facing(rect, toward);
+                End {
+                    point: side.midpoint(rect),
+                    direction: side.normal(),
+                }
+            }
+            Attachment::Border => {
+                let point = border_point(rect, toward);
+                let direction = match self.routing {
+                    Routing::Straight => unit(toward - point),
+                    _ => Side::nearest(rect, point).normal(),
+                };
+                End { point, direction }
+            }
+        };
+        (
+            end(&self.source, source, target_aim),
+            end(&self.target, target, source_aim),
+        )
+    }
+
+    fn draw_arrow(&self, scene: &mut Scene, arrow: Arrowhead, tip: Point, direction: Vec2) {
+        let length = self.arrow_length();
+        let back = tip - direction * length;
+        let side = Vec2::new(-direction.y, direction.x) * (length / 2.0);
+        match arrow {
+            Arrowhead::None => {}
+            Arrowhead::Triangle => {
+                let mut head = BezPath::new();
+                head.move_to(tip);
+                head.line_to(back + side);
+                head.line_to(back - side);
+                head.close_path();
+                scene.fill(Fill::NonZero, Affine::IDENTITY, self.color, None, &head);
+            }
+            Arrowhead::Open => {
+                let mut head = BezPath::new();
+                head.move_to(back + side);
+                head.line_to(tip);
+                head.line_to(back - side);
+                scene.stroke(
+                    &Stroke::new(self.width),
+                    Affine::IDENTITY,
+                    self.color,
+                    None,
+                    &head,
+                );
+            }
+            Arrowhead::Circle => {
+                let radius = length / 4.0;
+                let circle = Circle::new(tip - direction * radius, radius);
+                scene.fill(Fill::NonZero, Affine::IDENTITY, self.color, None, &circle);
+            }
+        }
+    }
+}
+
+fn port_point(rect: Rect, fx: f64, fy: f64) -> Point {
+    Point::new(rect.x0 + fx * rect.width(), rect.y0 + fy * rect.height())
+}
+
+// where the ray from `rect`'s center towards `toward` crosses its edge
+fn border_point(rect: Rect, toward: Point) -> Point {
+    let center = rect.center();
+    let d = toward - center;
+    let scale_x = if d.x != 0.0 {
+        rect.width() / 2.0 / d.x.abs()
+    } else {
+        f64::INFINITY
+    };
+    let scale_y = if d.y != 0.0 {
+        rect.height() / 2.0 / d.y.abs()
+    } else {
+        f64::INFINITY
+    };
+    let scale = scale_x.min(scale_y);
+    if scale.is_finite() {
+        center + d * scale
+    } else {
+        center
+    }
+}
+
+fn unit(v: Vec2) -> Vec2 {
+    let length = v.hypot();
+    if length > 1e-12 {
+        v / length
+    } else {
+        Vec2::new(1.0, 0.0)
+    }
+}
+
+// The corners of an orthogonal route from `from` leaving along `out` to
+// `to` arriving against `into`: one elbow when the ends run at right angles,
+// two meeting halfway otherwise.
+fn elbows(from: Point, out: Vec2, to: Point, into: Vec2) -> Vec<Point> {
+    let horizontal = |v: Vec2| v.x.abs() >= v.y.abs();
+    let corners = match (horizontal(out), horizontal(into)) {
+        (true, true) => {
+            let x = (from.x + to.x) / 2.0;
+            vec![Point::new(x, from.y), Point::new(x, to.y)]
+        }
+        (false, false) => {
+            let y = (from.y + to.y) / 2.0;
+            vec![Point::new(from.x, y), Point::new(to.x, y)]
+        }
+        (true, false) => vec![Point::new(to.x, from.y)],
+        (false, true) => vec![Point::new(from.x, to.y)],
+    };
+    corners
+        .into_iter()
+        .filter(|corner| *corner != from && *corner != to)
+        .collect()
+}
+
+// the point halfway along `path` by length
+fn halfway(path: &BezPath) -> Option<Point> {
+    let segments: Vec<_> = path.segments().collect();
+    let lengths: Vec<f64> = segments.iter().map(|s| s.arclen(TOLERANCE)).collect();
+    let mut remaining = lengths.iter().sum::<f64>() / 2.0;
+    for (segment, length) in segments.iter().zip(&lengths) {
+        if remaining <= *length {
+            let t = segment.inv_arclen(remaining, TOLERANCE);
+            return Some(segment.eval(t));
+        }
+        remaining -= length;
+    }
+    segments.last().map(|segment| segment.end())
+}
+
+impl Shape for IrConnector {
+    fn contains(&self, px: f64, py: f64) -> bool {
+        let point = Point::new(px, py);
+        let reach = self.width / 2.0 + HIT_SLOP;
+        self.path
+            .segments()
+            .any(|segment| segment.nearest(point, TOLERANCE).distance_sq <= reach * reach)
+            || self
+                .label
+                .as_ref()
+                .is_some_and(|label| label.contains(px, py))
+    }
+
+    fn bounds(&self) -> Rect {
+        let mut bounds = match self.tips {
+            Some([(start, _), (end, _)]) => {
+                let pad = self.arrow_length().max(self.width) / 2.0;
+                self.path
+                    .bounding_box()
+                    .union_pt(start)
+                    .union_pt(end)
+                    .inflate(pad, pad)
+            }
+            None => return Rect::ZERO,
+        };
+        if let Some(label) = &self.label {
+            bounds = bounds.union(label.bounds());
+        }
+        bounds
+    }
+
+    fn outline(&self) -> BezPath {
+        kurbo::stroke(
+            &self.path,
+            &Stroke::new(self.width),
+            &StrokeOpts::default(),
+            TOLERANCE,
+        )
+    }
+
+    fn draw(&self, scene: &mut Scene) {
+        let [(start, start_direction), (end, end_direction)] = match self.tips {
+            Some(tips) => tips,
+            None => return,
+        };
+        scene.stroke(
+            &Stroke::new(self.width),
+            Affine::IDENTITY,
+            self.color,
+            None,
+            &self.path,
+        );
+        self.draw_arrow(scene, self.start_arrow, start, start_direction);
+        self.draw_arrow(scene, self.end_arrow, end, end_direction);
+        if let Some(label) = &self.label {
+            label.draw(scene);
+        }
+    }
+
+    fn node(&self) -> &Node {
+        &self.node
+    }
+
+    fn node_mut(&mut self) -> &mut Node {
+        &mut self.node
+    }
+
+    fn duplicate(&self) -> Box<dyn Shape> {
+        Box::new(Self {
+            node: self.node.duplicate(),
+            source: self.source,
+            target: self.target,
+            routing: self.routing,
+            start_arrow: self.start_arrow,
+            end_arrow: self.end_arrow,
+            width: self.width,
+            color: self.color,
+            label: self.label.as_ref().map(|label| {
+                IrText::new(
+                    label.node.x.get(),
+                    label.node.y.get(),
+                    &label.text,
+                    label.font.clone(),
+                    label.size.get(),
+                    label.color,
+                )
+            }),
+            path: self.path.clone(),
+            tips: self.tips,
+            routed_between: self.routed_between,
+        })
+    }
+
+    fn color(&self) -> Option<Color> {
+        Some(self.color)
+    }
+
+    fn as_connector(&self) -> Option<&IrConnector> {
+        Some(self)
+    }
+
+    fn as_connector_mut(&mut self) -> Option<&mut IrConnector> {
+        Some(self)
+    }
+}
@@ -0,0 +1,487 @@
+// Linear relationships between signals, such as shapes kept aligned, evenly
+// spaced or inside a container. `ConstraintSystem` hands them to the
+// Cassowary solver and re-solves whenever a signal they mention changes:
+// what changed is held (strongly) at its new value and everything else moves
+// as little as the constraints allow.
+
+use std::ops::{Add, Mul, Neg, Sub};
+
+use crate::cassowary::{self, Linear, Solver};
+use crate::signal::IrSignal;
+
+pub use crate::cassowary::Relation;
+
+pub type ConstraintId = u32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Strength {
+    Required,
+    Strong,
+    Medium,
+    Weak,
+}
+
+impl Strength {
+    fn weight(self) -> f64 {
+        match self {
+            Strength::Required => cassowary::REQUIRED,
+            Strength::Strong => cassowary::STRONG,
+            Strength::Medium => cassowary::MEDIUM,
+            Strength::Weak => cassowary::WEAK,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Axis {
+    Horizontal,
+    Vertical,
+}
+
+impl Axis {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "horizontal" | "x" => Some(Axis::Horizontal),
+            "vertical" | "y" => Some(Axis::Vertical),
+            _ => None,
+        }
+    }
+}
+
+/// A sum of signals times coefficients, plus a constant.
+#[derive(Clone, Default)]
+pub struct Expression {
+    pub terms: Vec<(IrSignal, f64)>,
+    pub constant: f64,
+}
+
+impl Expression {
+    pub fn value(&self) -> f64 {
+        self.terms
+            .iter()
+            .map(|(signal, coefficient)| signal.get() * coefficient)
+            .sum::<f64>()
+            + self.constant
+    }
+}
+
+impl From<IrSignal> for Expression {
+    fn from(signal: IrSignal) -> Self {
+        Self {
+            terms: vec![(signal, 1.0)],
+            constant: 0.0,
+        }
+    }
+}
+
+impl From<f64> for Expression {
+    fn from(constant: f64) -> Self {
+        Self {
+            terms: Vec::new(),
+            constant,
+        }
+    }
+}
+
+impl<T: Into<Expression>> Add<T> for Expression {
+    type Output = Expression;
+
+    fn add(mut self, other: T) -> Expression {
+        let other = other.into();
+        self.terms.extend(other.terms);
+        self.constant += other.constant;
+        self
+    }
+}
+
+impl<T: Into<Expression>> Sub<T> for Expression {
+    type Output = Expression;
+
+    fn sub(self, other: T) -> Expression {
+        self + -other.into()
+    }
+}
+
+impl Mul<f64> for Expression {
+    type Output = Expression;
+
+    fn mul(mut self, factor: f64) -> Expression {
+        for (_, coefficient) in &mut self.terms {
+            *coefficient *= factor;
+        }
+        self.constant *= factor;
+        self
+    }
+}
+
+impl Neg for Expression {
+    type Output = Expression;
+
+    fn neg(self) -> Expression {
+        self * -1.0
+    }
+}
+
+/// Where a shape starts along one axis and how far it reaches, in its
+/// parent's coordinates; see `Editor::extent`.
+#[derive(Clone)]
+pub struct Extent {
+    pub start: Expression,
+    pub size: Expression,
+}
+
+impl Extent {
+    pub fn end(&self) -> Expression {
+        self.start.clone() + self.size.clone()
+    }
+
+    pub fn center(&self) -> Expression {
+        self.start.clone() + self.size.clone() * 0.5
+    }
+}
+
+#[derive(Clone)]
+struct Part {
+    expression: Expression,
+    relation: Relation,
+    strength: Strength,
+}
+
+/// One or more linear relations that are added and removed together.
+#[derive(Clone)]
+pub struct Constraint {
+    parts: Vec<Part>,
+    // signals held still when the constraint is first applied, so the
+    // others come to them rather than meeting somewhere in between
+    anchor: Vec<IrSignal>,
+    // sizes, which should give way only after positions have
+    stiff: Vec<IrSignal>,
+}
+
+impl Constraint {
+    /// `expression (relation) 0`.
+    pub fn new(expression: Expression, relation: Relation, strength: Strength) -> Self {
+        Self {
+            parts: vec![Part {
+                expression,
+                relation,
+                strength,
+            }],
+            anchor: Vec::new(),
+            stiff: Vec::new(),
+        }
+    }
+
+    /// Several constraints as one, anchored where the first is.
+    pub fn all(constraints: impl IntoIterator<Item = Constraint>) -> Self {
+        let mut all = Self {
+            parts: Vec::new(),
+            anchor: Vec::new(),
+            stiff: Vec::new(),
+        };
+        for constraint in constraints {
+            if all.parts.is_empty() {
+                all.anchor = constraint.anchor;
+            }
+            all.parts.extend(constraint.parts);
+            all.stiff.extend(constraint.stiff);
+        }
+        all
+    }
+
+    // Required relations between consecutive extents, anchored on the first
+    // and with their sizes stiff.
+    fn between<'a>(
+        extents: impl IntoIterator<Item = &'a Extent>,
+        relation: Relation,
+        relate: impl Fn(&Extent, &Extent) -> Expression,
+    ) -> Self {
+        let extents: Vec<&Extent> = extents.into_iter().collect();
+        Self {
+            parts: extents
+                .windows(2)
+                .map(|pair| Part {
+                    expression: relate(pair[0], pair[1]),
+                    relation,
+                    strength: Strength::Required,
+                })
+                .collect(),
+            anchor: extents.first().map_or_else(Vec::new, |first| {
+                signals(&[&first.start, &first.size]).collect()
+            }),
+            stiff: extents
+                .iter()
+                .flat_map(|extent| signals(&[&extent.size]).collect::<Vec<_>>())
+                .collect(),
+        }
+    }
+
+    /// Line up the starts (left or top edges); the rest come to the first.
+    pub fn align_start(extents: &[Extent]) -> Self {
+        Self::between(extents, Relation::Equal, |a, b| {
+            b.start.clone() - a.start.clone()
+        })
+    }
+
+    /// Line up the centers; the rest come to the first.
+    pub fn align_center(extents: &[Extent]) -> Self {
+        Self::between(extents, Relation::Equal, |a, b| b.center() - a.center())
+    }
+
+    /// Give everything the first one's width (or height).
+    pub fn equal_size(extents: &[Extent]) -> Self {
+        Self::between(extents, Relation::Equal, |a, b| {
+            b.size.clone() - a.size.clone()
+        })
+    }
+
+    /// Keep the gaps between consecutive extents equal; the first keeps its
+    /// place.
+    pub fn equal_spacing(extents: &[Extent]) -> Self {
+        let gap = |a: &Extent, b: &Extent| b.start.clone() - a.end();
+        // anchored and stiff like the others, with a part per pair of gaps
+        let mut constraint = Self::between(extents, Relation::Equal, |_, _| 0.0.into());
+        constraint.parts = extents
+            .windows(3)
+            .map(|triple| Part {
+                expression: gap(&triple[1], &triple[2]) - gap(&triple[0], &triple[1]),
+                relation: Relation::Equal,
+                strength: Strength::Required,
+            })
+            .collect();
+        constraint
+    }
+
+    /// Keep `b` starting exactly `distance` after `a` ends.
+    pub fn fixed_distance(a: &Extent, b: &Extent, distance: f64) -> Self {
+        Self::between([a, b], Relation::Equal, |a, b| {
+            b.start.clone() - a.end() - distance
+        })
+    }
+
+    /// Keep `child` within `container`, at least `padding` from its edges.
+    pub fn keep_inside(child: &Extent, container: &Extent, padding: f64) -> Self {
+        let mut constraint = Self::between([container, child], Relation::AtLeast, |k, c| {
+            c.start.clone() - k.start.clone() - padding
+        });
+        constraint.parts.push(Part {
+            expression: child.end() - container.end() + padding,
+            relation: Relation::AtMost,
+            strength: Strength::Required,
+        });
+        constraint
+    }
+
+    /// Every signal the constraint relates, possibly more than once.
+    pub fn signals(&self) -> impl Iterator<Item = IrSignal> + '_ {
+        self.parts
+            .iter()
+            .flat_map(|part| part.expression.terms.iter().map(|(signal, _)| *signal))
+    }
+}
+
+fn signals<'a>(expressions: &'a [&'a Expression]) -> impl Iterator<Item = IrSignal> + 'a {
+    expressions
+        .iter()
+        .flat_map(|expression| expression.terms.iter().map(|(signal, _)| *signal))
+}
+
+struct Entry {
+    id: ConstraintId,
+    constraint: Constraint,
+    // the solver's ids for its parts
+    parts: Vec<usize>,
+}
+
+// A signal as a solver variable, held at its value by an edit.
+struct Variable {
+    signal: IrSignal,
+    // what the last solve left it at
+    value: f64,
+    // how hard the edit holds it now, and when it hasn't just changed
+    strength: f64,
+    rest: f64,
+}
+
+/// Constraints between signals, kept satisfied by `solve`.
+#[derive(Default)]
+pub struct ConstraintSystem {
+    solver: Solver,
+    // indexed by solver variable
+    variables: Vec<Variable>,
+    entries: Vec<Entry>,
+    next_id: ConstraintId,
+}
+
+impl ConstraintSystem {
+    /// Add a constraint and move its signals to satisfy it, the anchor
+    /// staying put. `None` if it contradicts required constraints already
+    /// in place.
+    pub fn add(&mut self, constraint: Constraint) -> Option<ConstraintId> {
+        self.solve();
+        for signal in constraint.signals() {
+            self.variable(signal);
+        }
+        for signal in &constraint.stiff {
+            if let Some(variable) = index_of(&self.variables, *signal) {
+                self.variables[variable].rest = cassowary::MEDIUM;
+            }
+        }
+        let mut parts = Vec::new();
+        for part in &constraint.parts {
+            match self.solver.add_constraint(&linear(&self.variables, part)) {
+                Some(id) => parts.push(id),
+                None => {
+                    // a failed addition can leave the tableau half changed
+                    self.rebuild();
+                    return None;
+                }
+            }
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        let anchor: Vec<usize> = constraint
+            .anchor
+            .iter()
+            .filter_map(|signal| index_of(&self.variables, *signal))
+            .collect();
+        self.entries.push(Entry {
+            id,
+            constraint,
+            parts,
+        });
+        self.solve_holding(|variable| anchor.contains(&variable));
+        Some(id)
+    }
+
+    pub fn remove(&mut self, id: ConstraintId) -> bool {
+        let index = match self.entries.iter().position(|entry| entry.id == id) {
+            Some(index) => index,
+            None => return false,
+        };
+        let entry = self.entries.remove(index);
+        for part in entry.parts {
+            self.solver.remove_constraint(part);
+        }
+        // sizes it stiffened give way as easily as before, unless another
+        // constraint stiffens them too
+        for signal in &entry.constraint.stiff {
+            let still_stiff = self.entries.iter().any(|other| {
+                other
+                    .constraint
+                    .stiff
+                    .iter()
+                    .any(|stiff| stiff.set == signal.set)
+            });
+            if let (false, Some(variable)) = (still_stiff, index_of(&self.variables, *signal)) {
+                self.variables[variable].rest = cassowary::WEAK;
+            }
+        }
+        let changed = self.changed();
+        self.solve_holding(|variable| changed[variable]);
+        true
+    }
+
+    /// Re-solve if any constrained signal changed since the last solve,
+    /// holding the changed ones. Returns true if other signals moved.
+    pub fn solve(&mut self) -> bool {
+        let changed = self.changed();
+        if !changed.contains(&true) {
+            return false;
+        }
+        self.solve_holding(|variable| changed[variable])
+    }
+
+    // per variable, whether its signal was set since the last solve
+    fn changed(&self) -> Vec<bool> {
+        self.variables
+            .iter()
+            .map(|variable| variable.signal.get() != variable.value)
+            .collect()
+    }
+
+    fn solve_holding(&mut self, held: impl Fn(usize) -> bool) -> bool {
+        for (index, variable) in self.variables.iter_mut().enumerate() {
+            let strength = if held(index) {
+                cassowary::STRONG
+            } else {
+                variable.rest
+            };
+            if strength != variable.strength {
+                self.solver.remove_edit_variable(index);
+                self.solver.add_edit_variable(index, strength);
+                variable.strength = strength;
+            }
+            self.solver.suggest_value(index, variable.signal.get());
+        }
+        let mut moved = false;
+        for (index, variable) in self.variables.iter_mut().enumerate() {
+            if let Some(solved) = self.solver.value(index) {
+                if (solved - variable.signal.get()).abs() > 1e-9 {
+                    variable.signal.set(solved);
+                    moved = true;
+                }
+            }
+            variable.value = variable.signal.get();
+        }
+        moved
+    }
+
+    // Start over from the signals' current values, e.g. after the solver
+    // refused a constraint.
+    fn rebuild(&mut self) {
+        self.solver = Solver::default();
+        for (index, variable) in self.variables.iter_mut().enumerate() {
+            variable.strength = variable.rest;
+            self.solver.add_edit_variable(index, variable.strength);
+            self.solver.suggest_value(index, variable.signal.get());
+        }
+        let (solver, variables) = (&mut self.solver, &self.variables);
+        for entry in &mut self.entries {
+            entry.parts = entry
+                .constraint
+                .parts
+                .iter()
+                .filter_map(|part| solver.add_constraint(&linear(variables, part)))
+                .collect();
+        }
+    }
+
+    // the variable standing for `signal`, held weakly at its value if new
+    fn variable(&mut self, signal: IrSignal) -> usize {
+        if let Some(index) = index_of(&self.variables, signal) {
+            return index;
+        }
+        let index = self.variables.len();
+        let value = signal.get();
+        self.variables.push(Variable {
+            signal,
+            value,
+            strength: cassowary::WEAK,
+            rest: cassowary::WEAK,
+        });
+        self.solver.add_edit_variable(index, cassowary::WEAK);
+        self.solver.suggest_value(index, value);
+        index
+    }
+}
+
+fn index_of(variables: &[Variable], signal: IrSignal) -> Option<usize> {
+    variables
+        .iter()
+        .position(|variable| variable.signal.set == signal.set)
+}
+
+fn linear(variables: &[Variable], part: &Part) -> Linear {
+    Linear {
+        terms: part
+            .expression
+            .terms
+            .iter()
+            .filter_map(|(signal, coefficient)| Some((index_of(variables, *signal)?, *coefficient)))
+            .collect(),
+        constant: part.expression.constant,
+        relation: part.relation,
+        strength: part.strength.weight(),
+    }
+}
@@ -0,0 +1,2311 @@
+// The document and interaction state behind a `VelloContext`, kept free of
+// any web or GPU types so it can be driven from native code and tests.
+
+use std::collections::{HashMap, HashSet};
+
+use vello::kurbo::{Affine, BezPath, Line, Point, Rect, Stroke, Vec2};
+use vello::peniko::{Color, Fill, Font, Mix};
+use vello::Scene;
+
+use crate::animation::{Animation, AnimationId, Animator, Easing};
+use crate::arrange::{self, Alignment};
+use crate::boolean::{self, BooleanOp};
+use crate::connector::{IrConnector, Routing};
+use crate::constraints::{Axis, Constraint, ConstraintId, ConstraintSystem, Expression, Extent};
+use crate::freehand::BrushSettings;
+use crate::geometry;
+use crate::graph_layout::{Graph, GraphLayout};
+use crate::history::{Edit, History, SignalChange};
+use crate::input::{
+    GestureRecognizer, Modifiers, PointerEvent, PointerPhase, PointerType, PRIMARY_BUTTON,
+};
+use crate::keymap::{Action, KeyChord, Keymap};
+use crate::layout::{Container, Layout};
+use crate::path_edit::{self, EditablePath, Handle, PathPoint, PointKind};
+use crate::physics::{Physics, SpringConfig};
+use crate::shapes::{IrGroup, IrPath, IrText, Shape};
+use crate::signal::IrSignal;
+use crate::snapping::{self, SnapBypass, SnapSettings};
+use crate::timeline::Timeline;
+use crate::tools::{self, Tool};
+
+pub use crate::history::ShapeId;
+
+// pointer id used for the legacy `handle_mouse_*` entry points
+const MOUSE_POINTER_ID: i32 = 1;
+
+const MIN_ZOOM: f64 = 0.1;
+const MAX_ZOOM: f64 = 10.0;
+
+// how far a duplicate is offset from its original, in world units
+const DUPLICATE_OFFSET: f64 = 10.0;
+
+// a release later than this after the last move is a stop, not a throw
+const THROW_WINDOW: f64 = 0.05;
+// slowest release, in world units per second, that still carries momentum
+const MIN_THROW_SPEED: f64 = 50.0;
+
+// outline around the group being edited
+const ENTERED_GROUP_COLOR: Color = Color::from_rgba8(0, 150, 255, 160);
+
+// how close, in canvas pixels, the pointer must come to grab a path's point
+const PATH_HIT_RADIUS: f64 = 6.0;
+
+// A pointer that went down on a shape and is dragging the selection.
+struct PointerDrag {
+    shapes: Vec<ShapeId>,
+    // positions when the drag started; the drag sets absolute positions from
+    // these so snapping never accumulates, and they become the undo step
+    start: Vec<(f64, f64)>,
+    // union of the dragged shapes' bounds when the drag started
+    start_bounds: Rect,
+    // where the pointer went down, in world coordinates
+    origin: Point,
+    // the two most recent (time, world position) samples, for release velocity
+    samples: [(f64, Point); 2],
+    // whether the pointer has moved since going down; if not, it's a click
+    moved: bool,
+}
+
+// Node editing of one `IrPath`. Its anchors live here while it is edited,
+// and are written back to the shape on every change.
+struct PathEditing {
+    id: ShapeId,
+    path: EditablePath,
+    selected: Option<PathPoint>,
+    drag: Option<PathDrag>,
+}
+
+struct PathDrag {
+    pointer_id: i32,
+    point: PathPoint,
+    handle: Handle,
+    // the outline before the drag, for undo
+    start: BezPath,
+}
+
+/// What a key press did, so the host knows whether to swallow the event.
+#[derive(Debug, PartialEq)]
+pub struct KeyOutcome {
+    pub action: Action,
+    pub changed: bool,
+}
+
+/// Something the page may want to react to, collected as it happens and
+/// handed out by `Editor::take_events`. Positions are world coordinates.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EditorEvent {
+    /// The pointer went down and up on a shape without moving.
+    Click { shape: ShapeId, position: Point },
+    /// The selection started moving under the pointer.
+    DragStart {
+        shapes: Vec<ShapeId>,
+        position: Point,
+    },
+    /// The pointer let go of dragged shapes.
+    DragEnd {
+        shapes: Vec<ShapeId>,
+        position: Point,
+    },
+    /// The selection is now `shapes`, primary last.
+    Select { shapes: Vec<ShapeId> },
+    /// An edit was made, undone or redone.
+    Change,
+}
+
+impl EditorEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EditorEvent::Click { .. } => "click",
+            EditorEvent::DragStart { .. } => "drag_start",
+            EditorEvent::DragEnd { .. } => "drag_end",
+            EditorEvent::Select { .. } => "select",
+            EditorEvent::Change => "change",
+        }
+    }
+}
+
+pub struct Editor {
+    // removed shapes leave a `None` behind so ids stay stable for handles,
+    // followers and undo; so do shapes inside a group, which the group owns
+    // and which are found through `groups`
+    shapes: Vec<Option<Box<dyn Shape>>>,
+    // ids of each group's children, in the same order as `IrGroup::children`
+    groups: HashMap<ShapeId, Vec<ShapeId>>,
+    // the other way round: each child's group and its index there. Only
+    // `set_children` and `clear_children` touch either map
+    parents: HashMap<ShapeId, (ShapeId, usize)>,
+    // the group whose children are being edited, after a double-click
+    entered_group: Option<ShapeId>,
+    // the path whose points are being edited, after a double-click
+    path_editing: Option<PathEditing>,
+    selection: Vec<ShapeId>,
+    drags: HashMap<i32, PointerDrag>,
+    gestures: GestureRecognizer,
+    zoom: f64,
+    pan: Vec2,
+    history: History,
+    keymap: Keymap,
+    pressed_keys: HashSet<String>,
+    snapping: SnapSettings,
+    // smart guides from the drag in progress, in world coordinates
+    smart_guides: Vec<Line>,
+    animator: Animator,
+    physics: Physics,
+    // followers that chase their target on a spring instead of sticking to it
+    spring_followers: HashMap<ShapeId, SpringConfig>,
+    // friction for shapes thrown by a drag; `None` stops them dead on release
+    drag_momentum: Option<f64>,
+    timeline: Timeline,
+    brush: BrushSettings,
+    // font and size for new text
+    text_style: Option<(Font, f64)>,
+    // world bounds of the shapes connectors were last routed around
+    obstacles: Vec<(ShapeId, Rect)>,
+    constraints: ConstraintSystem,
+    // events not yet taken, and the selection and history revision last
+    // reported, which "select" and "change" events are worked out against
+    events: Vec<EditorEvent>,
+    reported_selection: Vec<ShapeId>,
+    reported_revision: u64,
+    // tools by name; the active one is taken out of the table while it runs
+    tools: HashMap<String, Box<dyn Tool>>,
+    active_tool: String,
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Editor {
+    pub fn new() -> Self {
+        Self {
+            shapes: Vec::new(),
+            groups: HashMap::new(),
+            parents: HashMap::new(),
+            entered_group: None,
+            path_editing: None,
+            selection: Vec::new(),
+            drags: HashMap::new(),
+            gestures: GestureRecognizer::default(),
+            zoom: 1.0,
+            pan: Vec2::ZERO,
+            history: History::default(),
+            keymap: Keymap::default(),
+            pressed_keys: HashSet::new(),
+            snapping: SnapSettings::default(),
+            smart_guides: Vec::new(),
+            animator: Animator::default(),
+            physics: Physics::default(),
+            spring_followers: HashMap::new(),
+            drag_momentum: None,
+            timeline: Timeline::new(),
+            brush: BrushSettings::default(),
+            text_style: None,
+            obstacles: Vec::new(),
+            constraints: ConstraintSystem::default(),
+            events: Vec::new(),
+            reported_selection: Vec::new(),
+            reported_revision: 0,
+            tools: tools::built_in()
+                .into_iter()
+                .map(|(name, tool)| (name.to_string(), tool))
+                .collect(),
+            active_tool: "select".to_string(),
+        }
+    }
+
+    pub fn add_shape(&mut self, shape: Box<dyn Shape>) -> ShapeId {
+        self.shapes.push(Some(shape));
+        let id = self.shapes.len() - 1;
+        self.register_children(id);
+        id
+    }
+
+    // give the children of a group (and theirs) ids of their own
+    fn register_children(&mut self, id: ShapeId) {
+        let count = match self.shape(id).and_then(|shape| shape.as_group()) {
+            Some(group) => group.children.len(),
+            None => return,
+        };
+        let children: Vec<ShapeId> = (0..count)
+            .map(|_| {
+                self.shapes.push(None);
+                self.shapes.len() - 1
+            })
+            .collect();
+        self.set_children(id, children.clone());
+        for child in children {
+            self.register_children(child);
+        }
+    }
+
+    pub fn shape(&self, id: ShapeId) -> Option<&dyn Shape> {
+        if let Some(Some(shape)) = self.shapes.get(id) {
+            return Some(shape.as_ref());
+        }
+        let (group, index) = self.parent_of(id)?;
+        self.shape(group)?
+            .as_group()?
+            .children
+            .get(index)
+            .map(|child| child.as_ref())
+    }
+
+    pub fn shape_mut(&mut self, id: ShapeId) -> Option<&mut dyn Shape> {
+        // matching rather than mapping lets the trait objects coerce
+        if let Some(Some(_)) = self.shapes.get(id) {
+            return match &mut self.shapes[id] {
+                Some(shape) => Some(shape.as_mut()),
+                None => None,
+            };
+        }
+        let (group, index) = self.parent_of(id)?;
+        match self
+            .shape_mut(group)?
+            .as_group_mut()?
+            .children
+            .get_mut(index)
+        {
+            Some(child) => Some(child.as_mut()),
+            None => None,
+        }
+    }
+
+    /// The group a shape belongs to, `None` for top-level shapes.
+    pub fn parent(&self, id: ShapeId) -> Option<ShapeId> {
+        self.parent_of(id).map(|(group, _)| group)
+    }
+
+    fn parent_of(&self, id: ShapeId) -> Option<(ShapeId, usize)> {
+        self.parents.get(&id).copied()
+    }
+
+    fn set_children(&mut self, group: ShapeId, children: Vec<ShapeId>) {
+        self.clear_children(group);
+        for (index, child) in children.iter().enumerate() {
+            self.parents.insert(*child, (group, index));
+        }
+        self.groups.insert(group, children);
+    }
+
+    fn clear_children(&mut self, group: ShapeId) {
+        for child in self.groups.remove(&group).into_iter().flatten() {
+            self.parents.remove(&child);
+        }
+    }
+
+    fn is_ancestor(&self, ancestor: ShapeId, id: ShapeId) -> bool {
+        let mut current = id;
+        while let Some(parent) = self.parent(current) {
+            if parent == ancestor {
+                return true;
+            }
+            current = parent;
+        }
+        false
+    }
+
+    /// Maps a shape's coordinates to world coordinates: identity for
+    /// top-level shapes, the enclosing groups' transforms otherwise.
+    pub fn parent_transform(&self, id: ShapeId) -> Affine {
+        let mut transform = Affine::IDENTITY;
+        let mut current = id;
+        while let Some(parent) = self.parent(current) {
+            if let Some(group) = self.shape(parent).and_then(|shape| shape.as_group()) {
+                transform = group.transform() * transform;
+            }
+            current = parent;
+        }
+        transform
+    }
+
+    /// A shape's bounding box in world coordinates.
+    pub fn world_bounds(&self, id: ShapeId) -> Option<Rect> {
+        let bounds = self.shape(id)?.bounds();
+        Some(self.parent_transform(id).transform_rect_bbox(bounds))
+    }
+
+    /// A shape's outline in world coordinates.
+    pub fn world_outline(&self, id: ShapeId) -> Option<BezPath> {
+        Some(self.parent_transform(id) * self.shape(id)?.outline())
+    }
+
+    /// Shapes whose world bounds lie wholly inside `rect`, as a marquee
+    /// picks them: the entered group's children, or top-level shapes.
+    pub fn shapes_in_rect(&self, rect: Rect) -> Vec<ShapeId> {
+        self.queryable()
+            .into_iter()
+            .filter(|id| {
+                self.world_bounds(*id)
+                    .is_some_and(|bounds| rect.intersect(bounds) == bounds)
+            })
+            .collect()
+    }
+
+    /// Shapes whose outline crosses `path`, a lasso in world coordinates, or
+    /// that overlap the area it encloses.
+    pub fn shapes_intersecting_path(&self, path: &BezPath) -> Vec<ShapeId> {
+        self.queryable()
+            .into_iter()
+            .filter(|id| {
+                self.world_outline(*id)
+                    .is_some_and(|outline| geometry::outlines_touch(&outline, path))
+            })
+            .collect()
+    }
+
+    /// How far a world point is from the edge of a shape, inside or out.
+    pub fn distance_to_outline(&self, id: ShapeId, point: Point) -> Option<f64> {
+        let outline = self.world_outline(id)?;
+        Some(geometry::distance_to_outline(&outline, point))
+    }
+
+    /// The shape closest to a world point, no further than `max_distance`,
+    /// with its distance; shapes containing the point are 0 away. Ties go to
+    /// the shape drawn on top.
+    pub fn nearest_shape(&self, point: Point, max_distance: f64) -> Option<(ShapeId, f64)> {
+        let mut nearest = None;
+        for id in self.queryable() {
+            let local = self.parent_transform(id).inverse() * point;
+            let distance = match self.shape(id) {
+                Some(shape) if shape.contains(local.x, local.y) => 0.0,
+                Some(_) => self.distance_to_outline(id, point).unwrap(),
+                None => continue,
+            };
+            if distance <= nearest.map_or(max_distance, |(_, closest)| closest) {
+                nearest = Some((id, distance));
+            }
+        }
+        nearest
+    }
+
+    // the shapes region and nearest-shape queries look at
+    fn queryable(&self) -> Vec<ShapeId> {
+        match self.entered_group.and_then(|group| self.groups.get(&group)) {
+            Some(children) => children.clone(),
+            None => self.shape_ids().collect(),
+        }
+    }
+
+    // a world-space offset as seen from inside the shape's parent
+    fn to_local(&self, id: ShapeId, delta: Vec2) -> Vec2 {
+        let inverse = self.parent_transform(id).inverse();
+        inverse * (Point::ORIGIN + delta) - inverse * Point::ORIGIN
+    }
+
+    /// Ids of all live top-level shapes, in drawing order.
+    pub fn shape_ids(&self) -> impl Iterator<Item = ShapeId> + '_ {
+        self.shapes
+            .iter()
+            .enumerate()
+            .filter(|(_, shape)| shape.is_some())
+            .map(|(id, _)| id)
+    }
+
+    /// The shape under a world point: a child of the entered group if one is
+    /// hit, otherwise a top-level shape.
+    pub fn shape_at(&self, x: f64, y: f64) -> Option<ShapeId> {
+        if let Some(group) = self.entered_group {
+            let to_group = self.parent_transform(group)
+                * self
+                    .shape(group)
+                    .and_then(|shape| shape.as_group())
+                    .map_or(Affine::IDENTITY, IrGroup::transform);
+            let local = to_group.inverse() * Point::new(x, y);
+            let hit = self.groups.get(&group).and_then(|children| {
+                children.iter().copied().find(|child| {
+                    self.shape(*child)
+                        .is_some_and(|shape| shape.contains(local.x, local.y))
+                })
+            });
+            if hit.is_some() {
+                return hit;
+            }
+        }
+        self.shapes
+            .iter()
+            .position(|shape| shape.as_ref().is_some_and(|s| s.contains(x, y)))
+    }
+
+    /// Events since the last call, oldest first, ending with "select" if
+    /// the selection changed and "change" if the document did.
+    pub fn take_events(&mut self) -> Vec<EditorEvent> {
+        if self.selection != self.reported_selection {
+            self.reported_selection = self.selection.clone();
+            self.events.push(EditorEvent::Select {
+                shapes: self.selection.clone(),
+            });
+        }
+        if self.history.revision() != self.reported_revision {
+            self.reported_revision = self.history.revision();
+            self.events.push(EditorEvent::Change);
+        }
+        std::mem::take(&mut self.events)
+    }
+
+    /// The most recently selected shape.
+    pub fn selected_shape(&self) -> Option<ShapeId> {
+        self.selection.last().copied()
+    }
+
+    pub fn selection(&self) -> &[ShapeId] {
+        &self.selection
+    }
+
+    pub fn set_selection(&mut self, ids: Vec<ShapeId>) {
+        self.selection = ids
+            .into_iter()
+            .filter(|id| self.shape(*id).is_some())
+            .collect();
+    }
+
+    /// Group the selected top-level shapes into a new `IrGroup` and select
+    /// it. The group is placed at the top-left of their bounds, so nothing
+    /// moves on screen, and is drawn above all other shapes.
+    pub fn group_selection(&mut self) -> Option<ShapeId> {
+        let (group, edits) = self.group_selected()?;
+        self.history.push(Edit::Batch(edits));
+        Some(group)
+    }
+
+    // Group the selection as `group_selection` does, returning the edits for
+    // the caller to record along with its own.
+    fn group_selected(&mut self) -> Option<(ShapeId, Vec<Edit>)> {
+        let children: Vec<ShapeId> = self
+            .shape_ids()
+            .filter(|id| self.selection.contains(id))
+            .collect();
+        let bounds = children
+            .iter()
+            .filter_map(|id| self.world_bounds(*id))
+            .reduce(|a, b| a.union(b))?;
+
+        // children are stored relative to the group's origin
+        let mut changes = Vec::new();
+        for id in &children {
+            let node = self.shape(*id).unwrap().node().clone();
+            self.stop_motion(node.x);
+            self.stop_motion(node.y);
+            for (signal, origin) in [(node.x, bounds.x0), (node.y, bounds.y0)] {
+                let from = signal.get();
+                signal.set(from - origin);
+                changes.push(SignalChange {
+                    signal,
+                    from,
+                    to: from - origin,
+                });
+            }
+        }
+
+        self.shapes.push(None);
+        let group = self.shapes.len() - 1;
+        let mut parked: Option<Box<dyn Shape>> =
+            Some(Box::new(IrGroup::new(bounds.x0, bounds.y0, Vec::new())));
+        self.set_grouped(group, &children, &mut parked, true);
+        self.selection = vec![group];
+        Some((
+            group,
+            vec![
+                Edit::Set(changes),
+                Edit::Group {
+                    group,
+                    children,
+                    parked,
+                },
+            ],
+        ))
+    }
+
+    /// Dissolve the selected top-level groups, keeping their children where
+    /// they appear on screen, and select the children. A group that is
+    /// rotated and scaled unevenly would skew children turned at an angle
+    /// to its axes, which a node can't express, so such groups are kept.
+    pub fn ungroup(&mut self) -> Vec<ShapeId> {
+        let groups: Vec<ShapeId> = self
+            .shape_ids()
+            .filter(|id| self.selection.contains(id) && self.groups.contains_key(id))
+            .collect();
+        if groups.is_empty() {
+            return Vec::new();
+        }
+
+        let mut changes = Vec::new();
+        let mut edits = Vec::new();
+        let mut released = Vec::new();
+        for group in groups {
+            let shape = self.shape(group).unwrap();
+            let group_node = shape.node().clone();
+            let transform = shape.as_group().unwrap().transform();
+            let children = self.groups[&group].clone();
+            let placed: Option<Vec<_>> = children
+                .iter()
+                .map(|child| {
+                    let node = self.shape(*child).unwrap().node().clone();
+                    let local = Affine::translate((node.x.get(), node.y.get()))
+                        * Affine::rotate(node.rotation.get())
+                        * Affine::scale_non_uniform(node.scale_x.get(), node.scale_y.get());
+                    let placement = decompose(
+                        transform * local,
+                        node.rotation.get() + group_node.rotation.get(),
+                        node.scale_x.get() * group_node.scale_x.get(),
+                    )?;
+                    Some((node, placement))
+                })
+                .collect();
+            let placed = match placed {
+                Some(placed) => placed,
+                None => continue,
+            };
+            for (node, (position, rotation, scale_x, scale_y)) in placed {
+                for (signal, to) in [
+                    (node.x, position.x),
+                    (node.y, position.y),
+                    (node.rotation, rotation),
+                    (node.scale_x, scale_x),
+                    (node.scale_y, scale_y),
+                ] {
+                    self.stop_motion(signal);
+                    let from = signal.get();
+                    if to != from {
+                        signal.set(to);
+                        changes.push(SignalChange { signal, from, to });
+                    }
+                }
+            }
+            let mut parked = None;
+            self.set_grouped(group, &children, &mut parked, false);
+            released.extend_from_slice(&children);
+            edits.push(Edit::Ungroup {
+                group,
+                children,
+                parked,
+            });
+        }
+        if edits.is_empty() {
+            return Vec::new();
+        }
+        edits.insert(0, Edit::Set(changes));
+        self.history.push(Edit::Batch(edits));
+        self.selection = released.clone();
+        self.update_followers();
+        released
+    }
+
+    // Move `children` between their own slots and the group in slot `group`.
+    // The emptied group waits in `parked` while the children are ungrouped.
+    fn set_grouped(
+        &mut self,
+        group: ShapeId,
+        children: &[ShapeId],
+        parked: &mut Option<Box<dyn Shape>>,
+        grouped: bool,
+    ) {
+        if grouped {
+            let mut shell = match parked.take() {
+                Some(shell) => shell,
+                None => return,
+            };
+            if let Some(shell) = shell.as_group_mut() {
+                for id in children {
+                    if let Some(child) = self.shapes[*id].take() {
+                        shell.children.push(child);
+                    }
+                }
+            }
+            self.shapes[group] = Some(shell);
+            self.set_children(group, children.to_vec());
+            self.selection.retain(|id| !children.contains(id));
+        } else {
+            let mut shell = match self.shapes[group].take() {
+                Some(shell) => shell,
+                None => return,
+            };
+            if let Some(shell) = shell.as_group_mut() {
+                for (id, child) in children.iter().zip(shell.children.drain(..)) {
+                    self.shapes[*id] = Some(child);
+                }
+            }
+            *parked = Some(shell);
+            self.clear_children(group);
+            self.selection.retain(|id| *id != group);
+            self.forget_removed();
+        }
+    }
+
+    /// Group the selected shapes and clip them with the bottom-most one.
+    pub fn mask_selection(&mut self) -> Option<ShapeId> {
+        let (group, mut edits) = self.group_selected()?;
+        if let Some(group) = self.shape_mut(group).and_then(|shape| shape.as_group_mut()) {
+            group.mask = Some(0);
+        }
+        edits.push(Edit::Mask {
+            group,
+            from: None,
+            to: Some(0),
+        });
+        self.history.push(Edit::Batch(edits));
+        Some(group)
+    }
+
+    /// Clip the other children of `group` with `mask`, which must be one of
+    /// them, or stop clipping with `None`.
+    pub fn set_mask(&mut self, group: ShapeId, mask: Option<ShapeId>) -> bool {
+        let index = match mask.map(|mask| self.parent_of(mask)) {
+            Some(Some((parent, index))) if parent == group => Some(index),
+            Some(_) => return false,
+            None => None,
+        };
+        let from = match self.shape_mut(group).and_then(|shape| shape.as_group_mut()) {
+            Some(group) => std::mem::replace(&mut group.mask, index),
+            None => return false,
+        };
+        if from != index {
+            self.history.push(Edit::Mask {
+                group,
+                from,
+                to: index,
+            });
+        }
+        true
+    }
+
+    /// Group the selected shapes into a stack or grid, sized to fit them
+    /// without wrapping. Resizing it later lays them out again.
+    pub fn contain_selection(&mut self, layout: Layout) -> Option<ShapeId> {
+        let (group, mut edits) = self.group_selected()?;
+        let (nodes, sizes): (Vec<_>, Vec<_>) = self.groups[&group]
+            .iter()
+            .filter_map(|id| self.shape(*id))
+            .map(|shape| (shape.node().clone(), shape.bounds().size()))
+            .unzip();
+        let size = layout.fit(&sizes);
+        if let Some(group) = self.shape_mut(group).and_then(|shape| shape.as_group_mut()) {
+            group.container = Some(Container::new(layout, size));
+        }
+        edits.push(Edit::Contain {
+            group,
+            parked: None,
+        });
+
+        // the layout moves the children into place
+        let before: Vec<(IrSignal, f64)> = nodes
+            .iter()
+            .flat_map(|node| [(node.x, node.x.get()), (node.y, node.y.get())])
+            .collect();
+        self.update_followers();
+        let moves: Vec<SignalChange> = before
+            .into_iter()
+            .filter(|(signal, from)| signal.get() != *from)
+            .map(|(signal, from)| SignalChange {
+                signal,
+                from,
+                to: signal.get(),
+            })
+            .collect();
+        if !moves.is_empty() {
+            edits.push(Edit::Set(moves));
+        }
+        self.history.push(Edit::Batch(edits));
+        Some(group)
+    }
+
+    /// Change how a stack or grid lays out its children. Fails for plain
+    /// groups and other shapes.
+    pub fn set_layout(&mut self, container: ShapeId, layout: Layout) -> bool {
+        let from = match self
+            .shape_mut(container)
+            .and_then(|shape| shape.as_group_mut())
+            .and_then(|group| group.container.as_mut())
+        {
+            Some(container) => std::mem::replace(&mut container.layout, layout),
+            None => return false,
+        };
+        if from != layout {
+            self.history.push(Edit::Layout {
+                container,
+                from,
+                to: layout,
+            });
+        }
+        self.update_followers();
+        true
+    }
+
+    /// How a group is composited onto what is below it.
+    pub fn set_blend_mode(&mut self, group: ShapeId, blend: Mix) -> bool {
+        let from = match self.shape_mut(group).and_then(|shape| shape.as_group_mut()) {
+            Some(group) => std::mem::replace(&mut group.blend, blend),
+            None => return false,
+        };
+        if from != blend {
+            self.history.push(Edit::Blend {
+                group,
+                from,
+                to: blend,
+            });
+        }
+        true
+    }
+
+    /// Edit the children of `group` directly, as after a double-click.
+    pub fn enter_group(&mut self, group: ShapeId) -> bool {
+        if !self.groups.contains_key(&group) || self.shape(group).is_none() {
+            return false;
+        }
+        self.entered_group = Some(group);
+        self.selection.clear();
+        true
+    }
+
+    /// Leave the entered group for its parent, selecting it.
+    pub fn exit_group(&mut self) -> bool {
+        match self.entered_group {
+            Some(group) => {
+                self.entered_group = self.parent(group);
+                self.selection = vec![group];
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn entered_group(&self) -> Option<ShapeId> {
+        self.entered_group
+    }
+
+    // drop the entered group and the edited path once they are no longer in
+    // the document
+    fn forget_removed(&mut self) {
+        if let Some(group) = self.entered_group {
+            if self.shape(group).is_none() || !self.groups.contains_key(&group) {
+                self.entered_group = None;
+            }
+        }
+        if let Some(editing) = &self.path_editing {
+            if self.shape(editing.id).is_none() {
+                self.path_editing = None;
+            }
+        }
+        // connectors to a removed shape stop being drawn
+        self.update_connectors();
+    }
+
+    /// Enter the group or start editing the path under a canvas point; on
+    /// the outline of the path being edited, insert a point instead.
+    /// Returns true if anything changed.
+    pub fn handle_double_click(&mut self, x: f64, y: f64) -> bool {
+        if self.insert_path_point(x, y) {
+            return true;
+        }
+        let world = self.screen_to_world(Point::new(x, y));
+        match self.shape_at(world.x, world.y) {
+            Some(id) if self.groups.contains_key(&id) => self.enter_group(id),
+            Some(id) if Some(id) != self.editing_path() => self.enter_path_editing(id),
+            _ => false,
+        }
+    }
+
+    /// Show the anchors and handles of a path so they can be dragged.
+    pub fn enter_path_editing(&mut self, id: ShapeId) -> bool {
+        let path = match self.shape(id).and_then(|shape| shape.as_path()) {
+            Some(shape) => EditablePath::from_path(&shape.path),
+            None => return false,
+        };
+        self.path_editing = Some(PathEditing {
+            id,
+            path,
+            selected: None,
+            drag: None,
+        });
+        self.selection = vec![id];
+        true
+    }
+
+    pub fn exit_path_editing(&mut self) -> bool {
+        self.path_editing.take().is_some()
+    }
+
+    pub fn editing_path(&self) -> Option<ShapeId> {
+        self.path_editing.as_ref().map(|editing| editing.id)
+    }
+
+    /// The anchors of the path being edited, in its own coordinates.
+    pub fn editable_path(&self) -> Option<&EditablePath> {
+        self.path_editing.as_ref().map(|editing| &editing.path)
+    }
+
+    pub fn selected_path_point(&self) -> Option<PathPoint> {
+        self.path_editing.as_ref()?.selected
+    }
+
+    pub fn select_path_point(&mut self, point: Option<PathPoint>) -> bool {
+        match &mut self.path_editing {
+            Some(editing) if point.is_none_or(|at| editing.path.anchor(at).is_some()) => {
+                editing.selected = point;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Split the edited path's segment under a canvas point without
+    /// changing its outline, and select the new point.
+    pub fn insert_path_point(&mut self, x: f64, y: f64) -> bool {
+        let to_screen = match self.editing_path() {
+            Some(id) => self.view_transform() * self.path_transform(id),
+            None => return false,
+        };
+        let point = Point::new(x, y);
+        self.edit_path(|editing| {
+            // double-clicking a point shouldn't stack another on top of it
+            if editing
+                .path
+                .hit(to_screen, point, PATH_HIT_RADIUS)
+                .is_some()
+            {
+                return false;
+            }
+            let inserted = editing
+                .path
+                .nearest_segment(to_screen, point, PATH_HIT_RADIUS)
+                .and_then(|(segment, t)| editing.path.insert_point(segment, t));
+            editing.selected = inserted.or(editing.selected);
+            inserted.is_some()
+        })
+    }
+
+    /// Remove the selected point of the edited path. The last points of a
+    /// path stay; delete the shape instead.
+    pub fn delete_path_point(&mut self) -> bool {
+        self.edit_path(|editing| {
+            let point = match editing.selected {
+                Some(point) => point,
+                None => return false,
+            };
+            let mut path = editing.path.clone();
+            if !path.delete_point(point) || path.contours.is_empty() {
+                return false;
+            }
+            editing.path = path;
+            editing.selected = None;
+            true
+        })
+    }
+
+    /// Make the selected point a corner, smooth or symmetric.
+    pub fn set_path_point_kind(&mut self, kind: PointKind) -> bool {
+        self.edit_path(|editing| match editing.selected {
+            Some(point) => editing.path.set_kind(point, kind),
+            None => false,
+        })
+    }
+
+    // Change the edited path's anchors and write the result back to the
+    // shape as one undo step.
+    fn edit_path(&mut self, edit: impl FnOnce(&mut PathEditing) -> bool) -> bool {
+        let id = match self.editing_path() {
+            Some(id) => id,
+            None => return false,
+        };
+        let from = match self.shape(id).and_then(|shape| shape.as_path()) {
+            Some(shape) => shape.path.clone(),
+            None => return false,
+        };
+        let editing = self.path_editing.as_mut().unwrap();
+        if !edit(editing) {
+            return false;
+        }
+        let to = editing.path.to_path();
+        if to != from {
+            self.set_path(id, to.clone());
+            self.history.push(Edit::Path { id, from, to });
+        }
+        true
+    }
+
+    fn set_path(&mut self, id: ShapeId, path: BezPath) {
+        if let Some(shape) = self.shape_mut(id).and_then(|shape| shape.as_path_mut()) {
+            shape.path = path;
+        }
+    }
+
+    // maps a path's own coordinates to world coordinates
+    fn path_transform(&self, id: ShapeId) -> Affine {
+        self.parent_transform(id)
+            * self
+                .shape(id)
+                .and_then(|shape| shape.as_path())
+                .map_or(Affine::IDENTITY, IrPath::transform)
+    }
+
+    pub fn snapping(&self) -> &SnapSettings {
+        &self.snapping
+    }
+
+    pub fn snapping_mut(&mut self) -> &mut SnapSettings {
+        &mut self.snapping
+    }
+
+    /// Add a tool, or replace the one with the same name. Replacing the
+    /// active tool deactivates the old one.
+    pub fn register_tool(&mut self, name: &str, tool: Box<dyn Tool>) {
+        if name == self.active_tool {
+            self.with_active_tool(|tool, editor| tool.deactivate(editor));
+        }
+        self.tools.insert(name.to_string(), tool);
+    }
+
+    /// Make the named tool active. Returns false if there is no such tool.
+    pub fn set_tool(&mut self, name: &str) -> bool {
+        if !self.tools.contains_key(name) {
+            return false;
+        }
+        if name != self.active_tool {
+            self.with_active_tool(|tool, editor| tool.deactivate(editor));
+            self.active_tool = name.to_string();
+        }
+        true
+    }
+
+    pub fn active_tool(&self) -> &str {
+        &self.active_tool
+    }
+
+    // Take the active tool out of the table so it can borrow the editor.
+    fn with_active_tool<T>(
+        &mut self,
+        run: impl FnOnce(&mut dyn Tool, &mut Self) -> T,
+    ) -> Option<T> {
+        let name = self.active_tool.clone();
+        let mut tool = self.tools.remove(&name)?;
+        let result = run(tool.as_mut(), self);
+        // unless the tool registered a replacement for itself meanwhile
+        self.tools.entry(name).or_insert(tool);
+        Some(result)
+    }
+
+    /// Font and size, in world units, for new text.
+    pub fn set_text_style(&mut self, font: Font, size: f64) {
+        self.text_style = Some((font, size));
+    }
+
+    pub fn text_style(&self) -> Option<(&Font, f64)> {
+        self.text_style.as_ref().map(|(font, size)| (font, *size))
+    }
+
+    pub fn brush(&self) -> &BrushSettings {
+        &self.brush
+    }
+
+    pub fn brush_mut(&mut self) -> &mut BrushSettings {
+        &mut self.brush
+    }
+
+    pub fn keymap_mut(&mut self) -> &mut Keymap {
+        &mut self.keymap
+    }
+
+    pub fn zoom(&self) -> f64 {
+        self.zoom
+    }
+
+    pub fn pan(&self) -> Vec2 {
+        self.pan
+    }
+
+    /// Scroll the view by `delta` canvas pixels.
+    pub fn pan_by(&mut self, delta: Vec2) {
+        self.pan += delta;
+    }
+
+    /// Maps world (document) coordinates to canvas pixels.
+    pub fn view_transform(&self) -> Affine {
+        Affine::translate(self.pan) * Affine::scale(self.zoom)
+    }
+
+    pub fn screen_to_world(&self, point: Point) -> Point {
+        Point::new(
+            (point.x - self.pan.x) / self.zoom,
+            (point.y - self.pan.y) / self.zoom,
+        )
+    }
+
+    /// Zoom by `factor` keeping the world point under `anchor` (in canvas
+    /// pixels) fixed, then move that point to `to`.
+    pub fn zoom_about(&mut self, anchor: Point, to: Point, factor: f64) {
+        let world = self.screen_to_world(anchor);
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.pan = to.to_vec2() - world.to_vec2() * self.zoom;
+    }
+
+    /// Returns false if either id doesn't refer to a live shape.
+    pub fn follow(&mut self, follower: ShapeId, target: ShapeId) -> bool {
+        if follower == target {
+            return false;
+        }
+        // nodes only hold signal handles, so a clone still tracks the target
+        let target_node = match self.shape(target) {
+            Some(shape) => shape.node().clone(),
+            None => return false,
+        };
+        match self.shape_mut(follower) {
+            Some(shape) => {
+                shape.node_mut().start_following(&target_node, target);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Like `follow`, but the follower lags behind on a spring.
+    pub fn follow_with_spring(
+        &mut self,
+        follower: ShapeId,
+        target: ShapeId,
+        config: SpringConfig,
+    ) -> bool {
+        if !self.follow(follower, target) {
+            return false;
+        }
+        self.spring_followers.insert(follower, config);
+        true
+    }
+
+    pub fn unfollow(&mut self, id: ShapeId) -> bool {
+        self.spring_followers.remove(&id);
+        match self.shape_mut(id) {
+            Some(shape) => {
+                shape.node_mut().unfollow();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Where a shape sits along `axis`, in its parent's coordinates, in terms
+    /// of its signals: position plus "width"/"height" or "radius" where it
+    /// has them, otherwise its current bounds' size.
+    pub fn extent(&self, id: ShapeId, axis: Axis) -> Option<Extent> {
+        let shape = self.shape(id)?;
+        let bounds = shape.bounds();
+        let (position, start, size, name) = match axis {
+            Axis::Horizontal => (shape.node().x, bounds.x0, bounds.width(), "width"),
+            Axis::Vertical => (shape.node().y, bounds.y0, bounds.height(), "height"),
+        };
+        Some(match (shape.signal(name), shape.signal("radius")) {
+            (None, Some(radius)) => Extent {
+                start: Expression::from(position) - radius,
+                size: Expression::from(radius) * 2.0,
+            },
+            (signal, _) => Extent {
+                start: Expression::from(position) + (start - position.get()),
+                size: signal.map_or_else(|| size.into(), Expression::from),
+            },
+        })
+    }
+
+    /// Keep a constraint between shape signals from now on, moving shapes to
+    /// satisfy it straight away. `None` if it contradicts one already kept.
+    /// The shapes it moves are recorded as an undo step; the constraint
+    /// itself stays until removed.
+    pub fn add_constraint(&mut self, constraint: Constraint) -> Option<ConstraintId> {
+        let mut before: Vec<(IrSignal, f64)> = Vec::new();
+        for signal in constraint.signals() {
+            if !before.iter().any(|(seen, _)| seen.set == signal.set) {
+                before.push((signal, signal.get()));
+            }
+        }
+        let id = self.constraints.add(constraint)?;
+        let changes: Vec<SignalChange> = before
+            .into_iter()
+            .filter(|(signal, from)| signal.get() != *from)
+            .map(|(signal, from)| SignalChange {
+                signal,
+                from,
+                to: signal.get(),
+            })
+            .collect();
+        if !changes.is_empty() {
+            self.history.push(Edit::Set(changes));
+        }
+        self.update_followers();
+        Some(id)
+    }
+
+    /// Stop keeping a constraint; shapes stay where they are.
+    pub fn remove_constraint(&mut self, id: ConstraintId) -> bool {
+        self.constraints.remove(id)
+    }
+
+    /// Re-solve constraints whose signals changed, lay out stacks and grids,
+    /// re-apply every `Node::following` relationship, in shape order, then
+    /// re-route connectors. Spring followers get a new target instead of
+    /// being moved.
+    pub fn update_followers(&mut self) {
+        self.constraints.solve();
+        // inner containers first: children overflowing one change its bounds
+        let mut groups: Vec<(usize, ShapeId)> = self
+            .groups
+            .keys()
+            .map(|group| {
+                let depth = std::iter::successors(Some(*group), |id| self.parent(*id)).count();
+                (depth, *group)
+            })
+            .collect();
+        groups.sort_unstable_by(|a, b| b.cmp(a));
+        for (_, group) in groups {
+            if let Some(group) = self.shape(group).and_then(|shape| shape.as_group()) {
+                group.lay_out();
+            }
+        }
+        for idx in 0..self.shapes.len() {
+            let target = match self.shape(idx).and_then(|s| s.node().following) {
+                Some((target, _)) => target,
+                None => continue,
+            };
+            let target_node = match self.shape(target).map(|s| s.node().clone()) {
+                Some(node) => node,
+                None => continue,
+            };
+            if let Some(config) = self.spring_followers.get(&idx).copied() {
+                let node = self.shape(idx).unwrap().node().clone();
+                if let Some((x, y)) = node.followed_position(&target_node) {
+                    self.physics.spring_to(node.x, x, config);
+                    self.physics.spring_to(node.y, y, config);
+                    node.rotation.set(target_node.rotation.get());
+                    node.scale_x.set(target_node.scale_x.get());
+                    node.scale_y.set(target_node.scale_y.get());
+                }
+            } else if let Some(shape) = self.shape_mut(idx) {
+                shape.node_mut().update_transform(&target_node);
+            }
+        }
+        self.update_connectors();
+    }
+
+    /// Link two shapes with a connector. Returns `None` if either end isn't
+    /// a live shape.
+    pub fn connect(&mut self, connector: IrConnector) -> Option<ShapeId> {
+        self.shape(connector.source.shape)?;
+        self.shape(connector.target.shape)?;
+        let id = self.add_shape(Box::new(connector));
+        self.update_connectors();
+        Some(id)
+    }
+
+    /// Change a connector's ends, routing or style, then re-route it.
+    pub fn update_connector(&mut self, id: ShapeId, edit: impl FnOnce(&mut IrConnector)) -> bool {
+        match self
+            .shape_mut(id)
+            .and_then(|shape| shape.as_connector_mut())
+        {
+            Some(connector) => {
+                edit(connector);
+                connector.clear_route();
+            }
+            None => return false,
+        }
+        self.update_connectors();
+        true
+    }
+
+    /// Label a connector halfway along in the text style, or remove its
+    /// label with an empty `text`. Fails without a text style.
+    pub fn set_connector_label(&mut self, id: ShapeId, text: &str) -> bool {
+        let label = match self.text_style() {
+            _ if text.is_empty() => None,
+            Some((font, size)) => Some(IrText::new(
+                0.0,
+                0.0,
+                text,
+                font.clone(),
+                size,
+                self.brush.color,
+            )),
+            None => return false,
+        };
+        self.update_connector(id, |connector| connector.label = label)
+    }
+
+    /// Route every connector between the current bounds of the shapes at
+    /// its ends, orthogonal ones around the other top-level shapes.
+    /// Connectors missing a shape aren't drawn. An orthogonal route is only
+    /// worked out again when its ends or a shape close to it moved.
+    pub fn update_connectors(&mut self) {
+        let obstacles: Vec<(ShapeId, Rect)> = self
+            .shape_ids()
+            .filter(|id| self.shape(*id).and_then(|s| s.as_connector()).is_none())
+            .filter_map(|id| Some((id, self.world_bounds(id)?)))
+            .collect();
+        // where shapes that moved, came or went since last time were and are
+        let previous: HashMap<ShapeId, Rect> = self.obstacles.drain(..).collect();
+        let mut moved = Vec::new();
+        for (id, bounds) in &obstacles {
+            match previous.get(id) {
+                Some(before) if before == bounds => {}
+                Some(before) => moved.extend([*before, *bounds]),
+                None => moved.push(*bounds),
+            }
+        }
+        moved.extend(
+            previous
+                .iter()
+                .filter(|(id, _)| !obstacles.iter().any(|(other, _)| other == *id))
+                .map(|(_, bounds)| *bounds),
+        );
+
+        for id in 0..self.shapes.len() {
+            let (source_id, target_id, routing) =
+                match self.shape(id).and_then(|shape| shape.as_connector()) {
+                    Some(connector) => (
+                        connector.source.shape,
+                        connector.target.shape,
+                        connector.routing,
+                    ),
+                    None => continue,
+                };
+            let to_parent = self.parent_transform(id).inverse();
+            let source = self.world_bounds(source_id);
+            let target = self.world_bounds(target_id);
+            // groups holding an end, or the connector, can't be routed around
+            let others: Vec<Rect> = if routing == Routing::Orthogonal {
+                obstacles
+                    .iter()
+                    .filter(|(other, _)| {
+                        ![source_id, target_id, id]
+                            .iter()
+                            .any(|inner| self.is_ancestor(*other, *inner))
+                    })
+                    .map(|(_, bounds)| to_parent.transform_rect_bbox(*bounds))
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            let connector = self
+                .shape_mut(id)
+                .and_then(|shape| shape.as_connector_mut())
+                .unwrap();
+            let (source, target) = match (source, target) {
+                (Some(source), Some(target)) => (
+                    to_parent.transform_rect_bbox(source),
+                    to_parent.transform_rect_bbox(target),
+                ),
+                _ => {
+                    connector.clear_route();
+                    continue;
+                }
+            };
+            let unchanged = connector.routed_between() == Some((source, target))
+                && !moved.iter().any(|bounds| {
+                    connector.route_passes_near(to_parent.transform_rect_bbox(*bounds))
+                });
+            if routing != Routing::Orthogonal || !unchanged {
+                connector.reroute_around(source, target, &others);
+            }
+        }
+        self.obstacles = obstacles;
+    }
+
+    /// Pull a shape property towards `target` on a spring.
+    pub fn spring_to(
+        &mut self,
+        id: ShapeId,
+        property: &str,
+        target: f64,
+        config: SpringConfig,
+    ) -> bool {
+        match self.shape(id).and_then(|shape| shape.signal(property)) {
+            Some(signal) => {
+                self.animator.stop_signal(signal);
+                self.physics.spring_to(signal, target, config);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Set a named property (see `Shape::signal`), e.g. a group's "opacity",
+    /// recording an undo step.
+    pub fn set_property(&mut self, id: ShapeId, property: &str, value: f64) -> bool {
+        let signal = match self.shape(id).and_then(|shape| shape.signal(property)) {
+            Some(signal) => signal,
+            None => return false,
+        };
+        self.stop_motion(signal);
+        let from = signal.get();
+        signal.set(value);
+        self.history.push(Edit::Set(vec![SignalChange {
+            signal,
+            from,
+            to: value,
+        }]));
+        self.update_followers();
+        true
+    }
+
+    /// Friction applied to shapes flung by a drag (higher stops sooner);
+    /// `None` turns momentum off.
+    pub fn set_drag_momentum(&mut self, friction: Option<f64>) {
+        self.drag_momentum = friction.filter(|f| *f > 0.0);
+    }
+
+    pub fn animate(&mut self, animation: Animation) -> AnimationId {
+        self.animator.play(animation)
+    }
+
+    pub fn stop_animation(&mut self, id: AnimationId) -> bool {
+        self.animator.stop(id)
+    }
+
+    pub fn animator_mut(&mut self) -> &mut Animator {
+        &mut self.animator
+    }
+
+    pub fn timeline(&self) -> &Timeline {
+        &self.timeline
+    }
+
+    pub fn timeline_mut(&mut self) -> &mut Timeline {
+        &mut self.timeline
+    }
+
+    /// Move the timeline's playhead to `time` (seconds) and update followers.
+    pub fn seek_timeline(&mut self, time: f64) {
+        self.timeline.seek(time);
+        self.update_followers();
+    }
+
+    pub fn is_animating(&self) -> bool {
+        self.animator.is_active() || self.physics.is_active() || self.timeline.is_playing()
+    }
+
+    /// Advance animations and physics to `now` (seconds). Returns true while
+    /// anything is still moving.
+    pub fn tick(&mut self, now: f64) -> bool {
+        if !self.is_animating() {
+            return false;
+        }
+        self.timeline.advance(now);
+        self.animator.update(now);
+        self.physics.update(now);
+        self.update_followers();
+        self.is_animating()
+    }
+
+    // stop anything else driving a signal the user is taking over
+    fn stop_motion(&mut self, signal: IrSignal) {
+        self.animator.stop_signal(signal);
+        self.physics.stop_signal(signal);
+    }
+
+    /// Move shapes by `delta`, recording a single undo step.
+    pub fn translate(&mut self, ids: &[ShapeId], delta: Vec2) -> bool {
+        self.move_each(ids.iter().map(|id| (*id, delta)))
+    }
+
+    /// Line shapes up along the outermost edge, or the middle, of their
+    /// bounds, as a single undo step.
+    pub fn align(&mut self, ids: &[ShapeId], alignment: Alignment) -> bool {
+        let (ids, bounds) = self.arrangeable(ids);
+        if ids.len() < 2 {
+            return false;
+        }
+        let offsets = arrange::align(&bounds, alignment);
+        self.move_each(
+            ids.into_iter()
+                .zip(offsets)
+                .filter(|(_, d)| *d != Vec2::ZERO),
+        )
+    }
+
+    /// Space shapes out along `axis`, `spacing` apart or, without one,
+    /// evenly between the first and last, as a single undo step.
+    pub fn distribute(&mut self, ids: &[ShapeId], axis: Axis, spacing: Option<f64>) -> bool {
+        let (ids, bounds) = self.arrangeable(ids);
+        if ids.len() < 2 {
+            return false;
+        }
+        let offsets = arrange::distribute(&bounds, axis, spacing);
+        self.move_each(
+            ids.into_iter()
+                .zip(offsets)
+                .filter(|(_, d)| *d != Vec2::ZERO),
+        )
+    }
+
+    // The live shapes among `ids`, once each, with their world bounds;
+    // connectors are left out since they go wherever their ends are.
+    fn arrangeable(&self, ids: &[ShapeId]) -> (Vec<ShapeId>, Vec<Rect>) {
+        let mut shapes = Vec::new();
+        let mut bounds = Vec::new();
+        for id in ids {
+            if shapes.contains(id) {
+                continue;
+            }
+            match (self.shape(*id), self.world_bounds(*id)) {
+                (Some(shape), Some(rect)) if shape.as_connector().is_none() => {
+                    shapes.push(*id);
+                    bounds.push(rect);
+                }
+                _ => {}
+            }
+        }
+        (shapes, bounds)
+    }
+
+    // move each shape by its own world-space offset as one undo step
+    fn move_each(&mut self, moves: impl IntoIterator<Item = (ShapeId, Vec2)>) -> bool {
+        let mut changes = Vec::new();
+        for (id, delta) in moves {
+            let delta = self.to_local(id, delta);
+            if let Some(shape) = self.shape(id) {
+                let node = shape.node();
+                for (signal, d) in [(node.x, delta.x), (node.y, delta.y)] {
+                    let from = signal.get();
+                    signal.set(from + d);
+                    changes.push(SignalChange {
+                        signal,
+                        from,
+                        to: from + d,
+                    });
+                }
+            }
+        }
+        if changes.is_empty() {
+            return false;
+        }
+        self.history.push(Edit::Set(changes));
+        self.update_followers();
+        true
+    }
+
+    /// Arrange shapes as a graph whose edges are the connectors between
+    /// them, recording a single undo step. With a `transition` (seconds and
+    /// easing) the shapes glide to their places; otherwise they jump.
+    pub fn layout_graph(
+        &mut self,
+        ids: &[ShapeId],
+        layout: &GraphLayout,
+        transition: Option<(f64, Easing)>,
+    ) -> bool {
+        let (nodes, bounds) = self.arrangeable(ids);
+        if nodes.is_empty() {
+            return false;
+        }
+        let index = |id: ShapeId| nodes.iter().position(|node| *node == id);
+        let edges = (0..self.shapes.len())
+            .filter_map(|id| self.shape(id)?.as_connector())
+            .filter_map(|connector| {
+                Some((
+                    index(connector.source.shape)?,
+                    index(connector.target.shape)?,
+                ))
+            })
+            .collect();
+        let graph = Graph {
+            sizes: bounds.iter().map(|rect| rect.size()).collect(),
+            edges,
+        };
+        let current: Vec<Point> = bounds.iter().map(|rect| rect.center()).collect();
+        let centers = layout.apply(&graph, &current);
+
+        let mut changes = Vec::new();
+        for ((id, before), after) in nodes.iter().zip(current).zip(centers) {
+            let delta = self.to_local(*id, after - before);
+            let node = self.shape(*id).unwrap().node();
+            for (signal, d) in [(node.x, delta.x), (node.y, delta.y)] {
+                let from = signal.get();
+                changes.push(SignalChange {
+                    signal,
+                    from,
+                    to: from + d,
+                });
+            }
+        }
+        for change in &changes {
+            self.stop_motion(change.signal);
+        }
+        match transition {
+            Some((duration, easing)) if duration > 0.0 => {
+                let tracks = changes
+                    .iter()
+                    .flat_map(|change| {
+                        Animation::tween(change.signal, change.to, duration, easing).tracks
+                    })
+                    .collect();
+                self.animate(Animation::new(tracks));
+            }
+            _ => {
+                for change in &changes {
+                    change.signal.set(change.to);
+                }
+            }
+        }
+        self.history.push(Edit::Set(changes));
+        self.update_followers();
+        true
+    }
+
+    /// Add a shape as one undo step.
+    pub fn create_shape(&mut self, shape: Box<dyn Shape>) -> ShapeId {
+        let id = self.add_shape(shape);
+        self.history.push(Edit::Insert {
+            ids: vec![id],
+            parked: Vec::new(),
+        });
+        id
+    }
+
+    /// Record typing into a text shape, whose text was `before`, as one
+    /// undo step.
+    pub fn record_text_change(&mut self, id: ShapeId, before: String) -> bool {
+        let after = match self.shape(id).and_then(|shape| shape.as_text()) {
+            Some(shape) => shape.text.clone(),
+            None => return false,
+        };
+        if after == before {
+            return false;
+        }
+        self.history.push(Edit::Text {
+            id,
+            from: before,
+            to: after,
+        });
+        true
+    }
+
+    /// Remove top-level shapes, recording a single undo step. Shapes inside
+    /// a group stay put; ungroup them first.
+    pub fn remove(&mut self, ids: &[ShapeId]) -> bool {
+        let mut removed = Vec::new();
+        let mut parked = Vec::new();
+        for id in ids {
+            if let Some(shape) = self.shapes.get_mut(*id).and_then(Option::take) {
+                removed.push(*id);
+                parked.push(shape);
+            }
+        }
+        if removed.is_empty() {
+            return false;
+        }
+        self.selection.retain(|id| !removed.contains(id));
+        self.drags
+            .retain(|_, drag| !drag.shapes.iter().any(|id| removed.contains(id)));
+        self.forget_removed();
+        self.history.push(Edit::Remove {
+            ids: removed,
+            parked,
+        });
+        true
+    }
+
+    /// Copy shapes, offset slightly, and select the copies. Copies are always
+    /// top-level, even when copied out of a group.
+    pub fn duplicate(&mut self, ids: &[ShapeId]) -> Vec<ShapeId> {
+        let copies: Vec<_> = ids
+            .iter()
+            .filter_map(|id| {
+                let shape = self.shape(*id)?;
+                Some((shape.duplicate(), self.parent_transform(*id)))
+            })
+            .collect();
+        let mut new_ids = Vec::new();
+        for (copy, transform) in copies {
+            let node = copy.node();
+            let position = transform * Point::new(node.x.get(), node.y.get());
+            node.x.set(position.x + DUPLICATE_OFFSET);
+            node.y.set(position.y + DUPLICATE_OFFSET);
+            new_ids.push(self.add_shape(copy));
+        }
+        if !new_ids.is_empty() {
+            self.history.push(Edit::Insert {
+                ids: new_ids.clone(),
+                parked: Vec::new(),
+            });
+            self.selection = new_ids.clone();
+        }
+        new_ids
+    }
+
+    /// Replace top-level shapes with the result of `op` applied across their
+    /// outlines in drawing order, as a single undo step. Subtract cuts every
+    /// later shape out of the bottom-most one. The result takes the bottom
+    /// shape's color and is selected; nothing changes if it would be empty.
+    pub fn combine(&mut self, ids: &[ShapeId], op: BooleanOp) -> Option<ShapeId> {
+        let operands: Vec<ShapeId> = self.shape_ids().filter(|id| ids.contains(id)).collect();
+        if operands.len() < 2 {
+            return None;
+        }
+
+        let first = self.shape(operands[0])?;
+        let color = first.color().unwrap_or(Color::BLACK);
+        let mut path = first.outline();
+        let mut fill = first.fill_rule();
+        for id in &operands[1..] {
+            let shape = self.shape(*id)?;
+            path = boolean::combine(&path, fill, &shape.outline(), shape.fill_rule(), op);
+            // the result's loops are oriented, so both rules agree
+            fill = Fill::NonZero;
+        }
+        if path.elements().is_empty() {
+            return None;
+        }
+
+        let mut parked = Vec::new();
+        for id in &operands {
+            if let Some(shape) = self.shapes[*id].take() {
+                parked.push(shape);
+            }
+        }
+        self.selection.retain(|id| !operands.contains(id));
+        self.drags
+            .retain(|_, drag| !drag.shapes.iter().any(|id| operands.contains(id)));
+        self.forget_removed();
+
+        let result = self.add_shape(Box::new(IrPath::from_outline(path, fill, color)));
+        self.history.push(Edit::Batch(vec![
+            Edit::Remove {
+                ids: operands,
+                parked,
+            },
+            Edit::Insert {
+                ids: vec![result],
+                parked: Vec::new(),
+            },
+        ]));
+        self.selection = vec![result];
+        Some(result)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.history.can_undo()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.history.can_redo()
+    }
+
+    pub fn undo(&mut self) -> bool {
+        match self.history.pop_undo() {
+            Some(mut edit) => {
+                self.apply_edit(&mut edit, false);
+                self.history.push_undone(edit);
+                self.update_followers();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn redo(&mut self) -> bool {
+        match self.history.pop_redo() {
+            Some(mut edit) => {
+                self.apply_edit(&mut edit, true);
+                self.history.push_redone(edit);
+                self.update_followers();
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Re-apply (`forward`) or revert an edit that was recorded earlier.
+    fn apply_edit(&mut self, edit: &mut Edit, forward: bool) {
+        match edit {
+            Edit::Set(changes) => {
+                for change in changes.iter() {
+                    self.stop_motion(change.signal);
+                    change
+                        .signal
+                        .set(if forward { change.to } else { change.from });
+                }
+            }
+            Edit::Insert { ids, parked } => self.set_present(ids, parked, forward),
+            Edit::Remove { ids, parked } => self.set_present(ids, parked, !forward),
+            Edit::Group {
+                group,
+                children,
+                parked,
+            } => self.set_grouped(*group, children, parked, forward),
+            Edit::Ungroup {
+                group,
+                children,
+                parked,
+            } => self.set_grouped(*group, children, parked, !forward),
+            Edit::Path { id, from, to } => {
+                let path = if forward { to.clone() } else { from.clone() };
+                if let Some(editing) = self.path_editing.as_mut().filter(|e| e.id == *id) {
+                    editing.path = EditablePath::from_path(&path);
+                    editing.selected = None;
+                    editing.drag = None;
+                }
+                self.set_path(*id, path);
+            }
+            Edit::Text { id, from, to } => {
+                if let Some(shape) = self.shape_mut(*id).and_then(|shape| shape.as_text_mut()) {
+                    shape.text = if forward { to.clone() } else { from.clone() };
+                }
+            }
+            Edit::Mask { group, from, to } => {
+                let mask = if forward { *to } else { *from };
+                if let Some(group) = self
+                    .shape_mut(*group)
+                    .and_then(|shape| shape.as_group_mut())
+                {
+                    group.mask = mask;
+                }
+            }
+            Edit::Blend { group, from, to } => {
+                let blend = if forward { *to } else { *from };
+                if let Some(group) = self
+                    .shape_mut(*group)
+                    .and_then(|shape| shape.as_group_mut())
+                {
+                    group.blend = blend;
+                }
+            }
+            Edit::Contain { group, parked } => {
+                if let Some(group) = self
+                    .shape_mut(*group)
+                    .and_then(|shape| shape.as_group_mut())
+                {
+                    if forward {
+                        group.container = parked.take();
+                    } else {
+                        *parked = group.container.take();
+                    }
+                }
+            }
+            Edit::Layout {
+                container,
+                from,
+                to,
+            } => {
+                let layout = if forward { *to } else { *from };
+                if let Some(container) = self
+                    .shape_mut(*container)
+                    .and_then(|shape| shape.as_group_mut())
+                    .and_then(|group| group.container.as_mut())
+                {
+                    container.layout = layout;
+                }
+            }
+            Edit::Batch(edits) => {
+                if forward {
+                    for edit in edits.iter_mut() {
+                        self.apply_edit(edit, true);
+                    }
+                } else {
+                    for edit in edits.iter_mut().rev() {
+                        self.apply_edit(edit, false);
+                    }
+                }
+            }
+        }
+    }
+
+    // Move shapes between their slots and `parked`.
+    fn set_present(&mut self, ids: &[ShapeId], parked: &mut Vec<Box<dyn Shape>>, present: bool) {
+        if present {
+            for (id, shape) in ids.iter().zip(parked.drain(..)) {
+                self.shapes[*id] = Some(shape);
+            }
+        } else {
+            for id in ids {
+                if let Some(shape) = self.shapes[*id].take() {
+                    parked.push(shape);
+                }
+            }
+            self.selection.retain(|id| !ids.contains(id));
+            self.forget_removed();
+        }
+    }
+
+    /// Offer `key` (a DOM `KeyboardEvent.key`) to the active tool, then look
+    /// it up in the keymap and run the action bound to it. Returns `None` if
+    /// neither wanted it, as for arrow nudges with nothing selected, so the
+    /// page can scroll instead.
+    pub fn handle_key_down(&mut self, key: &str, modifiers: Modifiers) -> Option<KeyOutcome> {
+        let chord = KeyChord::new(key, modifiers);
+        let repeat = !self.pressed_keys.insert(chord.key.clone());
+        let used = self.with_active_tool(|tool, editor| tool.key_down(editor, key, modifiers));
+        if used == Some(true) {
+            return Some(KeyOutcome {
+                action: Action::ToolInput,
+                changed: true,
+            });
+        }
+        let action = self.keymap.action_for(&chord)?.clone();
+        if matches!(action, Action::Nudge { .. }) && self.selection.is_empty() {
+            return None;
+        }
+
+        let selection = self.selection.clone();
+        let changed = match &action {
+            Action::Nudge { dx, dy } => {
+                // holding an arrow key down nudges many times; undo it in one go
+                self.history.set_merging(repeat);
+                let moved = self.translate(&selection, Vec2::new(*dx, *dy));
+                self.history.set_merging(false);
+                moved
+            }
+            Action::Delete if self.selected_path_point().is_some() => self.delete_path_point(),
+            Action::Delete => self.remove(&selection),
+            Action::Deselect if self.path_editing.is_some() => {
+                // the selected point first, then path editing itself
+                if self.selected_path_point().is_some() {
+                    self.select_path_point(None)
+                } else {
+                    self.exit_path_editing()
+                }
+            }
+            Action::Deselect => {
+                if self.selection.is_empty() {
+                    // a second escape leaves the group being edited
+                    self.exit_group()
+                } else {
+                    self.selection.clear();
+                    true
+                }
+            }
+            Action::SelectAll => {
+                self.selection = self.shape_ids().collect();
+                true
+            }
+            Action::Undo => self.undo(),
+            Action::Redo => self.redo(),
+            Action::Duplicate => !self.duplicate(&selection).is_empty(),
+            Action::Group => self.group_selection().is_some(),
+            Action::Ungroup => !self.ungroup().is_empty(),
+            Action::Tool(name) => self.set_tool(name),
+            Action::ToolInput | Action::Custom(_) => false,
+        };
+        Some(KeyOutcome { action, changed })
+    }
+
+    pub fn handle_key_up(&mut self, key: &str) {
+        self.pressed_keys.remove(&key.to_lowercase());
+    }
+
+    pub fn is_key_pressed(&self, key: &str) -> bool {
+        self.pressed_keys.contains(&key.to_lowercase())
+    }
+
+    /// Hand one pointer event to the active tool. Returns true if the scene
+    /// needs to be redrawn.
+    pub fn handle_pointer_event(&mut self, event: &PointerEvent) -> bool {
+        self.with_active_tool(|tool, editor| tool.pointer_event(editor, event))
+            .unwrap_or(false)
+    }
+
+    /// What the select tool does with a pointer event: selecting, dragging,
+    /// path point editing and touch gestures.
+    pub fn select_pointer_event(&mut self, event: &PointerEvent) -> bool {
+        match event.phase {
+            PointerPhase::Down => self.pointer_down(event),
+            PointerPhase::Move => self.pointer_move(event),
+            PointerPhase::Up | PointerPhase::Cancel => self.pointer_up(event),
+        }
+    }
+
+    fn pointer_down(&mut self, event: &PointerEvent) -> bool {
+        if event.buttons & PRIMARY_BUTTON == 0 {
+            return false;
+        }
+        let world = self.screen_to_world(event.position());
+        let hit = self.shape_at(world.x, world.y);
+        if let Some(id) = self.editing_path() {
+            let to_screen = self.view_transform() * self.path_transform(id);
+            let start = self
+                .shape(id)
+                .and_then(|shape| shape.as_path())
+                .map(|shape| shape.path.clone())
+                .unwrap_or_default();
+            let editing = self.path_editing.as_mut().unwrap();
+            match editing
+                .path
+                .hit(to_screen, event.position(), PATH_HIT_RADIUS)
+            {
+                Some((point, handle)) => {
+                    editing.selected = Some(point);
+                    editing.drag = Some(PathDrag {
+                        pointer_id: event.pointer_id,
+                        point,
+                        handle,
+                        start,
+                    });
+                    return true;
+                }
+                // the path's own fill just drops the selected point
+                None if hit == Some(id) => {
+                    editing.selected = None;
+                    return true;
+                }
+                None => self.path_editing = None,
+            }
+        }
+        if let Some(group) = self.entered_group {
+            // clicking anything outside the entered group leaves it
+            if hit.is_none_or(|id| !self.is_ancestor(group, id)) {
+                self.entered_group = None;
+            }
+        }
+        match hit {
+            Some(shape) => {
+                if event.modifiers.shift {
+                    // shift-click toggles membership without dragging
+                    if let Some(index) = self.selection.iter().position(|id| *id == shape) {
+                        self.selection.remove(index);
+                        return true;
+                    }
+                    self.selection.push(shape);
+                    return true;
+                } else if !self.selection.contains(&shape) {
+                    self.selection = vec![shape];
+                } else {
+                    // keep it selected, but make it the primary selection
+                    self.selection.retain(|id| *id != shape);
+                    self.selection.push(shape);
+                }
+
+                let shapes = self.selection.clone();
+                let mut start = Vec::new();
+                let mut start_bounds: Option<Rect> = None;
+                for id in &shapes {
+                    // catching a shape mid-flight stops it where it is
+                    let node = self.shape(*id).unwrap().node().clone();
+                    self.stop_motion(node.x);
+                    self.stop_motion(node.y);
+                    start.push((node.x.get(), node.y.get()));
+                    let bounds = self.world_bounds(*id).unwrap();
+                    start_bounds = Some(start_bounds.map_or(bounds, |b| b.union(bounds)));
+                }
+                self.drags.insert(
+                    event.pointer_id,
+                    PointerDrag {
+                        shapes,
+                        start,
+                        start_bounds: start_bounds.unwrap_or_default(),
+                        origin: world,
+                        samples: [(event.time, world); 2],
+                        moved: false,
+                    },
+                );
+                true
+            }
+            None => {
+                if event.pointer_type == PointerType::Touch {
+                    // fingers on empty canvas steer the view instead
+                    self.gestures.touch_down(event.pointer_id, event.position());
+                    false
+                } else if !event.modifiers.shift && !self.selection.is_empty() {
+                    self.selection.clear();
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+
+    fn pointer_move(&mut self, event: &PointerEvent) -> bool {
+        if self.gestures.is_tracking(event.pointer_id) {
+            return match self.gestures.touch_move(event.pointer_id, event.position()) {
+                Some(gesture) => {
+                    self.zoom_about(gesture.from, gesture.to, gesture.scale);
+                    true
+                }
+                None => false,
+            };
+        }
+
+        let world = self.screen_to_world(event.position());
+        if let Some(id) = self.path_drag(event.pointer_id) {
+            let local = self.path_transform(id).inverse() * world;
+            let editing = self.path_editing.as_mut().unwrap();
+            let drag = editing.drag.as_ref().unwrap();
+            editing.path.move_handle(drag.point, drag.handle, local);
+            let path = editing.path.to_path();
+            self.set_path(id, path);
+            return true;
+        }
+        let drag = match self.drags.get_mut(&event.pointer_id) {
+            Some(drag) => drag,
+            None => return false,
+        };
+        drag.samples = [drag.samples[1], (event.time, world)];
+        if !drag.moved {
+            drag.moved = true;
+            self.events.push(EditorEvent::DragStart {
+                shapes: drag.shapes.clone(),
+                position: drag.origin,
+            });
+        }
+        let drag = &self.drags[&event.pointer_id];
+        let mut delta = world - drag.origin;
+
+        self.smart_guides.clear();
+        if self.snapping.enabled && !snap_bypassed(self.snapping.bypass, event.modifiers) {
+            // top-level shapes and the entered group's children, other than
+            // the dragged shapes and the groups they are in
+            let siblings = self
+                .entered_group
+                .and_then(|group| self.groups.get(&group))
+                .into_iter()
+                .flatten()
+                .copied();
+            let others: Vec<Rect> = self
+                .shape_ids()
+                .chain(siblings)
+                .filter(|id| {
+                    !drag
+                        .shapes
+                        .iter()
+                        .any(|dragged| dragged == id || self.is_ancestor(*id, *dragged))
+                })
+                .filter_map(|id| self.world_bounds(id))
+                .collect();
+            let result = snapping::snap(
+                drag.start_bounds + delta,
+                &others,
+                &self.snapping,
+                self.snapping.threshold / self.zoom,
+            );
+            delta += result.offset;
+            self.smart_guides = result.lines;
+        }
+
+        for (id, (x0, y0)) in drag.shapes.iter().zip(&drag.start) {
+            let delta = self.to_local(*id, delta);
+            if let Some(shape) = self.shape(*id) {
+                let node = shape.node();
+                node.x.set(x0 + delta.x);
+                node.y.set(y0 + delta.y);
+            }
+        }
+
+        self.update_followers();
+        true
+    }
+
+    fn pointer_up(&mut self, event: &PointerEvent) -> bool {
+        self.gestures.touch_up(event.pointer_id);
+        if let Some(id) = self.path_drag(event.pointer_id) {
+            let editing = self.path_editing.as_mut().unwrap();
+            let drag = editing.drag.take().unwrap();
+            // a cancelled drag puts the point back and leaves no undo step
+            if event.phase == PointerPhase::Cancel {
+                editing.path = EditablePath::from_path(&drag.start);
+                self.set_path(id, drag.start);
+                return true;
+            }
+            let to = editing.path.to_path();
+            if to != drag.start {
+                self.history.push(Edit::Path {
+                    id,
+                    from: drag.start,
+                    to,
+                });
+            }
+            return false;
+        }
+        let drag = match self.drags.remove(&event.pointer_id) {
+            Some(drag) => drag,
+            None => return false,
+        };
+        let had_guides = !self.smart_guides.is_empty();
+        self.smart_guides.clear();
+        // where the pointer last moved to; mouse-up events carry no position
+        let position = drag.samples[1].1;
+        if drag.moved {
+            self.events.push(EditorEvent::DragEnd {
+                shapes: drag.shapes.clone(),
+                position,
+            });
+        } else if event.phase == PointerPhase::Up {
+            // the shape pressed is the primary selection, last in the drag
+            if let Some(shape) = drag.shapes.last().copied() {
+                self.events.push(EditorEvent::Click { shape, position });
+            }
+        }
+
+        if event.phase == PointerPhase::Cancel {
+            for (id, (x0, y0)) in drag.shapes.iter().zip(drag.start) {
+                if let Some(shape) = self.shape(*id) {
+                    let node = shape.node();
+                    node.x.set(x0);
+                    node.y.set(y0);
+                }
+            }
+            self.update_followers();
+            return true;
+        }
+
+        let velocity = match self.drag_momentum {
+            Some(_) => release_velocity(&drag, event.time),
+            _ => None,
+        };
+
+        // the whole drag becomes one undo step; a throw is recorded at the
+        // spot where it will come to rest
+        let mut changes = Vec::new();
+        for (id, (x0, y0)) in drag.shapes.iter().zip(drag.start) {
+            let node = match self.shape(*id) {
+                Some(shape) => shape.node().clone(),
+                None => continue,
+            };
+            let velocity = velocity.map(|v| self.to_local(*id, v));
+            for (signal, from, speed) in [
+                (node.x, x0, velocity.map(|v| v.x)),
+                (node.y, y0, velocity.map(|v| v.y)),
+            ] {
+                let mut to = signal.get();
+                if let (Some(speed), Some(friction)) = (speed, self.drag_momentum) {
+                    self.physics.glide(signal, speed, friction);
+                    to += speed / friction;
+                }
+                if to != from {
+                    changes.push(SignalChange { signal, from, to });
+                }
+            }
+        }
+        if !changes.is_empty() {
+            self.history.push(Edit::Set(changes));
+        }
+        had_guides
+    }
+
+    // the edited path, if `pointer_id` is dragging one of its points
+    fn path_drag(&self, pointer_id: i32) -> Option<ShapeId> {
+        let editing = self.path_editing.as_ref()?;
+        let drag = editing.drag.as_ref()?;
+        (drag.pointer_id == pointer_id).then_some(editing.id)
+    }
+
+    pub fn handle_mouse_down(&mut self, x: f64, y: f64) {
+        self.handle_pointer_event(&mouse_event(PointerPhase::Down, x, y));
+    }
+
+    /// Returns true if anything moved and the scene needs to be redrawn.
+    pub fn handle_mouse_move(&mut self, x: f64, y: f64) -> bool {
+        self.handle_pointer_event(&mouse_event(PointerPhase::Move, x, y))
+    }
+
+    pub fn handle_mouse_up(&mut self) {
+        self.handle_pointer_event(&mouse_event(PointerPhase::Up, 0.0, 0.0));
+    }
+
+    /// The document in world coordinates, without the view transform.
+    pub fn build_scene(&self) -> Scene {
+        let mut scene = Scene::new();
+        for shape in self.shapes.iter().flatten() {
+            shape.draw(&mut scene);
+        }
+        scene
+    }
+
+    /// The document as it appears on a `width` x `height` canvas: panned,
+    /// zoomed and with the grid and guides drawn over it.
+    pub fn build_view_scene(&self, width: f64, height: f64) -> Scene {
+        let mut scene = Scene::new();
+        let view = self.view_transform();
+        snapping::draw_grid(&mut scene, &self.snapping, view, width, height);
+        scene.append(&self.build_scene(), Some(view));
+        snapping::draw_overlay(
+            &mut scene,
+            &self.snapping,
+            &self.smart_guides,
+            view,
+            width,
+            height,
+        );
+        if let Some(bounds) = self
+            .entered_group
+            .and_then(|group| self.world_bounds(group))
+        {
+            scene.stroke(
+                &Stroke::new(1.0).with_dashes(0.0, [4.0, 4.0]),
+                Affine::IDENTITY,
+                ENTERED_GROUP_COLOR,
+                None,
+                &view.transform_rect_bbox(bounds),
+            );
+        }
+        if let Some(editing) = &self.path_editing {
+            path_edit::draw_overlay(
+                &mut scene,
+                &editing.path,
+                editing.selected,
+                view * self.path_transform(editing.id),
+            );
+        }
+        if let Some(tool) = self.tools.get(&self.active_tool) {
+            tool.draw_overlay(self, &mut scene);
+        }
+        scene
+    }
+}
+
+fn snap_bypassed(bypass: SnapBypass, modifiers: Modifiers) -> bool {
+    match bypass {
+        SnapBypass::Alt => modifiers.alt,
+        SnapBypass::Ctrl => modifiers.ctrl,
+        SnapBypass::Shift => modifiers.shift,
+        SnapBypass::Meta => modifiers.meta,
+    }
+}
+
+// Split `transform` into a node's position, rotation and scales, with the
+// rotation as close as it can be to `rotation` and the x scale's sign that
+// of `scale_x`. `None` if it skews or squashes flat.
+fn decompose(transform: Affine, rotation: f64, scale_x: f64) -> Option<(Point, f64, f64, f64)> {
+    let [a, b, c, d, e, f] = transform.as_coeffs();
+    let (x_axis, y_axis) = (Vec2::new(a, b), Vec2::new(c, d));
+    let (x_length, y_length) = (x_axis.hypot(), y_axis.hypot());
+    if x_length < 1e-12 || y_length < 1e-12 || x_axis.dot(y_axis).abs() > 1e-9 * x_length * y_length
+    {
+        return None;
+    }
+    let sign = if scale_x < 0.0 { -1.0 } else { 1.0 };
+    let angle = (sign * b).atan2(sign * a);
+    let turns = ((rotation - angle) / std::f64::consts::TAU).round();
+    let scale_x = sign * x_length;
+    Some((
+        Point::new(e, f),
+        angle + turns * std::f64::consts::TAU,
+        scale_x,
+        x_axis.cross(y_axis) / scale_x,
+    ))
+}
+
+fn mouse_event(phase: PointerPhase, x: f64, y: f64) -> PointerEvent {
+    PointerEvent {
+        pointer_id: MOUSE_POINTER_ID,
+        pointer_type: PointerType::Mouse,
+        phase,
+        x,
+        y,
+        buttons: PRIMARY_BUTTON,
+        modifiers: Modifiers::default(),
+        pressure: 0.5,
+        time: 0.0,
+    }
+}
+
+// Pointer velocity (world units per second) at release, if it was still
+// moving fast enough to count as a throw.
+fn release_velocity(drag: &PointerDrag, release_time: f64) -> Option<Vec2> {
+    let [(t0, p0), (t1, p1)] = drag.samples;
+    let dt = t1 - t0;
+    if dt <= 0.0 || release_time - t1 > THROW_WINDOW {
+        return None;
+    }
+    let velocity = (p1 - p0) / dt;
+    if velocity.hypot() < MIN_THROW_SPEED {
+        return None;
+    }
+    Some(velocity)
+}
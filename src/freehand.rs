@@ -0,0 +1,342 @@
+// Freehand drawing. Pointer samples are smoothed as they arrive, offset
+// either side by a pressure-dependent half width, capped with half circles,
+// and the resulting outline is simplified into cubic Béziers by Schneider's
+// curve fitting ("An Algorithm for Automatically Fitting Digitized Curves",
+// Graphics Gems, 1990).
+
+use std::f64::consts::PI;
+
+use vello::kurbo::{
+    BezPath, Circle, CubicBez, ParamCurve, ParamCurveDeriv, Point, Shape as _, Vec2,
+};
+use vello::peniko::Color;
+
+// width at zero pressure, as a fraction of the full width
+const MIN_PRESSURE_SCALE: f64 = 0.2;
+// points on each half-circle cap
+const CAP_SEGMENTS: usize = 8;
+// Newton steps tried before a curve that almost fits is split
+const MAX_REFINEMENTS: usize = 4;
+
+#[derive(Clone, Copy, Debug)]
+pub struct BrushSettings {
+    // stroke width in world units, at full pressure
+    pub width: f64,
+    pub color: Color,
+    // 0 follows the pointer exactly; towards 1 is smoother but lags behind
+    pub smoothing: f64,
+    // how far, in canvas pixels, the simplified outline may stray
+    pub tolerance: f64,
+    // vary the width with pen pressure
+    pub pressure: bool,
+}
+
+impl Default for BrushSettings {
+    fn default() -> Self {
+        Self {
+            width: 4.0,
+            color: Color::BLACK,
+            smoothing: 0.5,
+            tolerance: 0.5,
+            pressure: true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StrokeSample {
+    pub point: Point,
+    // 0 to 1
+    pub pressure: f64,
+}
+
+/// The samples of a stroke being drawn.
+pub struct FreehandStroke {
+    samples: Vec<StrokeSample>,
+    smoothing: f64,
+    // samples closer than this to the previous one are held back
+    min_distance: f64,
+    // the latest held-back sample, so the stroke still ends at the pointer
+    pending: Option<StrokeSample>,
+}
+
+impl FreehandStroke {
+    pub fn new(smoothing: f64, min_distance: f64) -> Self {
+        Self {
+            samples: Vec::new(),
+            smoothing: smoothing.clamp(0.0, 0.99),
+            min_distance,
+            pending: None,
+        }
+    }
+
+    pub fn push(&mut self, point: Point, pressure: f64) {
+        let pressure = pressure.clamp(0.0, 1.0);
+        let last = match self.samples.last() {
+            Some(last) => *last,
+            None => {
+                self.samples.push(StrokeSample { point, pressure });
+                return;
+            }
+        };
+        // an exponential moving average irons out jitter
+        let follow = 1.0 - self.smoothing;
+        let sample = StrokeSample {
+            point: last.point.lerp(point, follow),
+            pressure: last.pressure + (pressure - last.pressure) * follow,
+        };
+        if (sample.point - last.point).hypot() < self.min_distance {
+            self.pending = Some(StrokeSample { point, pressure });
+        } else {
+            self.samples.push(sample);
+            self.pending = None;
+        }
+    }
+
+    pub fn samples(&self) -> &[StrokeSample] {
+        &self.samples
+    }
+
+    /// The unsimplified outline, cheap enough to preview every frame.
+    pub fn outline(&self, width: f64) -> BezPath {
+        polygon(&outline_points(&self.samples, width))
+    }
+
+    /// The finished stroke as a filled outline whose curves stay within
+    /// `tolerance` of the sampled one. `None` if nothing was drawn.
+    pub fn finish(mut self, width: f64, tolerance: f64) -> Option<BezPath> {
+        if let Some(pending) = self.pending.take() {
+            self.samples.push(pending);
+        }
+        match self.samples.as_slice() {
+            [] => None,
+            // a tap leaves a dot
+            [sample] => {
+                Some(Circle::new(sample.point, half_width(width, sample.pressure)).to_path(0.1))
+            }
+            samples => {
+                let mut ring = outline_points(samples, width);
+                ring.push(ring[0]);
+                let curves = fit_curves(&ring, tolerance);
+                let mut path = BezPath::new();
+                path.move_to(ring[0]);
+                for curve in curves {
+                    path.curve_to(curve.p1, curve.p2, curve.p3);
+                }
+                path.close_path();
+                Some(path)
+            }
+        }
+    }
+}
+
+fn half_width(width: f64, pressure: f64) -> f64 {
+    width / 2.0 * (MIN_PRESSURE_SCALE + (1.0 - MIN_PRESSURE_SCALE) * pressure)
+}
+
+// Left side forwards, the end cap, right side backwards and the start cap.
+fn outline_points(samples: &[StrokeSample], width: f64) -> Vec<Point> {
+    let n = samples.len();
+    if n < 2 {
+        return Vec::new();
+    }
+    let mut left = Vec::with_capacity(n);
+    let mut right = Vec::with_capacity(n);
+    let mut directions = Vec::with_capacity(n);
+    for i in 0..n {
+        let before = samples[i.saturating_sub(1)].point;
+        let after = samples[(i + 1).min(n - 1)].point;
+        let direction = unit(after - before).unwrap_or_else(|| Vec2::new(1.0, 0.0));
+        let normal = Vec2::new(-direction.y, direction.x);
+        let offset = normal * half_width(width, samples[i].pressure);
+        left.push(samples[i].point + offset);
+        right.push(samples[i].point - offset);
+        directions.push(direction);
+    }
+
+    let mut points = left;
+    cap(&mut points, samples[n - 1], directions[n - 1], width);
+    points.extend(right.into_iter().rev());
+    cap(&mut points, samples[0], -directions[0], width);
+    points
+}
+
+// a half circle from the left side round to the right, bulging along
+// `direction`, without its two ends
+fn cap(points: &mut Vec<Point>, sample: StrokeSample, direction: Vec2, width: f64) {
+    let radius = half_width(width, sample.pressure);
+    let normal = Vec2::new(-direction.y, direction.x);
+    for step in 1..CAP_SEGMENTS {
+        let angle = PI * step as f64 / CAP_SEGMENTS as f64;
+        points.push(
+            sample.point + normal * (radius * angle.cos()) + direction * (radius * angle.sin()),
+        );
+    }
+}
+
+fn polygon(points: &[Point]) -> BezPath {
+    let mut path = BezPath::new();
+    if let Some((first, rest)) = points.split_first() {
+        path.move_to(*first);
+        for point in rest {
+            path.line_to(*point);
+        }
+        path.close_path();
+    }
+    path
+}
+
+fn unit(v: Vec2) -> Option<Vec2> {
+    let length = v.hypot();
+    (length > 1e-12).then(|| v / length)
+}
+
+/// Fit cubic Béziers through `points` so that none strays further than
+/// `tolerance` from them. The curves join end to end, starting at the first
+/// point and ending at the last.
+pub fn fit_curves(points: &[Point], tolerance: f64) -> Vec<CubicBez> {
+    let mut points = points.to_vec();
+    points.dedup_by(|a, b| (*a - *b).hypot() < 1e-9);
+    let mut curves = Vec::new();
+    let n = points.len();
+    if n < 2 {
+        return curves;
+    }
+    let start = unit(points[1] - points[0]).unwrap();
+    let end = unit(points[n - 2] - points[n - 1]).unwrap();
+    fit_cubic(&points, start, end, tolerance * tolerance, &mut curves);
+    curves
+}
+
+// `start` points along the curve from the first point, `end` back along it
+// from the last.
+fn fit_cubic(points: &[Point], start: Vec2, end: Vec2, error: f64, curves: &mut Vec<CubicBez>) {
+    let (first, last) = (points[0], points[points.len() - 1]);
+    if points.len() == 2 {
+        let length = (last - first).hypot() / 3.0;
+        curves.push(CubicBez::new(
+            first,
+            first + start * length,
+            last + end * length,
+            last,
+        ));
+        return;
+    }
+
+    let mut params = chord_length_params(points);
+    let mut curve = generate_bezier(points, &params, start, end);
+    let (mut max_error, mut split) = worst_point(points, &curve, &params);
+    if max_error < error {
+        curves.push(curve);
+        return;
+    }
+    // close enough that better parameters may make it fit
+    if max_error < error * 4.0 {
+        for _ in 0..MAX_REFINEMENTS {
+            params = reparameterize(points, &params, &curve);
+            curve = generate_bezier(points, &params, start, end);
+            let (e, s) = worst_point(points, &curve, &params);
+            max_error = e;
+            split = s;
+            if max_error < error {
+                curves.push(curve);
+                return;
+            }
+        }
+    }
+
+    let center = unit(points[split - 1] - points[split + 1])
+        .or_else(|| unit(points[split - 1] - points[split]))
+        .unwrap_or(end);
+    fit_cubic(&points[..=split], start, center, error, curves);
+    fit_cubic(&points[split..], -center, end, error, curves);
+}
+
+fn chord_length_params(points: &[Point]) -> Vec<f64> {
+    let mut params = Vec::with_capacity(points.len());
+    let mut total = 0.0;
+    params.push(0.0);
+    for pair in points.windows(2) {
+        total += (pair[1] - pair[0]).hypot();
+        params.push(total);
+    }
+    for param in &mut params {
+        *param /= total;
+    }
+    params
+}
+
+// Least-squares handle lengths along the fixed end tangents.
+fn generate_bezier(points: &[Point], params: &[f64], start: Vec2, end: Vec2) -> CubicBez {
+    let (first, last) = (points[0], points[points.len() - 1]);
+    let mut c = [[0.0; 2]; 2];
+    let mut x = [0.0; 2];
+    for (point, &t) in points.iter().zip(params) {
+        let s = 1.0 - t;
+        let (b0, b1, b2, b3) = (s * s * s, 3.0 * t * s * s, 3.0 * t * t * s, t * t * t);
+        let a0 = start * b1;
+        let a1 = end * b2;
+        c[0][0] += a0.dot(a0);
+        c[0][1] += a0.dot(a1);
+        c[1][1] += a1.dot(a1);
+        let rest = *point - (first.to_vec2() * (b0 + b1) + last.to_vec2() * (b2 + b3)).to_point();
+        x[0] += a0.dot(rest);
+        x[1] += a1.dot(rest);
+    }
+    c[1][0] = c[0][1];
+
+    let det = c[0][0] * c[1][1] - c[1][0] * c[0][1];
+    let (mut alpha_start, mut alpha_end) = if det.abs() > 1e-12 {
+        (
+            (x[0] * c[1][1] - x[1] * c[0][1]) / det,
+            (c[0][0] * x[1] - c[1][0] * x[0]) / det,
+        )
+    } else {
+        (0.0, 0.0)
+    };
+    // degenerate or backwards handles: fall back to a third of the chord
+    let length = (last - first).hypot();
+    if alpha_start < 1e-6 * length || alpha_end < 1e-6 * length {
+        alpha_start = length / 3.0;
+        alpha_end = length / 3.0;
+    }
+    CubicBez::new(
+        first,
+        first + start * alpha_start,
+        last + end * alpha_end,
+        last,
+    )
+}
+
+// squared distance of the worst interior point, and its index
+fn worst_point(points: &[Point], curve: &CubicBez, params: &[f64]) -> (f64, usize) {
+    let mut worst = (0.0, points.len() / 2);
+    for i in 1..points.len() - 1 {
+        let distance = (curve.eval(params[i]) - points[i]).hypot2();
+        if distance >= worst.0 {
+            worst = (distance, i);
+        }
+    }
+    worst
+}
+
+// One Newton-Raphson step per point towards its nearest spot on the curve.
+fn reparameterize(points: &[Point], params: &[f64], curve: &CubicBez) -> Vec<f64> {
+    let first = curve.deriv();
+    let second = first.deriv();
+    points
+        .iter()
+        .zip(params)
+        .map(|(point, &t)| {
+            let offset = curve.eval(t) - *point;
+            let d1 = first.eval(t).to_vec2();
+            let d2 = second.eval(t).to_vec2();
+            let denominator = d1.dot(d1) + offset.dot(d2);
+            if denominator.abs() < 1e-12 {
+                t
+            } else {
+                (t - offset.dot(d1) / denominator).clamp(0.0, 1.0)
+            }
+        })
+        .collect()
+}
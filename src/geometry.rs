@@ -0,0 +1,84 @@
+// Questions about outlines: how far a point is from one, and whether two of
+// them touch. Outlines are compared in whatever space they share; the
+// editor maps shapes to world coordinates first.
+
+use vello::kurbo::{
+    self, BezPath, Line, ParamCurveExtrema, ParamCurveNearest, PathEl, Point, Rect, Shape as _,
+};
+
+// accuracy of nearest-point searches and of flattening curves
+const TOLERANCE: f64 = 0.01;
+
+/// The shortest distance from `point` to the edge of `outline`, wherever
+/// the point is; infinite for an empty outline.
+pub fn distance_to_outline(outline: &BezPath, point: Point) -> f64 {
+    outline
+        .segments()
+        .map(|segment| segment.nearest(point, TOLERANCE).distance_sq)
+        .fold(f64::INFINITY, f64::min)
+        .sqrt()
+}
+
+/// Whether the areas enclosed by two outlines overlap or their edges
+/// cross, treating open subpaths as closed the way a fill does.
+pub fn outlines_touch(a: &BezPath, b: &BezPath) -> bool {
+    let (a_box, b_box) = (a.bounding_box(), b.bounding_box());
+    if a_box.x1 < b_box.x0 || b_box.x1 < a_box.x0 || a_box.y1 < b_box.y0 || b_box.y1 < a_box.y0 {
+        return false;
+    }
+    let lines = flatten(b);
+    let crossing = a.segments().any(|segment| {
+        let reach = ParamCurveExtrema::bounding_box(&segment);
+        lines.iter().any(|line| {
+            let span = Rect::from_points(line.p0, line.p1);
+            span.x0 <= reach.x1
+                && reach.x0 <= span.x1
+                && span.y0 <= reach.y1
+                && reach.y0 <= span.y1
+                && !segment.intersect_line(*line).is_empty()
+        })
+    });
+    // without crossings, one is either wholly inside the other or apart
+    crossing
+        || starts(a).any(|point| b.winding(point) != 0)
+        || starts(b).any(|point| a.winding(point) != 0)
+}
+
+// straight edges approximating an outline, closing each subpath
+fn flatten(path: &BezPath) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let (mut start, mut last) = (Point::ORIGIN, Point::ORIGIN);
+    kurbo::flatten(path.iter(), TOLERANCE, |element| match element {
+        PathEl::MoveTo(point) => {
+            close(&mut lines, last, start);
+            start = point;
+            last = point;
+        }
+        PathEl::LineTo(point) => {
+            lines.push(Line::new(last, point));
+            last = point;
+        }
+        PathEl::ClosePath => {
+            close(&mut lines, last, start);
+            last = start;
+        }
+        // flattening only produces the elements above
+        _ => {}
+    });
+    close(&mut lines, last, start);
+    lines
+}
+
+fn close(lines: &mut Vec<Line>, last: Point, start: Point) {
+    if last != start {
+        lines.push(Line::new(last, start));
+    }
+}
+
+// the first point of each subpath
+fn starts(path: &BezPath) -> impl Iterator<Item = Point> + '_ {
+    path.elements().iter().filter_map(|element| match element {
+        PathEl::MoveTo(point) => Some(*point),
+        _ => None,
+    })
+}
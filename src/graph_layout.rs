@@ -0,0 +1,562 @@
+// Automatic placement of node-link diagrams. The algorithms work on an
+// abstract graph of box sizes and directed edges and only decide where each
+// box's center goes; the editor turns that into node positions.
+//
+// Layered is Sugiyama's framework: break cycles, assign layers by longest
+// path, order each layer by barycenters to cut crossings, then pull boxes
+// towards their neighbours. Force is Fruchterman-Reingold. Tree and radial
+// lay out a breadth-first spanning tree, the first as a tidy tree and the
+// second in rings around the root.
+
+use std::collections::VecDeque;
+use std::f64::consts::{PI, TAU};
+
+use vello::kurbo::{Point, Rect, Size, Vec2};
+
+// barycenter sweeps, each down then up, when ordering layers
+const ORDERING_SWEEPS: usize = 12;
+// passes pulling layered boxes towards their neighbours
+const ALIGNMENT_PASSES: usize = 8;
+// steps of the force simulation
+const FORCE_ITERATIONS: usize = 300;
+// passes pushing apart boxes the simulation left overlapping
+const SEPARATION_PASSES: usize = 50;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LayoutAlgorithm {
+    Layered,
+    Force,
+    Tree,
+    Radial,
+}
+
+impl LayoutAlgorithm {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "layered" | "sugiyama" => Some(LayoutAlgorithm::Layered),
+            "force" => Some(LayoutAlgorithm::Force),
+            "tree" => Some(LayoutAlgorithm::Tree),
+            "radial" => Some(LayoutAlgorithm::Radial),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GraphLayout {
+    pub algorithm: LayoutAlgorithm,
+    // space between neighbouring boxes in a layer or ring, in world units
+    pub node_gap: f64,
+    // space between layers, tree levels or rings
+    pub layer_gap: f64,
+}
+
+impl Default for GraphLayout {
+    fn default() -> Self {
+        Self {
+            algorithm: LayoutAlgorithm::Layered,
+            node_gap: 40.0,
+            layer_gap: 80.0,
+        }
+    }
+}
+
+/// Boxes and the directed edges between them, by index.
+#[derive(Clone, Debug, Default)]
+pub struct Graph {
+    pub sizes: Vec<Size>,
+    pub edges: Vec<(usize, usize)>,
+}
+
+impl GraphLayout {
+    /// New centers for the boxes of `graph`, which are now centered on
+    /// `current`. The result is centered where the current layout is.
+    pub fn apply(&self, graph: &Graph, current: &[Point]) -> Vec<Point> {
+        let n = graph.sizes.len();
+        if n == 0 {
+            return Vec::new();
+        }
+        let mut centers = match self.algorithm {
+            LayoutAlgorithm::Layered => self.layered(graph),
+            LayoutAlgorithm::Force => self.force(graph, current),
+            LayoutAlgorithm::Tree => self.tree(graph),
+            LayoutAlgorithm::Radial => self.radial(graph),
+        };
+        let offset =
+            bounds(current, &graph.sizes).center() - bounds(&centers, &graph.sizes).center();
+        for center in &mut centers {
+            *center += offset;
+        }
+        centers
+    }
+
+    fn layered(&self, graph: &Graph) -> Vec<Point> {
+        let n = graph.sizes.len();
+        let edges = acyclic(n, &graph.edges);
+        let mut layer = longest_path_layers(n, &edges);
+
+        // long edges pass through a dummy on every layer they cross, so
+        // they take part in ordering
+        let mut widths: Vec<f64> = graph.sizes.iter().map(|size| size.width).collect();
+        let mut links = Vec::new();
+        for &(from, to) in &edges {
+            let mut previous = from;
+            for step in layer[from] + 1..layer[to] {
+                let dummy = widths.len();
+                widths.push(0.0);
+                layer.push(step);
+                links.push((previous, dummy));
+                previous = dummy;
+            }
+            links.push((previous, to));
+        }
+        let count = widths.len();
+        let mut up = vec![Vec::new(); count];
+        let mut down = vec![Vec::new(); count];
+        for &(from, to) in &links {
+            down[from].push(to);
+            up[to].push(from);
+        }
+
+        let depth = layer.iter().max().map_or(0, |max| max + 1);
+        let mut layers: Vec<Vec<usize>> = vec![Vec::new(); depth];
+        for (node, l) in layer.iter().enumerate() {
+            layers[*l].push(node);
+        }
+        let mut order = vec![0.0; count];
+        let renumber = |layers: &[Vec<usize>], order: &mut [f64]| {
+            for nodes in layers {
+                for (index, node) in nodes.iter().enumerate() {
+                    order[*node] = index as f64;
+                }
+            }
+        };
+        renumber(&layers, &mut order);
+        for _ in 0..ORDERING_SWEEPS {
+            for l in 1..depth {
+                sort_by_barycenter(&mut layers[l], &up, &order);
+                renumber(&layers[l..=l], &mut order);
+            }
+            for l in (0..depth.saturating_sub(1)).rev() {
+                sort_by_barycenter(&mut layers[l], &down, &order);
+                renumber(&layers[l..=l], &mut order);
+            }
+        }
+
+        // pack each layer, then pull boxes towards their neighbours' average
+        // while keeping the order and gaps
+        let mut x = vec![0.0; count];
+        for nodes in &layers {
+            let mut left = 0.0;
+            for node in nodes {
+                x[*node] = left + widths[*node] / 2.0;
+                left += widths[*node] + self.node_gap;
+            }
+        }
+        for pass in 0..ALIGNMENT_PASSES {
+            let (neighbours, range): (&[Vec<usize>], Vec<usize>) = if pass % 2 == 0 {
+                (&up, (1..depth).collect())
+            } else {
+                (&down, (0..depth.saturating_sub(1)).rev().collect())
+            };
+            for l in range {
+                let wanted: Vec<f64> = layers[l]
+                    .iter()
+                    .map(|node| {
+                        mean(neighbours[*node].iter().map(|other| x[*other])).unwrap_or(x[*node])
+                    })
+                    .collect();
+                place_in_order(&layers[l], &wanted, &widths, self.node_gap, &mut x);
+            }
+        }
+
+        let y = level_centers(&layer[..n], &graph.sizes, self.layer_gap);
+        (0..n)
+            .map(|node| Point::new(x[node], y[layer[node]]))
+            .collect()
+    }
+
+    fn force(&self, graph: &Graph, current: &[Point]) -> Vec<Point> {
+        let n = graph.sizes.len();
+        let spread = graph
+            .sizes
+            .iter()
+            .map(|size| size.width.max(size.height))
+            .sum::<f64>()
+            / n as f64;
+        // ideal distance between linked centers
+        let k = spread + self.node_gap;
+        let mut positions = current.to_vec();
+        // boxes on top of each other would never push apart
+        for i in 0..n {
+            if positions[..i]
+                .iter()
+                .any(|p| (*p - positions[i]).hypot() < 1e-6)
+            {
+                let angle = i as f64 * 2.399963;
+                positions[i] += Vec2::from_angle(angle) * (k * (i as f64).sqrt() / 2.0);
+            }
+        }
+
+        let mut temperature = k * (n as f64).sqrt();
+        let cooling = temperature / FORCE_ITERATIONS as f64;
+        for _ in 0..FORCE_ITERATIONS {
+            let mut moves = vec![Vec2::ZERO; n];
+            for i in 0..n {
+                for j in i + 1..n {
+                    let d = positions[i] - positions[j];
+                    let distance = d.hypot().max(1e-3);
+                    let push = d / distance * (k * k / distance);
+                    moves[i] += push;
+                    moves[j] -= push;
+                }
+            }
+            for &(a, b) in &graph.edges {
+                if a == b {
+                    continue;
+                }
+                let d = positions[a] - positions[b];
+                let distance = d.hypot().max(1e-3);
+                let pull = d / distance * (distance * distance / k);
+                moves[a] -= pull;
+                moves[b] += pull;
+            }
+            for (position, step) in positions.iter_mut().zip(moves) {
+                let length = step.hypot();
+                if length > 0.0 {
+                    *position += step / length * length.min(temperature);
+                }
+            }
+            temperature = (temperature - cooling).max(k * 0.01);
+        }
+        separate(&mut positions, &graph.sizes, self.node_gap / 2.0);
+        positions
+    }
+
+    fn tree(&self, graph: &Graph) -> Vec<Point> {
+        let n = graph.sizes.len();
+        let forest = Forest::new(n, &graph.edges);
+        let mut x = vec![0.0; n];
+        let mut left = 0.0;
+        for root in &forest.roots {
+            let width = self.subtree_width(&forest, &graph.sizes, *root);
+            self.place_subtree(&forest, &graph.sizes, *root, left, &mut x);
+            left += width + self.node_gap;
+        }
+
+        let y = level_centers(&forest.depth, &graph.sizes, self.layer_gap);
+        (0..n)
+            .map(|node| Point::new(x[node], y[forest.depth[node]]))
+            .collect()
+    }
+
+    fn subtree_width(&self, forest: &Forest, sizes: &[Size], node: usize) -> f64 {
+        let children = &forest.children[node];
+        let below: f64 = children
+            .iter()
+            .map(|child| self.subtree_width(forest, sizes, *child))
+            .sum::<f64>()
+            + self.node_gap * children.len().saturating_sub(1) as f64;
+        below.max(sizes[node].width)
+    }
+
+    // Children side by side from `left`, the parent centered over them.
+    fn place_subtree(
+        &self,
+        forest: &Forest,
+        sizes: &[Size],
+        node: usize,
+        left: f64,
+        x: &mut [f64],
+    ) {
+        let width = self.subtree_width(forest, sizes, node);
+        let children = &forest.children[node];
+        let below: f64 = children
+            .iter()
+            .map(|child| self.subtree_width(forest, sizes, *child))
+            .sum::<f64>()
+            + self.node_gap * children.len().saturating_sub(1) as f64;
+        let mut child_left = left + (width - below) / 2.0;
+        for child in children {
+            let child_width = self.subtree_width(forest, sizes, *child);
+            self.place_subtree(forest, sizes, *child, child_left, x);
+            child_left += child_width + self.node_gap;
+        }
+        x[node] = left + width / 2.0;
+    }
+
+    fn radial(&self, graph: &Graph) -> Vec<Point> {
+        let n = graph.sizes.len();
+        let forest = Forest::new(n, &graph.edges);
+        let largest = graph
+            .sizes
+            .iter()
+            .map(|size| size.width.hypot(size.height))
+            .fold(0.0, f64::max);
+        let ring = largest + self.layer_gap;
+        // several roots share the middle ring round an empty center
+        let shift = if forest.roots.len() > 1 { 1.0 } else { 0.0 };
+
+        let mut leaves = vec![0usize; n];
+        for node in forest.bottom_up() {
+            leaves[node] = forest.children[node]
+                .iter()
+                .map(|child| leaves[*child])
+                .sum::<usize>()
+                .max(1);
+        }
+        let total: usize = forest.roots.iter().map(|root| leaves[*root]).sum();
+        // neighbours on a ring are at least one leaf's angle apart, so the
+        // first ring must be wide enough for that to clear a box
+        let first_ring = if total > 1 {
+            ring.max((largest + self.node_gap) / (2.0 * (PI / total as f64).sin()))
+        } else {
+            ring
+        };
+
+        // each subtree gets a wedge in proportion to its leaves
+        let mut positions = vec![Point::ZERO; n];
+        let mut wedges: VecDeque<(usize, f64, f64)> = VecDeque::new();
+        let mut start = 0.0;
+        for root in &forest.roots {
+            let sweep = TAU * leaves[*root] as f64 / total as f64;
+            wedges.push_back((*root, start, sweep));
+            start += sweep;
+        }
+        while let Some((node, start, sweep)) = wedges.pop_front() {
+            let level = forest.depth[node] as f64 + shift;
+            let radius = if level > 0.0 {
+                first_ring + (level - 1.0) * ring
+            } else {
+                0.0
+            };
+            positions[node] = Point::ZERO + Vec2::from_angle(start + sweep / 2.0) * radius;
+            let mut child_start = start;
+            for child in &forest.children[node] {
+                let child_sweep = sweep * leaves[*child] as f64 / leaves[node] as f64;
+                wedges.push_back((*child, child_start, child_sweep));
+                child_start += child_sweep;
+            }
+        }
+        positions
+    }
+}
+
+// A breadth-first spanning forest, rooted at nodes with no incoming edges
+// (or, for a cycle with none, its first node).
+struct Forest {
+    roots: Vec<usize>,
+    children: Vec<Vec<usize>>,
+    depth: Vec<usize>,
+    // in breadth-first order
+    visited: Vec<usize>,
+}
+
+impl Forest {
+    fn new(n: usize, edges: &[(usize, usize)]) -> Self {
+        let mut outgoing = vec![Vec::new(); n];
+        let mut incoming = vec![0usize; n];
+        for &(from, to) in edges {
+            if from != to {
+                outgoing[from].push(to);
+                incoming[to] += 1;
+            }
+        }
+        let mut forest = Self {
+            roots: Vec::new(),
+            children: vec![Vec::new(); n],
+            depth: vec![0; n],
+            visited: Vec::with_capacity(n),
+        };
+        let mut seen = vec![false; n];
+        let candidates: Vec<usize> = (0..n)
+            .filter(|node| incoming[*node] == 0)
+            .chain(0..n)
+            .collect();
+        for root in candidates {
+            if seen[root] {
+                continue;
+            }
+            seen[root] = true;
+            forest.roots.push(root);
+            let mut queue = VecDeque::from(vec![root]);
+            while let Some(node) = queue.pop_front() {
+                forest.visited.push(node);
+                for next in &outgoing[node] {
+                    if !seen[*next] {
+                        seen[*next] = true;
+                        forest.children[node].push(*next);
+                        forest.depth[*next] = forest.depth[node] + 1;
+                        queue.push_back(*next);
+                    }
+                }
+            }
+        }
+        forest
+    }
+
+    fn bottom_up(&self) -> impl Iterator<Item = usize> + '_ {
+        self.visited.iter().rev().copied()
+    }
+}
+
+// The edges with those closing a cycle turned round, found by depth-first
+// search; self-loops are dropped.
+fn acyclic(n: usize, edges: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let mut outgoing = vec![Vec::new(); n];
+    for (index, &(from, to)) in edges.iter().enumerate() {
+        if from != to {
+            outgoing[from].push((to, index));
+        }
+    }
+    // 0 unvisited, 1 on the current path, 2 done
+    let mut state = vec![0u8; n];
+    let mut reversed = vec![false; edges.len()];
+    for root in 0..n {
+        if state[root] != 0 {
+            continue;
+        }
+        let mut stack = vec![(root, 0usize)];
+        state[root] = 1;
+        while let Some((node, next)) = stack.pop() {
+            match outgoing[node].get(next) {
+                Some(&(to, index)) => {
+                    stack.push((node, next + 1));
+                    match state[to] {
+                        0 => {
+                            state[to] = 1;
+                            stack.push((to, 0));
+                        }
+                        1 => reversed[index] = true,
+                        _ => {}
+                    }
+                }
+                None => state[node] = 2,
+            }
+        }
+    }
+    edges
+        .iter()
+        .zip(reversed)
+        .filter(|((from, to), _)| from != to)
+        .map(|(&(from, to), reversed)| if reversed { (to, from) } else { (from, to) })
+        .collect()
+}
+
+// Layer of every node in an acyclic graph: one below its lowest predecessor.
+fn longest_path_layers(n: usize, edges: &[(usize, usize)]) -> Vec<usize> {
+    let mut incoming = vec![0usize; n];
+    let mut outgoing = vec![Vec::new(); n];
+    for &(from, to) in edges {
+        incoming[to] += 1;
+        outgoing[from].push(to);
+    }
+    let mut layer = vec![0usize; n];
+    let mut ready: VecDeque<usize> = (0..n).filter(|node| incoming[*node] == 0).collect();
+    while let Some(node) = ready.pop_front() {
+        for next in &outgoing[node] {
+            layer[*next] = layer[*next].max(layer[node] + 1);
+            incoming[*next] -= 1;
+            if incoming[*next] == 0 {
+                ready.push_back(*next);
+            }
+        }
+    }
+    layer
+}
+
+// Sort a layer by the mean position of each node's neighbours in the
+// adjacent layer; nodes without any keep their place.
+fn sort_by_barycenter(nodes: &mut [usize], neighbours: &[Vec<usize>], order: &[f64]) {
+    let mut keys: Vec<(usize, f64)> = nodes
+        .iter()
+        .map(|node| {
+            let key =
+                mean(neighbours[*node].iter().map(|other| order[*other])).unwrap_or(order[*node]);
+            (*node, key)
+        })
+        .collect();
+    keys.sort_by(|a, b| a.1.total_cmp(&b.1));
+    for (slot, (node, _)) in nodes.iter_mut().zip(keys) {
+        *slot = node;
+    }
+}
+
+// Put each node of a layer as near its wanted x as the order and gaps
+// allow. Packing from the left pushes crowded boxes right and packing from
+// the right pushes them left; the average of the two keeps the gaps and
+// drifts neither way.
+fn place_in_order(nodes: &[usize], wanted: &[f64], widths: &[f64], gap: f64, x: &mut [f64]) {
+    let separation = |i: usize| (widths[nodes[i]] + widths[nodes[i + 1]]) / 2.0 + gap;
+    let mut from_left = wanted.to_vec();
+    for i in 1..nodes.len() {
+        from_left[i] = from_left[i].max(from_left[i - 1] + separation(i - 1));
+    }
+    let mut from_right = wanted.to_vec();
+    for i in (0..nodes.len().saturating_sub(1)).rev() {
+        from_right[i] = from_right[i].min(from_right[i + 1] - separation(i));
+    }
+    for (i, node) in nodes.iter().enumerate() {
+        x[*node] = (from_left[i] + from_right[i]) / 2.0;
+    }
+}
+
+// Push overlapping boxes apart along the axis they overlap least on, until
+// every pair is `gap` apart or the passes run out.
+fn separate(centers: &mut [Point], sizes: &[Size], gap: f64) {
+    for _ in 0..SEPARATION_PASSES {
+        let mut moved = false;
+        for i in 0..centers.len() {
+            for j in i + 1..centers.len() {
+                let d = centers[j] - centers[i];
+                let overlap_x = (sizes[i].width + sizes[j].width) / 2.0 + gap - d.x.abs();
+                let overlap_y = (sizes[i].height + sizes[j].height) / 2.0 + gap - d.y.abs();
+                if overlap_x <= 0.0 || overlap_y <= 0.0 {
+                    continue;
+                }
+                let push = if overlap_x < overlap_y {
+                    Vec2::new(overlap_x.copysign(d.x) / 2.0, 0.0)
+                } else {
+                    Vec2::new(0.0, overlap_y.copysign(d.y) / 2.0)
+                };
+                centers[i] -= push;
+                centers[j] += push;
+                moved = true;
+            }
+        }
+        if !moved {
+            break;
+        }
+    }
+}
+
+// The y of each level's middle, levels stacked downwards `gap` apart and as
+// tall as their tallest box.
+fn level_centers(levels: &[usize], sizes: &[Size], gap: f64) -> Vec<f64> {
+    let count = levels.iter().max().map_or(0, |max| max + 1);
+    let mut heights = vec![0.0f64; count];
+    for (level, size) in levels.iter().zip(sizes) {
+        heights[*level] = heights[*level].max(size.height);
+    }
+    let mut top = 0.0;
+    let mut y = Vec::with_capacity(count);
+    for height in heights {
+        y.push(top + height / 2.0);
+        top += height + gap;
+    }
+    y
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), v| (sum + v, count + 1));
+    (count > 0).then(|| sum / count as f64)
+}
+
+fn bounds(centers: &[Point], sizes: &[Size]) -> Rect {
+    centers
+        .iter()
+        .zip(sizes)
+        .map(|(center, size)| Rect::from_center_size(*center, *size))
+        .reduce(|a, b| a.union(b))
+        .unwrap_or_default()
+}
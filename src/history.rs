@@ -0,0 +1,152 @@
+// Undo/redo. Edits are recorded after they have been applied to the editor,
+// and carry whatever they need to be reverted and re-applied.
+
+use vello::kurbo::BezPath;
+use vello::peniko::Mix;
+
+use crate::layout::{Container, Layout};
+use crate::shapes::Shape;
+use crate::signal::IrSignal;
+
+pub type ShapeId = usize;
+
+pub struct SignalChange {
+    pub signal: IrSignal,
+    pub from: f64,
+    pub to: f64,
+}
+
+pub enum Edit {
+    /// Signal values that changed, e.g. positions after a drag.
+    Set(Vec<SignalChange>),
+    /// Shapes that were added. While undone, `parked` holds them.
+    Insert {
+        ids: Vec<ShapeId>,
+        parked: Vec<Box<dyn Shape>>,
+    },
+    /// Shapes that were removed, held in `parked` until undone.
+    Remove {
+        ids: Vec<ShapeId>,
+        parked: Vec<Box<dyn Shape>>,
+    },
+    /// Shapes moved into the group in slot `group`. While undone, `parked`
+    /// holds the emptied group.
+    Group {
+        group: ShapeId,
+        children: Vec<ShapeId>,
+        parked: Option<Box<dyn Shape>>,
+    },
+    /// A group dissolved into its children; `parked` holds the emptied group
+    /// until undone.
+    Ungroup {
+        group: ShapeId,
+        children: Vec<ShapeId>,
+        parked: Option<Box<dyn Shape>>,
+    },
+    /// A path's outline before and after its points were edited.
+    Path {
+        id: ShapeId,
+        from: BezPath,
+        to: BezPath,
+    },
+    /// A text shape's content before and after typing.
+    Text {
+        id: ShapeId,
+        from: String,
+        to: String,
+    },
+    /// A group's mask before and after, as indices into its children.
+    Mask {
+        group: ShapeId,
+        from: Option<usize>,
+        to: Option<usize>,
+    },
+    /// A group's blend mode before and after.
+    Blend { group: ShapeId, from: Mix, to: Mix },
+    /// A group made a stack or grid. While undone, `parked` holds its
+    /// container.
+    Contain {
+        group: ShapeId,
+        parked: Option<Container>,
+    },
+    /// A stack or grid's layout before and after.
+    Layout {
+        container: ShapeId,
+        from: Layout,
+        to: Layout,
+    },
+    /// Several edits undone and redone as one step.
+    Batch(Vec<Edit>),
+}
+
+#[derive(Default)]
+pub struct History {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+    // while set, `Set` edits on the same signals extend the previous step
+    // instead of starting a new one (e.g. holding down an arrow key)
+    merging: bool,
+    // bumped by every edit made, undone or redone
+    revision: u64,
+}
+
+impl History {
+    pub fn push(&mut self, edit: Edit) {
+        self.redo.clear();
+        self.revision += 1;
+        if self.merging {
+            if let (Some(Edit::Set(previous)), Edit::Set(changes)) = (self.undo.last_mut(), &edit) {
+                let same_signals = previous.len() == changes.len()
+                    && previous
+                        .iter()
+                        .zip(changes)
+                        .all(|(a, b)| a.signal.set == b.signal.set);
+                if same_signals {
+                    for (previous, change) in previous.iter_mut().zip(changes) {
+                        previous.to = change.to;
+                    }
+                    return;
+                }
+            }
+        }
+        self.undo.push(edit);
+    }
+
+    pub fn set_merging(&mut self, merging: bool) {
+        self.merging = merging;
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn pop_undo(&mut self) -> Option<Edit> {
+        self.merging = false;
+        self.undo.pop()
+    }
+
+    pub fn pop_redo(&mut self) -> Option<Edit> {
+        self.merging = false;
+        self.redo.pop()
+    }
+
+    // used by the editor once a popped edit has been reverted or re-applied
+    pub fn push_undone(&mut self, edit: Edit) {
+        self.revision += 1;
+        self.redo.push(edit);
+    }
+
+    pub fn push_redone(&mut self, edit: Edit) {
+        self.revision += 1;
+        self.undo.push(edit);
+    }
+
+    /// Changes whenever the document does, i.e. on every edit, undo or redo.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+}
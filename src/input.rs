@@ -0,0 +1,211 @@
+// Pointer input in a device-independent form, plus the two-finger gesture
+// recogniser used for pinch-to-zoom and pan.
+
+use vello::kurbo::Point;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PointerType {
+    Mouse,
+    Touch,
+    Pen,
+}
+
+impl PointerType {
+    /// Parse a DOM `PointerEvent.pointerType`; unknown types behave like a mouse.
+    pub fn parse(name: &str) -> Self {
+        match name {
+            "touch" => PointerType::Touch,
+            "pen" => PointerType::Pen,
+            _ => PointerType::Mouse,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PointerPhase {
+    Down,
+    Move,
+    Up,
+    Cancel,
+}
+
+impl PointerPhase {
+    /// Parse a DOM event type such as `"pointerdown"`.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "pointerdown" => Some(PointerPhase::Down),
+            "pointermove" => Some(PointerPhase::Move),
+            "pointerup" => Some(PointerPhase::Up),
+            "pointercancel" | "pointerleave" => Some(PointerPhase::Cancel),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub meta: bool,
+}
+
+impl Modifiers {
+    pub const SHIFT: u8 = 1;
+    pub const CTRL: u8 = 2;
+    pub const ALT: u8 = 4;
+    pub const META: u8 = 8;
+
+    pub fn from_bits(bits: u8) -> Self {
+        Self {
+            shift: bits & Self::SHIFT != 0,
+            ctrl: bits & Self::CTRL != 0,
+            alt: bits & Self::ALT != 0,
+            meta: bits & Self::META != 0,
+        }
+    }
+}
+
+// `PointerEvent.buttons` bit for the primary (left) button, pen contact or touch
+pub const PRIMARY_BUTTON: u16 = 1;
+
+#[derive(Clone, Copy, Debug)]
+pub struct PointerEvent {
+    pub pointer_id: i32,
+    pub pointer_type: PointerType,
+    pub phase: PointerPhase,
+    // canvas pixels
+    pub x: f64,
+    pub y: f64,
+    pub buttons: u16,
+    pub modifiers: Modifiers,
+    // 0..1; the DOM reports 0.5 for mice with a button held
+    pub pressure: f64,
+    // event timestamp in seconds, used to measure pointer velocity
+    pub time: f64,
+}
+
+impl PointerEvent {
+    pub fn position(&self) -> Point {
+        Point::new(self.x, self.y)
+    }
+}
+
+/// Change of view produced by two fingers moving from `from` to `to`
+/// (their midpoints) while their distance changed by `scale`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Gesture {
+    pub from: Point,
+    pub to: Point,
+    pub scale: f64,
+}
+
+#[derive(Default)]
+pub struct GestureRecognizer {
+    // in the order they touched down; the first two drive the gesture
+    touches: Vec<(i32, Point)>,
+}
+
+impl GestureRecognizer {
+    pub fn is_active(&self) -> bool {
+        self.touches.len() >= 2
+    }
+
+    pub fn is_tracking(&self, pointer_id: i32) -> bool {
+        self.touches.iter().any(|(id, _)| *id == pointer_id)
+    }
+
+    pub fn touch_down(&mut self, pointer_id: i32, position: Point) {
+        if !self.is_tracking(pointer_id) {
+            self.touches.push((pointer_id, position));
+        }
+    }
+
+    pub fn touch_move(&mut self, pointer_id: i32, position: Point) -> Option<Gesture> {
+        let index = self.touches.iter().position(|(id, _)| *id == pointer_id)?;
+        let before = self.span();
+        self.touches[index].1 = position;
+        let after = self.span();
+
+        match (before, after) {
+            (Some((from, from_distance)), Some((to, to_distance))) if index < 2 => {
+                let scale = if from_distance > f64::EPSILON {
+                    to_distance / from_distance
+                } else {
+                    1.0
+                };
+                Some(Gesture { from, to, scale })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn touch_up(&mut self, pointer_id: i32) {
+        self.touches.retain(|(id, _)| *id != pointer_id);
+    }
+
+    // midpoint and distance of the first two touches
+    fn span(&self) -> Option<(Point, f64)> {
+        match self.touches.as_slice() {
+            [(_, a), (_, b), ..] => Some((a.midpoint(*b), a.distance(*b))),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_fingers_spreading_apart_pinch_about_their_midpoint() {
+        let mut gestures = GestureRecognizer::default();
+        gestures.touch_down(1, Point::new(40.0, 50.0));
+        assert!(!gestures.is_active());
+        // one finger alone doesn't steer the view
+        assert_eq!(gestures.touch_move(1, Point::new(45.0, 50.0)), None);
+        gestures.touch_down(2, Point::new(55.0, 50.0));
+        assert!(gestures.is_active());
+
+        let gesture = gestures.touch_move(2, Point::new(65.0, 50.0)).unwrap();
+        assert_eq!(gesture.from, Point::new(50.0, 50.0));
+        assert_eq!(gesture.to, Point::new(55.0, 50.0));
+        assert_eq!(gesture.scale, 2.0);
+    }
+
+    #[test]
+    fn two_fingers_moving_together_pan() {
+        let mut gestures = GestureRecognizer::default();
+        gestures.touch_down(1, Point::new(0.0, 0.0));
+        gestures.touch_down(2, Point::new(10.0, 0.0));
+
+        let first = gestures.touch_move(1, Point::new(0.0, 20.0)).unwrap();
+        let second = gestures.touch_move(2, Point::new(10.0, 20.0)).unwrap();
+        assert_eq!(first.from, Point::new(5.0, 0.0));
+        assert_eq!(second.to, Point::new(5.0, 20.0));
+        // the distance comes back to where it started
+        assert!((first.scale * second.scale - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn only_the_first_two_fingers_drive_the_gesture() {
+        let mut gestures = GestureRecognizer::default();
+        gestures.touch_down(1, Point::new(0.0, 0.0));
+        gestures.touch_down(2, Point::new(10.0, 0.0));
+        gestures.touch_down(3, Point::new(50.0, 50.0));
+        assert_eq!(gestures.touch_move(3, Point::new(60.0, 60.0)), None);
+        assert_eq!(gestures.touch_move(4, Point::new(60.0, 60.0)), None);
+
+        // lifting one of the pair hands its place to the third finger
+        gestures.touch_up(1);
+        assert!(gestures.is_active());
+        assert!(!gestures.is_tracking(1));
+        let gesture = gestures.touch_move(3, Point::new(30.0, 0.0)).unwrap();
+        assert_eq!(gesture.from, Point::new(35.0, 30.0));
+        assert_eq!(gesture.to, Point::new(20.0, 0.0));
+
+        gestures.touch_up(3);
+        assert!(!gestures.is_active());
+        assert_eq!(gestures.touch_move(2, Point::new(0.0, 0.0)), None);
+    }
+}
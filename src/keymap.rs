@@ -0,0 +1,207 @@
+// Keyboard shortcuts: a table from key chords to editor actions that the
+// host app can rebind or extend with its own named actions.
+
+use std::collections::HashMap;
+
+use crate::input::Modifiers;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    // lowercased DOM `KeyboardEvent.key`, e.g. "z", "arrowup", "delete"
+    pub key: String,
+    pub modifiers: Modifiers,
+}
+
+impl KeyChord {
+    pub fn new(key: &str, modifiers: Modifiers) -> Self {
+        Self {
+            key: key.to_lowercase(),
+            modifiers,
+        }
+    }
+
+    /// Parse chords like `"ctrl+shift+z"`, `"shift+ArrowUp"` or `"Delete"`.
+    /// `"space"` and `"plus"` name the keys that can't be written literally.
+    pub fn parse(spec: &str) -> Option<Self> {
+        let mut modifiers = Modifiers::default();
+        let mut key = None;
+        for part in spec.split('+') {
+            match part.trim().to_lowercase().as_str() {
+                "" => return None,
+                "ctrl" | "control" => modifiers.ctrl = true,
+                "shift" => modifiers.shift = true,
+                "alt" | "option" => modifiers.alt = true,
+                "meta" | "cmd" | "super" => modifiers.meta = true,
+                other if key.is_none() => {
+                    key = Some(match other {
+                        "space" => " ".to_string(),
+                        "plus" => "+".to_string(),
+                        _ => other.to_string(),
+                    })
+                }
+                _ => return None,
+            }
+        }
+        key.map(|key| Self { key, modifiers })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    Nudge { dx: f64, dy: f64 },
+    Delete,
+    Deselect,
+    SelectAll,
+    Undo,
+    Redo,
+    Duplicate,
+    Group,
+    Ungroup,
+    // switch to the named tool
+    Tool(String),
+    // a key the active tool used itself, e.g. typing text; not bindable
+    ToolInput,
+    // handled by the host app; the editor only reports it
+    Custom(String),
+}
+
+impl Action {
+    /// Parse an action name as used by `VelloContext::bind_key`. Anything
+    /// that isn't a built-in action becomes `Custom`, except `tool_input`,
+    /// which only reports keys a tool used and can't be bound.
+    pub fn parse(spec: &str) -> Option<Self> {
+        let action = match spec {
+            "delete" => Action::Delete,
+            "deselect" => Action::Deselect,
+            "select_all" => Action::SelectAll,
+            "undo" => Action::Undo,
+            "redo" => Action::Redo,
+            "duplicate" => Action::Duplicate,
+            "group" => Action::Group,
+            "ungroup" => Action::Ungroup,
+            "tool_input" => return None,
+            _ => {
+                if let Some(tool) = spec.strip_prefix("tool:") {
+                    return Some(Action::Tool(tool.to_string()));
+                }
+                // "nudge:dx,dy"
+                let nudge = spec.strip_prefix("nudge:").and_then(|args| {
+                    let (dx, dy) = args.split_once(',')?;
+                    Some(Action::Nudge {
+                        dx: dx.trim().parse().ok()?,
+                        dy: dy.trim().parse().ok()?,
+                    })
+                });
+                nudge.unwrap_or_else(|| Action::Custom(spec.to_string()))
+            }
+        };
+        Some(action)
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Action::Nudge { dx, dy } => format!("nudge:{},{}", dx, dy),
+            Action::Delete => "delete".to_string(),
+            Action::Deselect => "deselect".to_string(),
+            Action::SelectAll => "select_all".to_string(),
+            Action::Undo => "undo".to_string(),
+            Action::Redo => "redo".to_string(),
+            Action::Duplicate => "duplicate".to_string(),
+            Action::Group => "group".to_string(),
+            Action::Ungroup => "ungroup".to_string(),
+            Action::Tool(tool) => format!("tool:{}", tool),
+            Action::ToolInput => "tool_input".to_string(),
+            Action::Custom(name) => name.clone(),
+        }
+    }
+}
+
+// world units moved per arrow key press, without and with shift
+const NUDGE_STEP: f64 = 1.0;
+const NUDGE_STEP_LARGE: f64 = 10.0;
+
+pub struct Keymap {
+    bindings: HashMap<KeyChord, Action>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut keymap = Self::empty();
+
+        for (key, dx, dy) in [
+            ("arrowleft", -1.0, 0.0),
+            ("arrowright", 1.0, 0.0),
+            ("arrowup", 0.0, -1.0),
+            ("arrowdown", 0.0, 1.0),
+        ] {
+            keymap.bind_str(
+                key,
+                Action::Nudge {
+                    dx: dx * NUDGE_STEP,
+                    dy: dy * NUDGE_STEP,
+                },
+            );
+            keymap.bind_str(
+                &format!("shift+{}", key),
+                Action::Nudge {
+                    dx: dx * NUDGE_STEP_LARGE,
+                    dy: dy * NUDGE_STEP_LARGE,
+                },
+            );
+        }
+
+        keymap.bind_str("delete", Action::Delete);
+        keymap.bind_str("backspace", Action::Delete);
+        keymap.bind_str("escape", Action::Deselect);
+
+        for (key, tool) in [
+            ("v", "select"),
+            ("r", "rectangle"),
+            ("o", "ellipse"),
+            ("l", "line"),
+            ("p", "pen"),
+            ("h", "hand"),
+            ("t", "text"),
+        ] {
+            keymap.bind_str(key, Action::Tool(tool.to_string()));
+        }
+
+        // ctrl on Windows/Linux, cmd on macOS
+        for primary in ["ctrl", "meta"] {
+            keymap.bind_str(&format!("{}+z", primary), Action::Undo);
+            keymap.bind_str(&format!("{}+shift+z", primary), Action::Redo);
+            keymap.bind_str(&format!("{}+y", primary), Action::Redo);
+            keymap.bind_str(&format!("{}+d", primary), Action::Duplicate);
+            keymap.bind_str(&format!("{}+a", primary), Action::SelectAll);
+            keymap.bind_str(&format!("{}+g", primary), Action::Group);
+            keymap.bind_str(&format!("{}+shift+g", primary), Action::Ungroup);
+        }
+
+        keymap
+    }
+}
+
+impl Keymap {
+    pub fn empty() -> Self {
+        Self {
+            bindings: HashMap::new(),
+        }
+    }
+
+    pub fn bind(&mut self, chord: KeyChord, action: Action) {
+        self.bindings.insert(chord, action);
+    }
+
+    pub fn unbind(&mut self, chord: &KeyChord) -> Option<Action> {
+        self.bindings.remove(chord)
+    }
+
+    pub fn action_for(&self, chord: &KeyChord) -> Option<&Action> {
+        self.bindings.get(chord)
+    }
+
+    fn bind_str(&mut self, spec: &str, action: Action) {
+        let chord = KeyChord::parse(spec).expect("invalid built-in key chord");
+        self.bind(chord, action);
+    }
+}
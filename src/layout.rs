@@ -0,0 +1,286 @@
+// Stacks and grids: where a container puts its children, worked out from
+// the container's size and the children's sizes alone. Groups with a
+// `Container` are laid out again whenever the editor updates followers, so
+// resizing one moves its children straight away.
+
+use vello::kurbo::{Point, Rect, Size};
+
+use crate::constraints::Axis;
+use crate::signal::IrSignal;
+
+/// Where a child sits across its line of a stack, or within its grid cell.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Align {
+    Start,
+    Center,
+    End,
+}
+
+impl Align {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "start" => Some(Align::Start),
+            "center" => Some(Align::Center),
+            "end" => Some(Align::End),
+            _ => None,
+        }
+    }
+
+    // offset into `room` for something `size` long
+    fn offset(self, room: f64, size: f64) -> f64 {
+        match self {
+            Align::Start => 0.0,
+            Align::Center => (room - size) / 2.0,
+            Align::End => room - size,
+        }
+    }
+}
+
+/// How a stack's line shares out the room left along its axis.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Justify {
+    Start,
+    Center,
+    End,
+    SpaceBetween,
+}
+
+impl Justify {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "start" => Some(Justify::Start),
+            "center" => Some(Justify::Center),
+            "end" => Some(Justify::End),
+            "space-between" => Some(Justify::SpaceBetween),
+            _ => None,
+        }
+    }
+}
+
+/// Children one after another along `axis`, `gap` apart and `padding` in
+/// from the container's edges. With `wrap`, a child that would run past the
+/// end starts a new line, `gap` further across.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stack {
+    pub axis: Axis,
+    pub gap: f64,
+    pub padding: f64,
+    pub justify: Justify,
+    pub align: Align,
+    pub wrap: bool,
+}
+
+impl Stack {
+    pub fn new(axis: Axis) -> Self {
+        Self {
+            axis,
+            gap: 10.0,
+            padding: 10.0,
+            justify: Justify::Start,
+            align: Align::Start,
+            wrap: false,
+        }
+    }
+}
+
+/// Children in rows of `columns` equal-width cells that share the
+/// container's width; each row is as tall as its tallest child.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Grid {
+    pub columns: usize,
+    pub gap: f64,
+    pub padding: f64,
+    pub align: Align,
+}
+
+impl Grid {
+    pub fn new(columns: usize) -> Self {
+        Self {
+            columns,
+            gap: 10.0,
+            padding: 10.0,
+            align: Align::Start,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Layout {
+    Stack(Stack),
+    Grid(Grid),
+}
+
+impl Layout {
+    /// Top-left corners for children of the given sizes in a container of
+    /// `size`, relative to the container's own top-left.
+    pub fn arrange(&self, size: Size, children: &[Size]) -> Vec<Point> {
+        match self {
+            Layout::Stack(stack) => arrange_stack(stack, size, children),
+            Layout::Grid(grid) => arrange_grid(grid, size, children),
+        }
+    }
+
+    /// The smallest container that holds the children without a stack
+    /// wrapping or a grid's cells being narrower than its widest child.
+    pub fn fit(&self, children: &[Size]) -> Size {
+        match self {
+            Layout::Stack(stack) => {
+                let along: f64 = children.iter().map(|size| main(stack.axis, *size)).sum();
+                let across = children
+                    .iter()
+                    .map(|size| cross(stack.axis, *size))
+                    .fold(0.0, f64::max);
+                let gaps = stack.gap * children.len().saturating_sub(1) as f64;
+                let fitted = oriented(stack.axis, along + gaps, across);
+                Size::new(
+                    fitted.width + 2.0 * stack.padding,
+                    fitted.height + 2.0 * stack.padding,
+                )
+            }
+            Layout::Grid(grid) => {
+                let columns = grid.columns.max(1);
+                let cell = children.iter().map(|size| size.width).fold(0.0, f64::max);
+                let rows: Vec<f64> = children
+                    .chunks(columns)
+                    .map(|row| row.iter().map(|size| size.height).fold(0.0, f64::max))
+                    .collect();
+                let width = cell * columns as f64 + grid.gap * (columns - 1) as f64;
+                let height =
+                    rows.iter().sum::<f64>() + grid.gap * rows.len().saturating_sub(1) as f64;
+                Size::new(width + 2.0 * grid.padding, height + 2.0 * grid.padding)
+            }
+        }
+    }
+}
+
+/// Makes a group a stack or grid of the given size. The size is animatable
+/// as the group's "width" and "height".
+pub struct Container {
+    pub layout: Layout,
+    pub width: IrSignal,
+    pub height: IrSignal,
+}
+
+impl Container {
+    pub fn new(layout: Layout, size: Size) -> Self {
+        Self {
+            layout,
+            width: IrSignal::new(size.width),
+            height: IrSignal::new(size.height),
+        }
+    }
+
+    pub fn size(&self) -> Size {
+        Size::new(self.width.get().max(0.0), self.height.get().max(0.0))
+    }
+
+    // the container's box in the group's own space
+    pub fn rect(&self) -> Rect {
+        Rect::from_origin_size(Point::ORIGIN, self.size())
+    }
+
+    pub fn duplicate(&self) -> Self {
+        Self::new(self.layout, self.size())
+    }
+}
+
+fn main(axis: Axis, size: Size) -> f64 {
+    match axis {
+        Axis::Horizontal => size.width,
+        Axis::Vertical => size.height,
+    }
+}
+
+fn cross(axis: Axis, size: Size) -> f64 {
+    match axis {
+        Axis::Horizontal => size.height,
+        Axis::Vertical => size.width,
+    }
+}
+
+// a size given along and across `axis`
+fn oriented(axis: Axis, along: f64, across: f64) -> Size {
+    match axis {
+        Axis::Horizontal => Size::new(along, across),
+        Axis::Vertical => Size::new(across, along),
+    }
+}
+
+fn arrange_stack(stack: &Stack, size: Size, children: &[Size]) -> Vec<Point> {
+    let axis = stack.axis;
+    let room = main(axis, size) - 2.0 * stack.padding;
+
+    // break into lines where a child would run past the end
+    let mut lines: Vec<Vec<usize>> = Vec::new();
+    let mut used = 0.0;
+    for (index, child) in children.iter().enumerate() {
+        let length = main(axis, *child);
+        match lines.last_mut() {
+            Some(line) if !stack.wrap || used + stack.gap + length <= room => {
+                line.push(index);
+                used += stack.gap + length;
+            }
+            _ => {
+                lines.push(vec![index]);
+                used = length;
+            }
+        }
+    }
+
+    let mut places = vec![Point::ORIGIN; children.len()];
+    let mut line_start = stack.padding;
+    for line in &lines {
+        let lengths: f64 = line.iter().map(|index| main(axis, children[*index])).sum();
+        let free = room - lengths - stack.gap * (line.len() - 1) as f64;
+        let (mut along, spacing) = match stack.justify {
+            Justify::Start => (0.0, stack.gap),
+            Justify::Center => (free / 2.0, stack.gap),
+            Justify::End => (free, stack.gap),
+            Justify::SpaceBetween if line.len() > 1 => {
+                (0.0, stack.gap + free.max(0.0) / (line.len() - 1) as f64)
+            }
+            Justify::SpaceBetween => (0.0, stack.gap),
+        };
+        // a single line spans the whole container, so it can be centered
+        let thickness = if lines.len() == 1 {
+            cross(axis, size) - 2.0 * stack.padding
+        } else {
+            line.iter()
+                .map(|index| cross(axis, children[*index]))
+                .fold(0.0, f64::max)
+        };
+        for index in line {
+            let child = children[*index];
+            let across = line_start + stack.align.offset(thickness, cross(axis, child));
+            let along_edge = stack.padding + along;
+            places[*index] = match axis {
+                Axis::Horizontal => Point::new(along_edge, across),
+                Axis::Vertical => Point::new(across, along_edge),
+            };
+            along += main(axis, child) + spacing;
+        }
+        line_start += thickness + stack.gap;
+    }
+    places
+}
+
+fn arrange_grid(grid: &Grid, size: Size, children: &[Size]) -> Vec<Point> {
+    let columns = grid.columns.max(1);
+    let inner = size.width - 2.0 * grid.padding;
+    let cell = ((inner - grid.gap * (columns - 1) as f64) / columns as f64).max(0.0);
+
+    let mut places = Vec::with_capacity(children.len());
+    let mut top = grid.padding;
+    for row in children.chunks(columns) {
+        let height = row.iter().map(|size| size.height).fold(0.0, f64::max);
+        for (column, child) in row.iter().enumerate() {
+            let left = grid.padding + column as f64 * (cell + grid.gap);
+            places.push(Point::new(
+                left + grid.align.offset(cell, child.width),
+                top + grid.align.offset(height, child.height),
+            ));
+        }
+        top += height + grid.gap;
+    }
+    places
+}
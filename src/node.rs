@@ -0,0 +1,94 @@
+use crate::signal::IrSignal;
+
+#[derive(Clone)]
+pub struct Node {
+    pub x: IrSignal,
+    pub y: IrSignal,
+    pub scale_x: IrSignal,
+    pub scale_y: IrSignal,
+    pub rotation: IrSignal,
+    pub following: Option<(usize, (f64, f64))>, // (target_index, initial_offset)
+}
+
+impl Node {
+    pub fn new(x: f64, y: f64) -> Self {
+        Self {
+            x: IrSignal::new(x),
+            y: IrSignal::new(y),
+            scale_x: IrSignal::new(1.0),
+            scale_y: IrSignal::new(1.0),
+            rotation: IrSignal::new(0.0),
+            following: None,
+        }
+    }
+
+    /// Look up a transform property by name, e.g. for animation.
+    pub fn signal(&self, name: &str) -> Option<IrSignal> {
+        match name {
+            "x" => Some(self.x),
+            "y" => Some(self.y),
+            "scale_x" => Some(self.scale_x),
+            "scale_y" => Some(self.scale_y),
+            "rotation" => Some(self.rotation),
+            _ => None,
+        }
+    }
+
+    /// A node with fresh signals holding the same values, not following
+    /// anything.
+    pub fn duplicate(&self) -> Self {
+        let node = Node::new(self.x.get(), self.y.get());
+        node.scale_x.set(self.scale_x.get());
+        node.scale_y.set(self.scale_y.get());
+        node.rotation.set(self.rotation.get());
+        node
+    }
+
+    pub fn start_following(&mut self, other: &Node, target_index: usize) {
+        // Calculate initial offset in target's local space
+        let dx = self.x.get() - other.x.get();
+        let dy = self.y.get() - other.y.get();
+
+        // Store target and offset
+        self.following = Some((target_index, (dx, dy)));
+
+        // Initial update
+        self.update_transform(other);
+    }
+
+    pub fn update_transform(&mut self, target: &Node) {
+        if let Some((x, y)) = self.followed_position(target) {
+            // Set new position
+            self.x.set(x);
+            self.y.set(y);
+
+            // Match rotation and scale
+            self.rotation.set(target.rotation.get());
+            self.scale_x.set(target.scale_x.get());
+            self.scale_y.set(target.scale_y.get());
+        }
+    }
+
+    /// Where following `target` puts this node, without moving it.
+    pub fn followed_position(&self, target: &Node) -> Option<(f64, f64)> {
+        let (_, (dx, dy)) = self.following?;
+
+        // Apply target's rotation to offset
+        let rot = target.rotation.get();
+        let cos_rot = rot.cos();
+        let sin_rot = rot.sin();
+
+        // Rotate and scale offset
+        let scaled_dx = dx * target.scale_x.get();
+        let scaled_dy = dy * target.scale_y.get();
+
+        let rotated_dx = scaled_dx * cos_rot - scaled_dy * sin_rot;
+        let rotated_dy = scaled_dx * sin_rot + scaled_dy * cos_rot;
+
+        Some((target.x.get() + rotated_dx, target.y.get() + rotated_dy))
+    }
+
+    pub fn unfollow(&mut self) {
+        self.following = None;
+    }
+}
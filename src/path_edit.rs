@@ -0,0 +1,496 @@
+// Node editing for paths. A `BezPath` is taken apart into anchors with
+// optional Bézier handles, edited, and put back together. Whether an anchor
+// is a corner, smooth or symmetric isn't stored in a `BezPath`, so it is
+// inferred from the handles when editing starts.
+
+use vello::kurbo::{
+    Affine, BezPath, Circle, CubicBez, Line, ParamCurve, ParamCurveNearest, PathEl, Point, Rect,
+    Stroke, Vec2,
+};
+use vello::peniko::{Color, Fill};
+use vello::Scene;
+
+const OVERLAY_COLOR: Color = Color::from_rgb8(0, 150, 255);
+const ANCHOR_SIZE: f64 = 7.0;
+const HANDLE_RADIUS: f64 = 3.5;
+// handles closer than this to their anchor count as missing
+const DEGENERATE: f64 = 1e-9;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PointKind {
+    // handles move independently
+    Corner,
+    // handles stay on one line but keep their own lengths
+    Smooth,
+    // handles mirror each other
+    Symmetric,
+}
+
+impl PointKind {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "corner" => Some(PointKind::Corner),
+            "smooth" => Some(PointKind::Smooth),
+            "symmetric" => Some(PointKind::Symmetric),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Anchor {
+    pub point: Point,
+    // control point of the segment arriving here; `None` if it is straight
+    // at this end
+    pub handle_in: Option<Point>,
+    // control point of the segment leaving here
+    pub handle_out: Option<Point>,
+    pub kind: PointKind,
+}
+
+impl Anchor {
+    fn corner(point: Point) -> Self {
+        Self {
+            point,
+            handle_in: None,
+            handle_out: None,
+            kind: PointKind::Corner,
+        }
+    }
+
+    fn translate(&mut self, delta: Vec2) {
+        self.point += delta;
+        self.handle_in = self.handle_in.map(|handle| handle + delta);
+        self.handle_out = self.handle_out.map(|handle| handle + delta);
+    }
+
+    // the kind the handles look like they have
+    fn infer_kind(&self) -> PointKind {
+        let (handle_in, handle_out) = match (self.handle_in, self.handle_out) {
+            (Some(handle_in), Some(handle_out)) => (handle_in, handle_out),
+            _ => return PointKind::Corner,
+        };
+        let (a, b) = (self.point - handle_in, handle_out - self.point);
+        let (a_length, b_length) = (a.hypot(), b.hypot());
+        if a_length < DEGENERATE || b_length < DEGENERATE {
+            return PointKind::Corner;
+        }
+        let scale = a_length * b_length;
+        if a.cross(b).abs() > 1e-6 * scale || a.dot(b) <= 0.0 {
+            PointKind::Corner
+        } else if (a_length - b_length).abs() <= 1e-6 * a_length.max(b_length) {
+            PointKind::Symmetric
+        } else {
+            PointKind::Smooth
+        }
+    }
+}
+
+/// One subpath: anchors joined by straight or cubic segments.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Contour {
+    pub anchors: Vec<Anchor>,
+    pub closed: bool,
+}
+
+impl Contour {
+    fn next(&self, index: usize) -> Option<usize> {
+        if index + 1 < self.anchors.len() {
+            Some(index + 1)
+        } else if self.closed && self.anchors.len() > 1 {
+            Some(0)
+        } else {
+            None
+        }
+    }
+
+    fn previous(&self, index: usize) -> Option<usize> {
+        if index > 0 {
+            Some(index - 1)
+        } else if self.closed && self.anchors.len() > 1 {
+            Some(self.anchors.len() - 1)
+        } else {
+            None
+        }
+    }
+}
+
+/// Which part of an anchor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Handle {
+    Anchor,
+    In,
+    Out,
+}
+
+/// An anchor by contour and position within it. As a segment, the one
+/// leaving that anchor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PathPoint {
+    pub contour: usize,
+    pub index: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EditablePath {
+    pub contours: Vec<Contour>,
+}
+
+impl EditablePath {
+    pub fn from_path(path: &BezPath) -> Self {
+        let mut contours = Vec::new();
+        let mut current: Option<Contour> = None;
+        for el in path.elements() {
+            let (c1, c2, to) = match *el {
+                PathEl::MoveTo(p) => {
+                    contours.extend(current.take());
+                    current = Some(Contour {
+                        anchors: vec![Anchor::corner(p)],
+                        closed: false,
+                    });
+                    continue;
+                }
+                PathEl::ClosePath => {
+                    if let Some(mut contour) = current.take() {
+                        // a segment back to the start is the closing one
+                        if contour.anchors.len() > 1
+                            && contour.anchors.first().map(|a| a.point)
+                                == contour.anchors.last().map(|a| a.point)
+                        {
+                            let last = contour.anchors.pop().unwrap();
+                            contour.anchors[0].handle_in = last.handle_in;
+                        }
+                        contour.closed = true;
+                        contours.push(contour);
+                    }
+                    continue;
+                }
+                PathEl::LineTo(p) => (None, None, p),
+                PathEl::QuadTo(c, p) => {
+                    // raised to a cubic with the same shape
+                    let from = current
+                        .as_ref()
+                        .and_then(|contour| contour.anchors.last())
+                        .map_or(c, |anchor| anchor.point);
+                    (Some(from.lerp(c, 2.0 / 3.0)), Some(p.lerp(c, 2.0 / 3.0)), p)
+                }
+                PathEl::CurveTo(c1, c2, p) => (Some(c1), Some(c2), p),
+            };
+            let contour = current.get_or_insert_with(|| Contour {
+                anchors: Vec::new(),
+                closed: false,
+            });
+            if let Some(last) = contour.anchors.last_mut() {
+                last.handle_out = c1;
+            }
+            contour.anchors.push(Anchor {
+                point: to,
+                handle_in: c2,
+                handle_out: None,
+                kind: PointKind::Corner,
+            });
+        }
+        contours.extend(current);
+
+        for anchor in contours.iter_mut().flat_map(|c| c.anchors.iter_mut()) {
+            anchor.kind = anchor.infer_kind();
+        }
+        Self { contours }
+    }
+
+    pub fn to_path(&self) -> BezPath {
+        let mut path = BezPath::new();
+        for contour in &self.contours {
+            let first = match contour.anchors.first() {
+                Some(first) => first,
+                None => continue,
+            };
+            path.move_to(first.point);
+            for pair in contour.anchors.windows(2) {
+                push_segment(&mut path, &pair[0], &pair[1]);
+            }
+            if contour.closed {
+                if contour.anchors.len() > 1 {
+                    push_segment(&mut path, contour.anchors.last().unwrap(), first);
+                }
+                path.close_path();
+            }
+        }
+        path
+    }
+
+    pub fn anchor(&self, at: PathPoint) -> Option<&Anchor> {
+        self.contours.get(at.contour)?.anchors.get(at.index)
+    }
+
+    fn anchor_mut(&mut self, at: PathPoint) -> Option<&mut Anchor> {
+        self.contours.get_mut(at.contour)?.anchors.get_mut(at.index)
+    }
+
+    /// The segment leaving `at`, as a cubic even if it is straight.
+    pub fn segment(&self, at: PathPoint) -> Option<CubicBez> {
+        let contour = self.contours.get(at.contour)?;
+        let from = contour.anchors.get(at.index)?;
+        let to = &contour.anchors[contour.next(at.index)?];
+        Some(CubicBez::new(
+            from.point,
+            from.handle_out.unwrap_or(from.point),
+            to.handle_in.unwrap_or(to.point),
+            to.point,
+        ))
+    }
+
+    fn segments(&self) -> impl Iterator<Item = (PathPoint, CubicBez)> + '_ {
+        self.contours
+            .iter()
+            .enumerate()
+            .flat_map(|(contour, c)| {
+                (0..c.anchors.len()).map(move |index| PathPoint { contour, index })
+            })
+            .filter_map(move |at| Some((at, self.segment(at)?)))
+    }
+
+    /// The anchor or handle within `radius` of `point`, nearest first, with
+    /// both measured after `transform` (e.g. in canvas pixels).
+    pub fn hit(&self, transform: Affine, point: Point, radius: f64) -> Option<(PathPoint, Handle)> {
+        let mut best: Option<(PathPoint, Handle, f64)> = None;
+        for (contour, c) in self.contours.iter().enumerate() {
+            for (index, anchor) in c.anchors.iter().enumerate() {
+                let at = PathPoint { contour, index };
+                // handles first, so they win ties with their anchor
+                let parts = [
+                    (Handle::In, anchor.handle_in),
+                    (Handle::Out, anchor.handle_out),
+                    (Handle::Anchor, Some(anchor.point)),
+                ];
+                for (handle, position) in parts {
+                    let position = match position {
+                        Some(position) => transform * position,
+                        None => continue,
+                    };
+                    let distance = (position - point).hypot();
+                    if distance <= radius && best.is_none_or(|(_, _, d)| distance < d) {
+                        best = Some((at, handle, distance));
+                    }
+                }
+            }
+        }
+        best.map(|(at, handle, _)| (at, handle))
+    }
+
+    /// The segment passing within `radius` of `point` (both measured after
+    /// `transform`), and the parameter on it nearest to the point.
+    pub fn nearest_segment(
+        &self,
+        transform: Affine,
+        point: Point,
+        radius: f64,
+    ) -> Option<(PathPoint, f64)> {
+        self.segments()
+            .map(|(at, segment)| (at, (transform * segment).nearest(point, 1e-6)))
+            .filter(|(_, nearest)| nearest.distance_sq <= radius * radius)
+            .min_by(|a, b| a.1.distance_sq.total_cmp(&b.1.distance_sq))
+            .map(|(at, nearest)| (at, nearest.t))
+    }
+
+    /// Move one part of an anchor to `to`. Anchors carry their handles with
+    /// them; a handle of a smooth or symmetric anchor turns the other one.
+    pub fn move_handle(&mut self, at: PathPoint, handle: Handle, to: Point) -> bool {
+        let (has_in, has_out) = match self.contours.get(at.contour) {
+            Some(contour) if at.index < contour.anchors.len() => (
+                contour.previous(at.index).is_some(),
+                contour.next(at.index).is_some(),
+            ),
+            _ => return false,
+        };
+        let anchor = self.anchor_mut(at).unwrap();
+        let (moved, opposite, has_opposite) = match handle {
+            Handle::Anchor => {
+                anchor.translate(to - anchor.point);
+                return true;
+            }
+            Handle::In => (&mut anchor.handle_in, &mut anchor.handle_out, has_out),
+            Handle::Out => (&mut anchor.handle_out, &mut anchor.handle_in, has_in),
+        };
+        *moved = Some(to);
+
+        let arm = to - anchor.point;
+        if arm.hypot() < DEGENERATE {
+            return true;
+        }
+        match anchor.kind {
+            PointKind::Corner => {}
+            PointKind::Smooth => {
+                if let Some(other) = *opposite {
+                    let length = (other - anchor.point).hypot();
+                    *opposite = Some(anchor.point - arm / arm.hypot() * length);
+                }
+            }
+            PointKind::Symmetric => {
+                if has_opposite {
+                    *opposite = Some(anchor.point - arm);
+                }
+            }
+        }
+        true
+    }
+
+    /// Change how an anchor's handles move together. Making a point smooth
+    /// or symmetric lines its handles up, pulling them out along the
+    /// neighbours' direction if it had none.
+    pub fn set_kind(&mut self, at: PathPoint, kind: PointKind) -> bool {
+        let contour = match self.contours.get(at.contour) {
+            Some(contour) if at.index < contour.anchors.len() => contour,
+            _ => return false,
+        };
+        let previous = contour.previous(at.index).map(|i| contour.anchors[i].point);
+        let next = contour.next(at.index).map(|i| contour.anchors[i].point);
+        let anchor = self.anchor_mut(at).unwrap();
+        anchor.kind = kind;
+        if kind == PointKind::Corner {
+            return true;
+        }
+
+        let point = anchor.point;
+        let (in_length, out_length, direction) = match (anchor.handle_in, anchor.handle_out) {
+            (Some(handle_in), Some(handle_out)) => (
+                (point - handle_in).hypot(),
+                (handle_out - point).hypot(),
+                handle_out - handle_in,
+            ),
+            // a third of the way to each neighbour, like a smooth curve
+            // through them would have
+            _ => (
+                previous.map_or(0.0, |p| (point - p).hypot() / 3.0),
+                next.map_or(0.0, |p| (p - point).hypot() / 3.0),
+                next.unwrap_or(point) - previous.unwrap_or(point),
+            ),
+        };
+        if direction.hypot() < DEGENERATE {
+            return true;
+        }
+        let direction = direction / direction.hypot();
+        let (in_length, out_length) = match kind {
+            PointKind::Symmetric => {
+                let length = if previous.is_some() && next.is_some() {
+                    (in_length + out_length) / 2.0
+                } else {
+                    in_length.max(out_length)
+                };
+                (length, length)
+            }
+            _ => (in_length, out_length),
+        };
+        if previous.is_some() {
+            anchor.handle_in = Some(point - direction * in_length);
+        }
+        if next.is_some() {
+            anchor.handle_out = Some(point + direction * out_length);
+        }
+        true
+    }
+
+    /// Split the segment leaving `at` at parameter `t`, without changing
+    /// the outline, and return the new anchor.
+    pub fn insert_point(&mut self, at: PathPoint, t: f64) -> Option<PathPoint> {
+        let segment = self.segment(at)?;
+        let contour = &mut self.contours[at.contour];
+        let next = contour.next(at.index)?;
+        let index = at.index + 1;
+        let straight = contour.anchors[at.index].handle_out.is_none()
+            && contour.anchors[next].handle_in.is_none();
+        if straight {
+            let point = segment.p0.lerp(segment.p3, t);
+            contour.anchors.insert(index, Anchor::corner(point));
+        } else {
+            let (first, second) = (segment.subsegment(0.0..t), segment.subsegment(t..1.0));
+            contour.anchors[at.index].handle_out = Some(first.p1);
+            contour.anchors[next].handle_in = Some(second.p2);
+            contour.anchors.insert(
+                index,
+                Anchor {
+                    point: first.p3,
+                    handle_in: Some(first.p2),
+                    handle_out: Some(second.p1),
+                    kind: PointKind::Smooth,
+                },
+            );
+        }
+        Some(PathPoint {
+            contour: at.contour,
+            index,
+        })
+    }
+
+    /// Remove an anchor, joining its neighbours with the handles they
+    /// already had. A contour left with a single anchor goes too.
+    pub fn delete_point(&mut self, at: PathPoint) -> bool {
+        let contour = match self.contours.get_mut(at.contour) {
+            Some(contour) if at.index < contour.anchors.len() => contour,
+            _ => return false,
+        };
+        contour.anchors.remove(at.index);
+        if contour.anchors.len() < 2 {
+            self.contours.remove(at.contour);
+        } else if !contour.closed {
+            // the ends of an open contour have nothing beyond them
+            contour.anchors[0].handle_in = None;
+            contour.anchors.last_mut().unwrap().handle_out = None;
+        }
+        true
+    }
+}
+
+fn push_segment(path: &mut BezPath, from: &Anchor, to: &Anchor) {
+    match (from.handle_out, to.handle_in) {
+        (None, None) => path.line_to(to.point),
+        (c1, c2) => path.curve_to(c1.unwrap_or(from.point), c2.unwrap_or(to.point), to.point),
+    }
+}
+
+/// Draw the outline, anchors and handles of a path being edited.
+/// `transform` maps the path to the canvas, so the markers stay the same
+/// size at any zoom.
+pub fn draw_overlay(
+    scene: &mut Scene,
+    path: &EditablePath,
+    selected: Option<PathPoint>,
+    transform: Affine,
+) {
+    let stroke = Stroke::new(1.0);
+    scene.stroke(
+        &stroke,
+        Affine::IDENTITY,
+        OVERLAY_COLOR,
+        None,
+        &(transform * path.to_path()),
+    );
+
+    for (contour, c) in path.contours.iter().enumerate() {
+        for (index, anchor) in c.anchors.iter().enumerate() {
+            let point = transform * anchor.point;
+            for handle in [anchor.handle_in, anchor.handle_out].iter().flatten() {
+                let handle = transform * *handle;
+                scene.stroke(
+                    &stroke,
+                    Affine::IDENTITY,
+                    OVERLAY_COLOR,
+                    None,
+                    &Line::new(point, handle),
+                );
+                let marker = Circle::new(handle, HANDLE_RADIUS);
+                scene.fill(Fill::NonZero, Affine::IDENTITY, Color::WHITE, None, &marker);
+                scene.stroke(&stroke, Affine::IDENTITY, OVERLAY_COLOR, None, &marker);
+            }
+
+            let marker = Rect::from_center_size(point, (ANCHOR_SIZE, ANCHOR_SIZE));
+            let fill = if selected == Some(PathPoint { contour, index }) {
+                OVERLAY_COLOR
+            } else {
+                Color::WHITE
+            };
+            scene.fill(Fill::NonZero, Affine::IDENTITY, fill, None, &marker);
+            scene.stroke(&stroke, Affine::IDENTITY, OVERLAY_COLOR, None, &marker);
+        }
+    }
+}
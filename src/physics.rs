@@ -0,0 +1,260 @@
+// Spring and friction integrators that pull `IrSignal`s towards targets, for
+// motion that should feel physical rather than follow a fixed curve.
+
+use crate::signal::IrSignal;
+
+// integration step; larger frame gaps are split so stiff springs stay stable
+const MAX_STEP: f64 = 1.0 / 120.0;
+// a frame gap longer than this (e.g. a background tab) is treated as this long
+const MAX_FRAME: f64 = 0.1;
+
+const REST_DISTANCE: f64 = 0.01;
+const REST_VELOCITY: f64 = 0.01;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpringConfig {
+    pub stiffness: f64,
+    pub damping: f64,
+    pub mass: f64,
+}
+
+impl Default for SpringConfig {
+    // slightly underdamped: settles quickly with a hint of overshoot
+    fn default() -> Self {
+        Self {
+            stiffness: 170.0,
+            damping: 26.0,
+            mass: 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Spring {
+    pub config: SpringConfig,
+    pub value: f64,
+    pub velocity: f64,
+    pub target: f64,
+}
+
+impl Spring {
+    pub fn new(value: f64, target: f64, config: SpringConfig) -> Self {
+        Self {
+            config,
+            value,
+            velocity: 0.0,
+            target,
+        }
+    }
+
+    pub fn step(&mut self, dt: f64) {
+        let mut remaining = dt;
+        while remaining > 0.0 {
+            let h = remaining.min(MAX_STEP);
+            // semi-implicit Euler
+            let force = -self.config.stiffness * (self.value - self.target)
+                - self.config.damping * self.velocity;
+            self.velocity += force / self.config.mass.max(f64::EPSILON) * h;
+            self.value += self.velocity * h;
+            remaining -= h;
+        }
+        if self.is_settled() {
+            self.value = self.target;
+            self.velocity = 0.0;
+        }
+    }
+
+    pub fn is_settled(&self) -> bool {
+        (self.value - self.target).abs() < REST_DISTANCE && self.velocity.abs() < REST_VELOCITY
+    }
+}
+
+/// Free motion slowed by friction: velocity decays as `exp(-friction * t)`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Glide {
+    pub value: f64,
+    pub velocity: f64,
+    pub friction: f64,
+}
+
+impl Glide {
+    pub fn step(&mut self, dt: f64) {
+        if self.friction > 0.0 {
+            let decay = (-self.friction * dt).exp();
+            // exact integral of the decaying velocity over dt
+            self.value += self.velocity * (1.0 - decay) / self.friction;
+            self.velocity *= decay;
+        } else {
+            self.value += self.velocity * dt;
+        }
+    }
+
+    /// Where the glide comes to rest.
+    pub fn resting_value(&self) -> f64 {
+        if self.friction > 0.0 {
+            self.value + self.velocity / self.friction
+        } else {
+            self.value
+        }
+    }
+
+    pub fn is_settled(&self) -> bool {
+        self.velocity.abs() < REST_VELOCITY * 10.0
+    }
+}
+
+/// Runs springs and glides against a clock supplied by the caller.
+#[derive(Default)]
+pub struct Physics {
+    springs: Vec<(IrSignal, Spring)>,
+    glides: Vec<(IrSignal, Glide)>,
+    last_time: Option<f64>,
+}
+
+impl Physics {
+    /// Pull `signal` towards `target`. A signal already on a spring keeps its
+    /// velocity, so moving the target mid-flight stays smooth.
+    pub fn spring_to(&mut self, signal: IrSignal, target: f64, config: SpringConfig) {
+        self.glides.retain(|(s, _)| s.set != signal.set);
+        match self.springs.iter_mut().find(|(s, _)| s.set == signal.set) {
+            Some((_, spring)) => {
+                spring.target = target;
+                spring.config = config;
+            }
+            None => {
+                let spring = Spring::new(signal.get(), target, config);
+                if !spring.is_settled() {
+                    self.springs.push((signal, spring));
+                }
+            }
+        }
+    }
+
+    /// Let `signal` coast at `velocity` (units per second) until friction
+    /// stops it.
+    pub fn glide(&mut self, signal: IrSignal, velocity: f64, friction: f64) {
+        self.stop_signal(signal);
+        self.glides.push((
+            signal,
+            Glide {
+                value: signal.get(),
+                velocity,
+                friction,
+            },
+        ));
+    }
+
+    pub fn stop_signal(&mut self, signal: IrSignal) {
+        self.springs.retain(|(s, _)| s.set != signal.set);
+        self.glides.retain(|(s, _)| s.set != signal.set);
+    }
+
+    pub fn stop_all(&mut self) {
+        self.springs.clear();
+        self.glides.clear();
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.springs.is_empty() || !self.glides.is_empty()
+    }
+
+    /// Advance to `now` (seconds). Returns true while anything is moving.
+    pub fn update(&mut self, now: f64) -> bool {
+        let dt = match self.last_time {
+            Some(last) => (now - last).clamp(0.0, MAX_FRAME),
+            None => 0.0,
+        };
+        self.last_time = Some(now);
+
+        for (signal, spring) in &mut self.springs {
+            spring.step(dt);
+            signal.set(spring.value);
+        }
+        for (signal, glide) in &mut self.glides {
+            glide.step(dt);
+            if glide.is_settled() {
+                glide.value = glide.resting_value();
+                glide.velocity = 0.0;
+            }
+            signal.set(glide.value);
+        }
+        self.springs.retain(|(_, spring)| !spring.is_settled());
+        self.glides.retain(|(_, glide)| !glide.is_settled());
+
+        if !self.is_active() {
+            // start the next motion from a fresh clock
+            self.last_time = None;
+        }
+        self.is_active()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: f64 = 1.0 / 60.0;
+
+    #[test]
+    fn default_spring_settles_on_its_target() {
+        let mut spring = Spring::new(0.0, 100.0, SpringConfig::default());
+        let mut elapsed = 0.0;
+        while !spring.is_settled() {
+            spring.step(FRAME);
+            elapsed += FRAME;
+            assert!(elapsed < 3.0, "still moving: {:?}", spring);
+        }
+        assert_eq!(spring.value, 100.0);
+        assert_eq!(spring.velocity, 0.0);
+    }
+
+    #[test]
+    fn glide_comes_to_rest_where_it_said_it_would() {
+        let mut glide = Glide {
+            value: 10.0,
+            velocity: 500.0,
+            friction: 5.0,
+        };
+        let rest = glide.resting_value();
+        assert_eq!(rest, 110.0);
+        // the step is exact, so the frame rate doesn't move the landing point
+        for _ in 0..600 {
+            glide.step(FRAME);
+            assert!((glide.resting_value() - rest).abs() < 1e-9);
+        }
+        assert!(glide.is_settled());
+        assert!((glide.value - rest).abs() < 1e-9);
+    }
+
+    #[test]
+    fn update_treats_long_gaps_as_one_short_frame() {
+        let signal = IrSignal::new(0.0);
+        let mut physics = Physics::default();
+        // without friction the glide keeps going, so distance measures time
+        physics.glide(signal, 100.0, 0.0);
+        assert!(physics.update(5.0));
+        assert_eq!(signal.get(), 0.0);
+        assert!(physics.update(65.0));
+        assert!((signal.get() - 100.0 * MAX_FRAME).abs() < 1e-9);
+        // a clock running backwards doesn't move anything
+        assert!(physics.update(1.0));
+        assert!((signal.get() - 100.0 * MAX_FRAME).abs() < 1e-9);
+    }
+
+    #[test]
+    fn thrown_shapes_stop_where_the_undo_step_says() {
+        let signal = IrSignal::new(20.0);
+        let (speed, friction) = (300.0, 6.0);
+        // what `Editor::pointer_up` records for a throw
+        let recorded = signal.get() + speed / friction;
+
+        let mut physics = Physics::default();
+        physics.glide(signal, speed, friction);
+        let mut now = 0.0;
+        while physics.update(now) {
+            now += FRAME;
+            assert!(now < 5.0);
+        }
+        assert!((signal.get() - recorded).abs() < 1e-9);
+    }
+}
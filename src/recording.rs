@@ -0,0 +1,324 @@
+// Recording rendered frames to video. The encoder itself sits behind a trait
+// so the browser can use WebCodecs while tests plug in a stub; pacing,
+// keyframe placement and the WebM container are handled here.
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+
+// a keyframe at least this often (seconds), so the result stays seekable
+const KEYFRAME_INTERVAL: f64 = 2.0;
+
+#[derive(Debug)]
+pub enum RecordingError {
+    UnsupportedCodec(String),
+    Encoder(String),
+}
+
+impl fmt::Display for RecordingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordingError::UnsupportedCodec(codec) => {
+                write!(f, "unsupported codec: {} (use vp8 or vp9)", codec)
+            }
+            RecordingError::Encoder(e) => write!(f, "video encoder failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RecordingError {}
+
+/// Codecs that can be muxed into WebM.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Codec {
+    Vp8,
+    Vp9,
+}
+
+impl Codec {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "vp8" => Some(Codec::Vp8),
+            "vp9" | "vp09" => Some(Codec::Vp9),
+            _ => None,
+        }
+    }
+
+    /// Codec string as understood by `VideoEncoder.configure`.
+    pub fn webcodecs_name(self) -> &'static str {
+        match self {
+            Codec::Vp8 => "vp8",
+            // profile 0, level 1.0, 8 bit
+            Codec::Vp9 => "vp09.00.10.08",
+        }
+    }
+
+    fn matroska_id(self) -> &'static str {
+        match self {
+            Codec::Vp8 => "V_VP8",
+            Codec::Vp9 => "V_VP9",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RecordingSettings {
+    pub fps: f64,
+    // bits per second
+    pub bitrate: f64,
+    pub codec: Codec,
+}
+
+/// One compressed frame as produced by an encoder.
+#[derive(Clone, Debug)]
+pub struct EncodedChunk {
+    pub data: Vec<u8>,
+    pub timestamp_us: i64,
+    pub keyframe: bool,
+}
+
+pub type FlushFuture = Pin<Box<dyn Future<Output = Result<Vec<EncodedChunk>, RecordingError>>>>;
+
+pub trait FrameEncoder {
+    /// Whatever the encoder consumes, e.g. a `VideoFrame` in the browser.
+    type Frame;
+
+    fn encode(
+        &mut self,
+        frame: Self::Frame,
+        timestamp_us: i64,
+        keyframe: bool,
+    ) -> Result<(), RecordingError>;
+
+    /// Wait for pending frames and hand back every chunk produced so far, in
+    /// presentation order.
+    fn flush(&mut self) -> FlushFuture;
+}
+
+pub struct Recorder<E: FrameEncoder> {
+    encoder: E,
+    settings: RecordingSettings,
+    width: u32,
+    height: u32,
+    // clock time of the first captured frame
+    start: Option<f64>,
+    // frames offered before this (seconds since start) are dropped
+    next_frame: f64,
+    last_keyframe: Option<f64>,
+    frames: u64,
+}
+
+impl<E: FrameEncoder> Recorder<E> {
+    pub fn new(encoder: E, settings: RecordingSettings, width: u32, height: u32) -> Self {
+        Self {
+            encoder,
+            settings,
+            width,
+            height,
+            start: None,
+            next_frame: 0.0,
+            last_keyframe: None,
+            frames: 0,
+        }
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Offer the frame rendered at clock time `now` (seconds). Frames that
+    /// arrive faster than the configured fps are dropped without calling
+    /// `frame`, which otherwise gets the frame's timestamp in microseconds
+    /// from the start of the recording. Returns whether it was encoded.
+    pub fn capture<F>(&mut self, now: f64, frame: F) -> Result<bool, RecordingError>
+    where
+        F: FnOnce(i64) -> Result<E::Frame, RecordingError>,
+    {
+        let start = *self.start.get_or_insert(now);
+        let elapsed = now - start;
+        if elapsed < self.next_frame {
+            return Ok(false);
+        }
+        let interval = 1.0 / self.settings.fps.max(1.0);
+        self.next_frame = ((elapsed / interval).floor() + 1.0) * interval;
+
+        let keyframe = self
+            .last_keyframe
+            .is_none_or(|last| elapsed - last >= KEYFRAME_INTERVAL);
+        if keyframe {
+            self.last_keyframe = Some(elapsed);
+        }
+        let timestamp_us = (elapsed * 1_000_000.0).round() as i64;
+        self.encoder
+            .encode(frame(timestamp_us)?, timestamp_us, keyframe)?;
+        self.frames += 1;
+        Ok(true)
+    }
+
+    /// Stop at clock time `now` and resolve to a WebM file.
+    pub fn finish(mut self, now: f64) -> impl Future<Output = Result<Vec<u8>, RecordingError>> {
+        let duration = self.start.map_or(0.0, |start| now - start);
+        async move {
+            let mut chunks = self.encoder.flush().await?;
+            chunks.sort_by_key(|chunk| chunk.timestamp_us);
+            Ok(mux_webm(
+                self.settings.codec,
+                self.width,
+                self.height,
+                self.settings.fps,
+                duration,
+                &chunks,
+            ))
+        }
+    }
+}
+
+// EBML element ids, see https://www.matroska.org/technical/elements.html
+const EBML: u32 = 0x1A45_DFA3;
+const EBML_VERSION: u32 = 0x4286;
+const EBML_READ_VERSION: u32 = 0x42F7;
+const EBML_MAX_ID_LENGTH: u32 = 0x42F2;
+const EBML_MAX_SIZE_LENGTH: u32 = 0x42F3;
+const DOC_TYPE: u32 = 0x4282;
+const DOC_TYPE_VERSION: u32 = 0x4287;
+const DOC_TYPE_READ_VERSION: u32 = 0x4285;
+const SEGMENT: u32 = 0x1853_8067;
+const INFO: u32 = 0x1549_A966;
+const TIMECODE_SCALE: u32 = 0x2A_D7B1;
+const DURATION: u32 = 0x4489;
+const MUXING_APP: u32 = 0x4D80;
+const WRITING_APP: u32 = 0x5741;
+const TRACKS: u32 = 0x1654_AE6B;
+const TRACK_ENTRY: u32 = 0xAE;
+const TRACK_NUMBER: u32 = 0xD7;
+const TRACK_UID: u32 = 0x73C5;
+const TRACK_TYPE: u32 = 0x83;
+const FLAG_LACING: u32 = 0x9C;
+const DEFAULT_DURATION: u32 = 0x23_E383;
+const CODEC_ID: u32 = 0x86;
+const VIDEO: u32 = 0xE0;
+const PIXEL_WIDTH: u32 = 0xB0;
+const PIXEL_HEIGHT: u32 = 0xBA;
+const CLUSTER: u32 = 0x1F43_B675;
+const TIMECODE: u32 = 0xE7;
+const SIMPLE_BLOCK: u32 = 0xA3;
+
+// timestamps are stored in milliseconds
+const TIMECODE_SCALE_NS: u64 = 1_000_000;
+const APP_NAME: &str = "svelte-vello";
+
+/// Write `chunks` as the single video track of an in-memory WebM file.
+/// A new cluster starts at every keyframe, and whenever a block's offset
+/// would overflow the 16 bit relative timecode.
+pub fn mux_webm(
+    codec: Codec,
+    width: u32,
+    height: u32,
+    fps: f64,
+    duration: f64,
+    chunks: &[EncodedChunk],
+) -> Vec<u8> {
+    let mut header = Vec::new();
+    uint(&mut header, EBML_VERSION, 1);
+    uint(&mut header, EBML_READ_VERSION, 1);
+    uint(&mut header, EBML_MAX_ID_LENGTH, 4);
+    uint(&mut header, EBML_MAX_SIZE_LENGTH, 8);
+    string(&mut header, DOC_TYPE, "webm");
+    uint(&mut header, DOC_TYPE_VERSION, 4);
+    uint(&mut header, DOC_TYPE_READ_VERSION, 2);
+
+    let mut info = Vec::new();
+    uint(&mut info, TIMECODE_SCALE, TIMECODE_SCALE_NS);
+    float(&mut info, DURATION, duration * 1000.0);
+    string(&mut info, MUXING_APP, APP_NAME);
+    string(&mut info, WRITING_APP, APP_NAME);
+
+    let mut video = Vec::new();
+    uint(&mut video, PIXEL_WIDTH, width as u64);
+    uint(&mut video, PIXEL_HEIGHT, height as u64);
+
+    let mut track = Vec::new();
+    uint(&mut track, TRACK_NUMBER, 1);
+    uint(&mut track, TRACK_UID, 1);
+    uint(&mut track, TRACK_TYPE, 1);
+    uint(&mut track, FLAG_LACING, 0);
+    if fps > 0.0 {
+        uint(&mut track, DEFAULT_DURATION, (1e9 / fps).round() as u64);
+    }
+    string(&mut track, CODEC_ID, codec.matroska_id());
+    element(&mut track, VIDEO, &video);
+
+    let mut tracks = Vec::new();
+    element(&mut tracks, TRACK_ENTRY, &track);
+
+    let mut segment = Vec::new();
+    element(&mut segment, INFO, &info);
+    element(&mut segment, TRACKS, &tracks);
+
+    let mut cluster: Option<(i64, Vec<u8>)> = None;
+    for chunk in chunks {
+        let time = chunk.timestamp_us / 1000;
+        let fits = cluster
+            .as_ref()
+            .is_some_and(|(start, _)| !chunk.keyframe && time - start <= i16::MAX as i64);
+        if !fits {
+            if let Some((_, body)) = cluster.take() {
+                element(&mut segment, CLUSTER, &body);
+            }
+            let mut body = Vec::new();
+            uint(&mut body, TIMECODE, time.max(0) as u64);
+            cluster = Some((time, body));
+        }
+        let (start, body) = cluster.as_mut().unwrap();
+
+        let mut block = Vec::with_capacity(chunk.data.len() + 4);
+        // track number as a one byte vint
+        block.push(0x81);
+        block.extend_from_slice(&((time - *start) as i16).to_be_bytes());
+        block.push(if chunk.keyframe { 0x80 } else { 0x00 });
+        block.extend_from_slice(&chunk.data);
+        element(body, SIMPLE_BLOCK, &block);
+    }
+    if let Some((_, body)) = cluster {
+        element(&mut segment, CLUSTER, &body);
+    }
+
+    let mut out = Vec::new();
+    element(&mut out, EBML, &header);
+    element(&mut out, SEGMENT, &segment);
+    out
+}
+
+fn id(out: &mut Vec<u8>, id: u32) {
+    let bytes = id.to_be_bytes();
+    let skip = bytes.iter().take_while(|byte| **byte == 0).count();
+    out.extend_from_slice(&bytes[skip..]);
+}
+
+// element sizes as variable length integers, in as few bytes as possible
+fn size(out: &mut Vec<u8>, size: u64) {
+    // all ones is reserved for "unknown size"
+    let length = (1..=8).find(|n| size < (1 << (7 * n)) - 1).unwrap_or(8);
+    let marked = size | (1 << (7 * length));
+    out.extend_from_slice(&marked.to_be_bytes()[8 - length as usize..]);
+}
+
+fn element(out: &mut Vec<u8>, element_id: u32, body: &[u8]) {
+    id(out, element_id);
+    size(out, body.len() as u64);
+    out.extend_from_slice(body);
+}
+
+fn uint(out: &mut Vec<u8>, element_id: u32, value: u64) {
+    let bytes = value.to_be_bytes();
+    let skip = bytes.iter().take_while(|byte| **byte == 0).count().min(7);
+    element(out, element_id, &bytes[skip..]);
+}
+
+fn float(out: &mut Vec<u8>, element_id: u32, value: f64) {
+    element(out, element_id, &value.to_be_bytes());
+}
+
+fn string(out: &mut Vec<u8>, element_id: u32, value: &str) {
+    element(out, element_id, value.as_bytes());
+}
@@ -0,0 +1,319 @@
+// Offscreen rendering: draw a scene into a texture instead of the canvas
+// surface and read the pixels back to the CPU. Nothing in here touches the
+// DOM, so it works natively as well as in the browser.
+
+use std::fmt;
+use std::future::Future;
+use std::num::NonZeroUsize;
+
+use vello::{
+    kurbo::Affine, peniko::Color, wgpu, AaConfig, AaSupport, RenderParams, Renderer,
+    RendererOptions, Scene,
+};
+
+// wgpu requires the rows of a texture -> buffer copy to be padded to this
+const ROW_ALIGNMENT: u32 = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+#[derive(Debug)]
+pub enum ImageError {
+    Render(vello::Error),
+    Readback(wgpu::BufferAsyncError),
+    Encode(png::EncodingError),
+    Cancelled,
+    NoAdapter,
+    Device(wgpu::RequestDeviceError),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::Render(e) => write!(f, "failed to render image: {}", e),
+            ImageError::Readback(e) => write!(f, "failed to read back image: {}", e),
+            ImageError::Encode(e) => write!(f, "failed to encode png: {}", e),
+            ImageError::Cancelled => write!(f, "image readback was cancelled"),
+            ImageError::NoAdapter => write!(f, "no suitable wgpu adapter found"),
+            ImageError::Device(e) => write!(f, "failed to create wgpu device: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+/// Tightly packed, non-premultiplied RGBA8 pixels.
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+impl RgbaImage {
+    pub fn to_png(&self) -> Result<Vec<u8>, ImageError> {
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut bytes, self.width, self.height);
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header().map_err(ImageError::Encode)?;
+            writer
+                .write_image_data(&self.data)
+                .map_err(ImageError::Encode)?;
+        }
+        Ok(bytes)
+    }
+}
+
+pub struct ImageParams {
+    pub width: u32,
+    pub height: u32,
+    // multiplies scene coordinates, e.g. 0.25 for a thumbnail or 2.0 for hi-dpi
+    pub scale: f64,
+    // use Color::TRANSPARENT for a transparent background
+    pub base_color: Color,
+    pub antialiasing_method: AaConfig,
+}
+
+/// GPU work that has been submitted but whose pixels are still on the GPU.
+pub struct PendingImage {
+    buffer: wgpu::Buffer,
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32,
+}
+
+/// Render `scene` into a fresh offscreen texture and queue a copy of it
+/// into a mappable buffer.
+pub fn render_offscreen(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    renderer: &mut Renderer,
+    scene: &Scene,
+    params: &ImageParams,
+) -> Result<PendingImage, ImageError> {
+    let width = params.width.max(1);
+    let height = params.height.max(1);
+
+    let mut scaled = Scene::new();
+    scaled.append(scene, Some(Affine::scale(params.scale)));
+
+    let size = wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+    let target = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("offscreen target"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8Unorm,
+        usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+    renderer
+        .render_to_texture(
+            device,
+            queue,
+            &scaled,
+            &view,
+            &RenderParams {
+                base_color: params.base_color,
+                width,
+                height,
+                antialiasing_method: params.antialiasing_method,
+            },
+        )
+        .map_err(ImageError::Render)?;
+
+    let padded_bytes_per_row = (width * 4).div_ceil(ROW_ALIGNMENT) * ROW_ALIGNMENT;
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("offscreen readback"),
+        size: (padded_bytes_per_row * height) as u64,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("offscreen copy"),
+    });
+    encoder.copy_texture_to_buffer(
+        target.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_bytes_per_row),
+                rows_per_image: None,
+            },
+        },
+        size,
+    );
+    queue.submit([encoder.finish()]);
+
+    Ok(PendingImage {
+        buffer,
+        width,
+        height,
+        padded_bytes_per_row,
+    })
+}
+
+impl PendingImage {
+    /// Start mapping the readback buffer. The returned future owns everything
+    /// it needs, so it can be handed to `wasm_bindgen_futures` as-is.
+    pub fn read(
+        self,
+        device: &wgpu::Device,
+    ) -> impl Future<Output = Result<RgbaImage, ImageError>> {
+        let (sender, receiver) = futures_intrusive::channel::shared::oneshot_channel();
+        self.buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+        // blocks on native, no-op on the web where the browser drives the queue
+        device.poll(wgpu::Maintain::Wait);
+
+        async move {
+            receiver
+                .receive()
+                .await
+                .ok_or(ImageError::Cancelled)?
+                .map_err(ImageError::Readback)?;
+
+            let row_bytes = (self.width * 4) as usize;
+            let mut data = Vec::with_capacity(row_bytes * self.height as usize);
+            {
+                let mapped = self.buffer.slice(..).get_mapped_range();
+                for row in mapped.chunks(self.padded_bytes_per_row as usize) {
+                    data.extend_from_slice(&row[..row_bytes]);
+                }
+            }
+            self.buffer.unmap();
+
+            Ok(RgbaImage {
+                width: self.width,
+                height: self.height,
+                data,
+            })
+        }
+    }
+}
+
+/// Pick an adapter and open a device on it, asking for the same features
+/// `vello::util::RenderContext` does.
+pub async fn request_device(
+    instance: &wgpu::Instance,
+    power_preference: wgpu::PowerPreference,
+    force_fallback_adapter: bool,
+    compatible_surface: Option<&wgpu::Surface<'_>>,
+) -> Result<(wgpu::Adapter, wgpu::Device, wgpu::Queue), ImageError> {
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference,
+            force_fallback_adapter,
+            compatible_surface,
+        })
+        .await
+        .ok_or(ImageError::NoAdapter)?;
+
+    let (device, queue) = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                required_features: adapter.features() & wgpu::Features::CLEAR_TEXTURE,
+                required_limits: wgpu::Limits::default(),
+                memory_hints: wgpu::MemoryHints::default(),
+            },
+            None,
+        )
+        .await
+        .map_err(ImageError::Device)?;
+
+    Ok((adapter, device, queue))
+}
+
+pub struct HeadlessOptions {
+    // run vello's shaders on the CPU; a device is still needed for the
+    // final texture, but it can be a software one
+    pub use_cpu: bool,
+    // ask wgpu for a software adapter (e.g. lavapipe or WARP) instead of a GPU
+    pub force_fallback_adapter: bool,
+    // threads compiling vello's shaders when the renderer is created; `None`
+    // lets vello use all available parallelism
+    pub num_init_threads: Option<NonZeroUsize>,
+}
+
+impl Default for HeadlessOptions {
+    fn default() -> Self {
+        Self {
+            use_cpu: false,
+            force_fallback_adapter: false,
+            num_init_threads: NonZeroUsize::new(1),
+        }
+    }
+}
+
+/// A renderer with its own device and no window or surface, for tests and
+/// server-side thumbnails.
+pub struct HeadlessRenderer {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    renderer: Renderer,
+}
+
+impl HeadlessRenderer {
+    pub async fn new(options: HeadlessOptions) -> Result<Self, ImageError> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let (_, device, queue) = request_device(
+            &instance,
+            wgpu::PowerPreference::default(),
+            options.force_fallback_adapter,
+            None,
+        )
+        .await?;
+
+        let renderer = Renderer::new(
+            &device,
+            RendererOptions {
+                surface_format: None,
+                use_cpu: options.use_cpu,
+                antialiasing_support: AaSupport::all(),
+                num_init_threads: options.num_init_threads,
+            },
+        )
+        .map_err(ImageError::Render)?;
+
+        Ok(Self {
+            device,
+            queue,
+            renderer,
+        })
+    }
+
+    pub async fn render(
+        &mut self,
+        scene: &Scene,
+        params: &ImageParams,
+    ) -> Result<RgbaImage, ImageError> {
+        let pending =
+            render_offscreen(&self.device, &self.queue, &mut self.renderer, scene, params)?;
+        pending.read(&self.device).await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn new_blocking(options: HeadlessOptions) -> Result<Self, ImageError> {
+        pollster::block_on(Self::new(options))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn render_blocking(
+        &mut self,
+        scene: &Scene,
+        params: &ImageParams,
+    ) -> Result<RgbaImage, ImageError> {
+        pollster::block_on(self.render(scene, params))
+    }
+}
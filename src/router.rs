@@ -0,0 +1,297 @@
+// Orthogonal routing around obstacles. The search runs A* over a sparse
+// grid whose lines are the edges of the (inflated) obstacles and the two
+// ends, so every route hugs obstacles at exactly the clearance and turns
+// only where it has to. Each bend costs extra, which keeps routes from
+// staircasing.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use vello::kurbo::{Point, Rect, Vec2};
+
+// extra cost of a 90° turn, in world units of length
+const BEND_PENALTY: f64 = 20.0;
+
+// +x, -x, +y, -y
+const DIRECTIONS: [(i64, i64); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+/// An orthogonal route from `from`, leaving along `out`, to `to`, arriving
+/// against `into` (both unit vectors along an axis, pointing away from
+/// their shapes), that keeps `clearance` away from every obstacle. The
+/// result starts at `from` and ends at `to`, with a point at every bend.
+/// `None` if the obstacles leave no way through.
+pub fn route(
+    from: Point,
+    out: Vec2,
+    to: Point,
+    into: Vec2,
+    obstacles: &[Rect],
+    clearance: f64,
+) -> Option<Vec<Point>> {
+    // leave and arrive square to the shapes, clear of them
+    let start = from + out * clearance;
+    let goal = to + into * clearance;
+    let obstacles: Vec<Rect> = obstacles
+        .iter()
+        .map(|rect| rect.inflate(clearance, clearance))
+        .filter(|rect| !strictly_inside(*rect, start) && !strictly_inside(*rect, goal))
+        .collect();
+
+    let grid = Grid::new(&obstacles, start, goal);
+    let start_node = grid.node(start)?;
+    let goal_node = grid.node(goal)?;
+    let start_direction = direction_index(out)?;
+    let goal_direction = direction_index(-into)?;
+
+    let mut best = vec![f64::INFINITY; grid.len() * 4];
+    let mut came_from = vec![usize::MAX; grid.len() * 4];
+    let mut queue = BinaryHeap::new();
+    let first = start_node * 4 + start_direction;
+    best[first] = 0.0;
+    queue.push(Candidate {
+        estimate: grid.distance(start_node, goal_node),
+        state: first,
+    });
+
+    let mut found = None;
+    while let Some(Candidate { state, estimate }) = queue.pop() {
+        let (node, direction) = (state / 4, state % 4);
+        let cost = best[state];
+        if estimate > cost + grid.distance(node, goal_node) + 1e-9 {
+            // superseded by a cheaper way here
+            continue;
+        }
+        if node == goal_node {
+            // arriving sideways still needs a turn into the target
+            let total = cost
+                + if direction == goal_direction {
+                    0.0
+                } else {
+                    BEND_PENALTY
+                };
+            if found.is_none_or(|(_, best_total)| total < best_total) {
+                found = Some((state, total));
+            }
+            continue;
+        }
+        if let Some((_, best_total)) = found {
+            if estimate >= best_total {
+                break;
+            }
+        }
+        for (next_direction, step) in DIRECTIONS.iter().enumerate() {
+            if is_reverse(direction, next_direction) {
+                continue;
+            }
+            let next = match grid.step(node, *step) {
+                Some(next) => next,
+                None => continue,
+            };
+            let bend = if next_direction == direction {
+                0.0
+            } else {
+                BEND_PENALTY
+            };
+            let next_cost = cost + grid.distance(node, next) + bend;
+            let next_state = next * 4 + next_direction;
+            if next_cost < best[next_state] {
+                best[next_state] = next_cost;
+                came_from[next_state] = state;
+                queue.push(Candidate {
+                    estimate: next_cost + grid.distance(next, goal_node),
+                    state: next_state,
+                });
+            }
+        }
+    }
+
+    let (mut state, _) = found?;
+    let mut points = vec![to];
+    loop {
+        points.push(grid.point(state / 4));
+        if state == first {
+            break;
+        }
+        state = came_from[state];
+    }
+    points.push(from);
+    points.reverse();
+    Some(simplify(points))
+}
+
+// A grid of candidate bend points, with the cells and edges that cross an
+// obstacle blocked.
+struct Grid {
+    xs: Vec<f64>,
+    ys: Vec<f64>,
+    blocked_nodes: Vec<bool>,
+    // the edge from each node to the next one along +x, and along +y
+    blocked_x: Vec<bool>,
+    blocked_y: Vec<bool>,
+}
+
+impl Grid {
+    fn new(obstacles: &[Rect], start: Point, goal: Point) -> Self {
+        let mut xs = vec![start.x, goal.x, (start.x + goal.x) / 2.0];
+        let mut ys = vec![start.y, goal.y, (start.y + goal.y) / 2.0];
+        for rect in obstacles {
+            xs.extend([rect.x0, rect.x1]);
+            ys.extend([rect.y0, rect.y1]);
+        }
+        for coordinates in [&mut xs, &mut ys] {
+            coordinates.sort_by(f64::total_cmp);
+            coordinates.dedup();
+            // channels between neighbouring obstacles, run down the middle
+            let middles: Vec<f64> = coordinates
+                .windows(2)
+                .map(|pair| (pair[0] + pair[1]) / 2.0)
+                .collect();
+            coordinates.extend(middles);
+            coordinates.sort_by(f64::total_cmp);
+        }
+
+        let (w, h) = (xs.len(), ys.len());
+        let mut grid = Self {
+            blocked_nodes: vec![false; w * h],
+            blocked_x: vec![false; w * h],
+            blocked_y: vec![false; w * h],
+            xs,
+            ys,
+        };
+        for rect in obstacles {
+            let (i0, i1) = (grid.index_x(rect.x0), grid.index_x(rect.x1));
+            let (j0, j1) = (grid.index_y(rect.y0), grid.index_y(rect.y1));
+            // nodes strictly inside, and edges through the inside; the
+            // outline itself stays open to run along
+            for j in j0..=j1 {
+                for i in i0..=i1 {
+                    let node = j * w + i;
+                    let inner_x = i > i0 && i < i1;
+                    let inner_y = j > j0 && j < j1;
+                    if inner_x && inner_y {
+                        grid.blocked_nodes[node] = true;
+                    }
+                    if inner_y && i < i1 {
+                        grid.blocked_x[node] = true;
+                    }
+                    if inner_x && j < j1 {
+                        grid.blocked_y[node] = true;
+                    }
+                }
+            }
+        }
+        grid
+    }
+
+    fn len(&self) -> usize {
+        self.xs.len() * self.ys.len()
+    }
+
+    fn index_x(&self, x: f64) -> usize {
+        self.xs.partition_point(|v| *v < x)
+    }
+
+    fn index_y(&self, y: f64) -> usize {
+        self.ys.partition_point(|v| *v < y)
+    }
+
+    fn node(&self, point: Point) -> Option<usize> {
+        let node = self.index_y(point.y) * self.xs.len() + self.index_x(point.x);
+        (!self.blocked_nodes[node]).then_some(node)
+    }
+
+    fn point(&self, node: usize) -> Point {
+        let w = self.xs.len();
+        Point::new(self.xs[node % w], self.ys[node / w])
+    }
+
+    fn distance(&self, a: usize, b: usize) -> f64 {
+        let (a, b) = (self.point(a), self.point(b));
+        (a.x - b.x).abs() + (a.y - b.y).abs()
+    }
+
+    // the neighbouring node one grid line over, unless the way is blocked
+    fn step(&self, node: usize, (dx, dy): (i64, i64)) -> Option<usize> {
+        let w = self.xs.len() as i64;
+        let (i, j) = (node as i64 % w, node as i64 / w);
+        let (ni, nj) = (i + dx, j + dy);
+        if ni < 0 || nj < 0 || ni >= w || nj >= self.ys.len() as i64 {
+            return None;
+        }
+        let next = (nj * w + ni) as usize;
+        let edge = match (dx, dy) {
+            (1, 0) => self.blocked_x[node],
+            (-1, 0) => self.blocked_x[next],
+            (0, 1) => self.blocked_y[node],
+            _ => self.blocked_y[next],
+        };
+        (!edge && !self.blocked_nodes[next]).then_some(next)
+    }
+}
+
+#[derive(PartialEq)]
+struct Candidate {
+    // cost so far plus the distance still to go
+    estimate: f64,
+    state: usize,
+}
+
+impl Eq for Candidate {}
+
+impl Ord for Candidate {
+    // cheapest first out of the max-heap
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn strictly_inside(rect: Rect, point: Point) -> bool {
+    point.x > rect.x0 && point.x < rect.x1 && point.y > rect.y0 && point.y < rect.y1
+}
+
+fn direction_index(v: Vec2) -> Option<usize> {
+    DIRECTIONS
+        .iter()
+        .position(|(dx, dy)| (v.x - *dx as f64).abs() < 1e-9 && (v.y - *dy as f64).abs() < 1e-9)
+}
+
+fn is_reverse(a: usize, b: usize) -> bool {
+    a != b && a / 2 == b / 2
+}
+
+// drop repeated points and the middle of straight runs
+fn simplify(points: Vec<Point>) -> Vec<Point> {
+    let mut result: Vec<Point> = Vec::with_capacity(points.len());
+    for point in points {
+        if result.last() == Some(&point) {
+            continue;
+        }
+        if let [.., a, b] = result.as_slice() {
+            let collinear = (a.x == b.x && b.x == point.x) || (a.y == b.y && b.y == point.y);
+            if collinear {
+                result.pop();
+            }
+        }
+        result.push(point);
+    }
+    result
+}
+
+/// Whether a route passes within `distance` of `rect`; a route that doesn't
+/// can't have been shaped by it.
+pub fn passes_near(route: &[Point], rect: Rect, distance: f64) -> bool {
+    let rect = rect.inflate(distance, distance);
+    route.windows(2).any(|pair| {
+        let segment = Rect::from_points(pair[0], pair[1]);
+        segment.x0 <= rect.x1
+            && rect.x0 <= segment.x1
+            && segment.y0 <= rect.y1
+            && rect.y0 <= segment.y1
+    })
+}
@@ -0,0 +1,708 @@
+use std::sync::Arc;
+
+use skrifa::{
+    instance::{LocationRef, Size},
+    raw::{FileRef, FontRef},
+    MetadataProvider,
+};
+use vello::{
+    kurbo::{Affine, BezPath, Circle, Point, Rect, Shape as _},
+    peniko::{Blob, Color, Fill, Font, Mix},
+    Glyph, Scene,
+};
+
+use crate::connector::IrConnector;
+use crate::layout::Container;
+use crate::node::Node;
+use crate::signal::IrSignal;
+
+// flattening tolerance for outlines of curved shapes
+const OUTLINE_TOLERANCE: f64 = 0.1;
+
+// Define base Shape trait
+pub trait Shape {
+    // fn new(x: f64, y: f64, color: Color) -> Self;
+    fn contains(&self, x: f64, y: f64) -> bool;
+    // axis-aligned bounding box in the parent's coordinates, which are world
+    // coordinates unless the shape is inside a group
+    fn bounds(&self) -> Rect;
+    // outline in the parent's coordinates, e.g. for clipping
+    fn outline(&self) -> BezPath;
+    fn draw(&self, scene: &mut Scene);
+    fn node(&self) -> &Node;
+    fn node_mut(&mut self) -> &mut Node;
+    // an independent copy with its own signals
+    fn duplicate(&self) -> Box<dyn Shape>;
+
+    /// Named animatable property; shapes add their own to the node's.
+    fn signal(&self, name: &str) -> Option<IrSignal> {
+        self.node().signal(name)
+    }
+
+    // lets the editor reach into groups without knowing concrete types
+    fn as_group(&self) -> Option<&IrGroup> {
+        None
+    }
+
+    fn as_group_mut(&mut self) -> Option<&mut IrGroup> {
+        None
+    }
+
+    // and into paths, for node editing
+    fn as_path(&self) -> Option<&IrPath> {
+        None
+    }
+
+    fn as_path_mut(&mut self) -> Option<&mut IrPath> {
+        None
+    }
+
+    // and into text, for typing
+    fn as_text(&self) -> Option<&IrText> {
+        None
+    }
+
+    fn as_text_mut(&mut self) -> Option<&mut IrText> {
+        None
+    }
+
+    // and into connectors, for re-routing
+    fn as_connector(&self) -> Option<&IrConnector> {
+        None
+    }
+
+    fn as_connector_mut(&mut self) -> Option<&mut IrConnector> {
+        None
+    }
+
+    // how `outline` is filled; overlapping subpaths matter for boolean ops
+    fn fill_rule(&self) -> Fill {
+        Fill::NonZero
+    }
+
+    // the fill of shapes painted in a single color
+    fn color(&self) -> Option<Color> {
+        None
+    }
+}
+
+pub struct IrRectangle {
+    pub node: Node,
+    pub width: IrSignal,
+    pub height: IrSignal,
+    pub color: Color,
+}
+
+impl IrRectangle {
+    pub fn new(x: f64, y: f64, width: f64, height: f64, color: Color) -> Self {
+        Self {
+            node: Node::new(x, y),
+            width: IrSignal::new(width),
+            height: IrSignal::new(height),
+            color,
+        }
+    }
+}
+
+impl Shape for IrRectangle {
+    fn node(&self) -> &Node {
+        &self.node
+    }
+
+    fn node_mut(&mut self) -> &mut Node {
+        &mut self.node
+    }
+
+    fn duplicate(&self) -> Box<dyn Shape> {
+        Box::new(Self {
+            node: self.node.duplicate(),
+            width: IrSignal::new(self.width.get()),
+            height: IrSignal::new(self.height.get()),
+            color: self.color,
+        })
+    }
+
+    fn signal(&self, name: &str) -> Option<IrSignal> {
+        match name {
+            "width" => Some(self.width),
+            "height" => Some(self.height),
+            _ => self.node.signal(name),
+        }
+    }
+
+    fn color(&self) -> Option<Color> {
+        Some(self.color)
+    }
+
+    fn contains(&self, px: f64, py: f64) -> bool {
+        px >= self.node.x.get()
+            && px <= self.node.x.get() + self.width.get()
+            && py >= self.node.y.get()
+            && py <= self.node.y.get() + self.height.get()
+    }
+
+    fn bounds(&self) -> Rect {
+        Rect::new(
+            self.node.x.get(),
+            self.node.y.get(),
+            self.node.x.get() + self.width.get(),
+            self.node.y.get() + self.height.get(),
+        )
+    }
+
+    fn outline(&self) -> BezPath {
+        self.bounds().to_path(OUTLINE_TOLERANCE)
+    }
+
+    fn draw(&self, scene: &mut Scene) {
+        scene.fill(
+            Fill::NonZero,
+            Affine::IDENTITY,
+            self.color,
+            None,
+            &Rect::new(
+                self.node.x.get(),
+                self.node.y.get(),
+                self.node.x.get() + self.width.get(),
+                self.node.y.get() + self.height.get(),
+            ),
+        );
+    }
+}
+
+// Circle implementation
+pub struct IrCircle {
+    pub node: Node,
+    pub radius: IrSignal,
+    pub color: Color,
+}
+
+impl IrCircle {
+    pub fn new(x: f64, y: f64, radius: f64, color: Color) -> Self {
+        Self {
+            node: Node::new(x, y),
+            radius: IrSignal::new(radius),
+            color,
+        }
+    }
+}
+
+impl Shape for IrCircle {
+    fn contains(&self, px: f64, py: f64) -> bool {
+        let dx = self.node.x.get() - px;
+        let dy = self.node.y.get() - py;
+        (dx * dx + dy * dy).sqrt() <= self.radius.get()
+    }
+
+    fn bounds(&self) -> Rect {
+        Circle::new((self.node.x.get(), self.node.y.get()), self.radius.get()).bounding_box()
+    }
+
+    fn outline(&self) -> BezPath {
+        Circle::new((self.node.x.get(), self.node.y.get()), self.radius.get())
+            .to_path(OUTLINE_TOLERANCE)
+    }
+
+    fn draw(&self, scene: &mut Scene) {
+        scene.fill(
+            Fill::NonZero,
+            Affine::IDENTITY,
+            self.color,
+            None,
+            &Circle::new((self.node.x.get(), self.node.y.get()), self.radius.get()),
+        );
+    }
+
+    fn node(&self) -> &Node {
+        &self.node
+    }
+
+    fn node_mut(&mut self) -> &mut Node {
+        &mut self.node
+    }
+
+    fn duplicate(&self) -> Box<dyn Shape> {
+        Box::new(Self {
+            node: self.node.duplicate(),
+            radius: IrSignal::new(self.radius.get()),
+            color: self.color,
+        })
+    }
+
+    fn signal(&self, name: &str) -> Option<IrSignal> {
+        match name {
+            "radius" => Some(self.radius),
+            _ => self.node.signal(name),
+        }
+    }
+
+    fn color(&self) -> Option<Color> {
+        Some(self.color)
+    }
+}
+
+/// An arbitrary filled outline, e.g. the result of a boolean operation.
+/// The path is relative to the node's position.
+pub struct IrPath {
+    pub node: Node,
+    pub path: BezPath,
+    pub fill: Fill,
+    pub color: Color,
+}
+
+impl IrPath {
+    pub fn new(x: f64, y: f64, path: BezPath, fill: Fill, color: Color) -> Self {
+        Self {
+            node: Node::new(x, y),
+            path,
+            fill,
+            color,
+        }
+    }
+
+    /// A shape for an outline in parent coordinates, placed at the top-left
+    /// of its bounds.
+    pub fn from_outline(outline: BezPath, fill: Fill, color: Color) -> Self {
+        let origin = outline.bounding_box().origin();
+        let path = Affine::translate(-origin.to_vec2()) * outline;
+        Self::new(origin.x, origin.y, path, fill, color)
+    }
+
+    /// Maps the path's coordinates into the shape's parent.
+    pub fn transform(&self) -> Affine {
+        Affine::translate((self.node.x.get(), self.node.y.get()))
+    }
+}
+
+impl Shape for IrPath {
+    fn contains(&self, px: f64, py: f64) -> bool {
+        let winding = self
+            .path
+            .winding(Point::new(px - self.node.x.get(), py - self.node.y.get()));
+        match self.fill {
+            Fill::NonZero => winding != 0,
+            Fill::EvenOdd => winding % 2 != 0,
+        }
+    }
+
+    fn bounds(&self) -> Rect {
+        self.transform()
+            .transform_rect_bbox(self.path.bounding_box())
+    }
+
+    fn outline(&self) -> BezPath {
+        self.transform() * self.path.clone()
+    }
+
+    fn draw(&self, scene: &mut Scene) {
+        scene.fill(self.fill, self.transform(), self.color, None, &self.path);
+    }
+
+    fn node(&self) -> &Node {
+        &self.node
+    }
+
+    fn node_mut(&mut self) -> &mut Node {
+        &mut self.node
+    }
+
+    fn duplicate(&self) -> Box<dyn Shape> {
+        Box::new(Self {
+            node: self.node.duplicate(),
+            path: self.path.clone(),
+            fill: self.fill,
+            color: self.color,
+        })
+    }
+
+    fn fill_rule(&self) -> Fill {
+        self.fill
+    }
+
+    fn color(&self) -> Option<Color> {
+        Some(self.color)
+    }
+    fn as_path(&self) -> Option<&IrPath> {
+        Some(self)
+    }
+
+    fn as_path_mut(&mut self) -> Option<&mut IrPath> {
+        Some(self)
+    }
+}
+
+/// Shapes drawn, hit-tested and moved as one. Children are laid out in the
+/// group's own space, which the group's node places, rotates and scales.
+/// A group is also a layer: its children are composited together, then
+/// blended onto what is below with the group's opacity and blend mode.
+/// A group with a `container` is a stack or grid: it has a size of its own
+/// and places its children itself.
+pub struct IrGroup {
+    pub node: Node,
+    pub children: Vec<Box<dyn Shape>>,
+    // 0 to 1, animatable as "opacity"
+    pub opacity: IrSignal,
+    pub blend: Mix,
+    // index of a child whose outline clips the others; it isn't drawn itself
+    pub mask: Option<usize>,
+    pub container: Option<Container>,
+}
+
+impl IrGroup {
+    pub fn new(x: f64, y: f64, children: Vec<Box<dyn Shape>>) -> Self {
+        Self {
+            node: Node::new(x, y),
+            children,
+            opacity: IrSignal::new(1.0),
+            blend: Mix::Normal,
+            mask: None,
+            container: None,
+        }
+    }
+
+    fn mask_shape(&self) -> Option<&dyn Shape> {
+        self.mask
+            .and_then(|index| self.children.get(index))
+            .map(|mask| mask.as_ref())
+    }
+
+    // the children that are drawn, i.e. all but the mask
+    fn contents(&self) -> impl Iterator<Item = &dyn Shape> + '_ {
+        let mask = self.mask;
+        self.children
+            .iter()
+            .enumerate()
+            .filter(move |(index, _)| Some(*index) != mask)
+            .map(|(_, child)| child.as_ref())
+    }
+
+    // union of the drawn children's bounds, and the container's box, in the
+    // group's own space
+    fn local_bounds(&self) -> Option<Rect> {
+        let bounds = self
+            .contents()
+            .map(|child| child.bounds())
+            .chain(self.container.as_ref().map(Container::rect))
+            .reduce(|a, b| a.union(b))?;
+        Some(match self.mask_shape() {
+            Some(mask) => bounds.intersect(mask.bounds()),
+            None => bounds,
+        })
+    }
+
+    /// Move the children to where the container's layout puts them, for its
+    /// current size. Does nothing for a plain group.
+    pub fn lay_out(&self) {
+        let container = match &self.container {
+            Some(container) => container,
+            None => return,
+        };
+        let children: Vec<&dyn Shape> = self.contents().collect();
+        let sizes: Vec<_> = children.iter().map(|child| child.bounds().size()).collect();
+        let places = container.layout.arrange(container.size(), &sizes);
+        for (child, place) in children.iter().zip(places) {
+            let bounds = child.bounds();
+            let node = child.node();
+            for (signal, by) in [(node.x, place.x - bounds.x0), (node.y, place.y - bounds.y0)] {
+                if by != 0.0 {
+                    signal.set(signal.get() + by);
+                }
+            }
+        }
+    }
+
+    /// Maps the children's coordinates into the group's parent.
+    pub fn transform(&self) -> Affine {
+        Affine::translate((self.node.x.get(), self.node.y.get()))
+            * Affine::rotate(self.node.rotation.get())
+            * Affine::scale_non_uniform(self.node.scale_x.get(), self.node.scale_y.get())
+    }
+}
+
+impl Shape for IrGroup {
+    fn contains(&self, px: f64, py: f64) -> bool {
+        let local = self.transform().inverse() * Point::new(px, py);
+        let masked = self
+            .mask_shape()
+            .is_none_or(|mask| mask.contains(local.x, local.y));
+        // a container is hit anywhere in its box, not just on its children
+        let boxed = self
+            .container
+            .as_ref()
+            .is_some_and(|container| container.rect().contains(local));
+        masked
+            && (boxed
+                || self
+                    .contents()
+                    .any(|child| child.contains(local.x, local.y)))
+    }
+
+    fn bounds(&self) -> Rect {
+        let transform = self.transform();
+        match self.local_bounds() {
+            Some(bounds) => transform.transform_rect_bbox(bounds),
+            None => Rect::from_origin_size(transform.translation().to_point(), (0.0, 0.0)),
+        }
+    }
+
+    fn outline(&self) -> BezPath {
+        let mut outline = BezPath::new();
+        for child in self.contents() {
+            outline.extend(child.outline());
+        }
+        self.transform() * outline
+    }
+
+    fn draw(&self, scene: &mut Scene) {
+        let bounds = match self.local_bounds() {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        let transform = self.transform();
+        let opacity = self.opacity.get().clamp(0.0, 1.0) as f32;
+        let layer = if opacity < 1.0 || self.blend != Mix::Normal {
+            Some(self.blend)
+        } else if self.mask.is_some() {
+            // a plain clip is cheaper than a blended layer
+            Some(Mix::Clip)
+        } else {
+            None
+        };
+
+        if let Some(mix) = layer {
+            match self.mask_shape() {
+                Some(mask) => scene.push_layer(mix, opacity, transform, &mask.outline()),
+                None => scene.push_layer(mix, opacity, transform, &bounds),
+            }
+        }
+        let mut children = Scene::new();
+        for child in self.contents() {
+            child.draw(&mut children);
+        }
+        scene.append(&children, Some(transform));
+        if layer.is_some() {
+            scene.pop_layer();
+        }
+    }
+
+    fn node(&self) -> &Node {
+        &self.node
+    }
+
+    fn node_mut(&mut self) -> &mut Node {
+        &mut self.node
+    }
+
+    fn duplicate(&self) -> Box<dyn Shape> {
+        Box::new(Self {
+            node: self.node.duplicate(),
+            children: self
+                .children
+                .iter()
+                .map(|child| child.duplicate())
+                .collect(),
+            opacity: IrSignal::new(self.opacity.get()),
+            blend: self.blend,
+            mask: self.mask,
+            container: self.container.as_ref().map(Container::duplicate),
+        })
+    }
+
+    fn signal(&self, name: &str) -> Option<IrSignal> {
+        match name {
+            "opacity" => Some(self.opacity),
+            "width" => self.container.as_ref().map(|container| container.width),
+            "height" => self.container.as_ref().map(|container| container.height),
+            _ => self.node.signal(name),
+        }
+    }
+
+    fn as_group(&self) -> Option<&IrGroup> {
+        Some(self)
+    }
+
+    fn as_group_mut(&mut self) -> Option<&mut IrGroup> {
+        Some(self)
+    }
+}
+
+/// Text in one font, size and color, with lines broken at `\n`. The node
+/// places the top-left corner of the first line.
+pub struct IrText {
+    pub node: Node,
+    pub text: String,
+    pub font: Font,
+    // in world units, animatable as "size"
+    pub size: IrSignal,
+    pub color: Color,
+}
+
+/// Where an `IrText`'s glyphs go, relative to its node.
+pub struct TextLayout {
+    pub glyphs: Vec<Glyph>,
+    pub bounds: Rect,
+    // top of the caret after the last character
+    pub caret: Point,
+    pub line_height: f64,
+}
+
+impl IrText {
+    pub fn new(x: f64, y: f64, text: &str, font: Font, size: f64, color: Color) -> Self {
+        Self {
+            node: Node::new(x, y),
+            text: text.to_string(),
+            font,
+            size: IrSignal::new(size),
+            color,
+        }
+    }
+
+    /// Maps the layout's coordinates into the shape's parent.
+    pub fn transform(&self) -> Affine {
+        Affine::translate((self.node.x.get(), self.node.y.get()))
+    }
+
+    pub fn layout(&self) -> TextLayout {
+        let size = self.size.get();
+        let font = match font_ref(&self.font) {
+            Some(font) => font,
+            None => {
+                return TextLayout {
+                    glyphs: Vec::new(),
+                    bounds: Rect::new(0.0, 0.0, 0.0, size),
+                    caret: Point::ZERO,
+                    line_height: size,
+                }
+            }
+        };
+        let font_size = Size::new(size as f32);
+        let metrics = font.metrics(font_size, LocationRef::default());
+        let ascent = metrics.ascent as f64;
+        let line_height = (metrics.ascent - metrics.descent + metrics.leading) as f64;
+        let charmap = font.charmap();
+        let advances = font.glyph_metrics(font_size, LocationRef::default());
+
+        let mut glyphs = Vec::new();
+        let (mut x, mut top, mut width) = (0.0, 0.0, 0.0f64);
+        for ch in self.text.chars() {
+            if ch == '\n' {
+                width = width.max(x);
+                x = 0.0;
+                top += line_height;
+                continue;
+            }
+            let id = charmap.map(ch).unwrap_or_default();
+            glyphs.push(Glyph {
+                id: id.to_u32(),
+                x: x as f32,
+                y: (top + ascent) as f32,
+            });
+            x += advances.advance_width(id).unwrap_or_default() as f64;
+        }
+        TextLayout {
+            glyphs,
+            bounds: Rect::new(0.0, 0.0, width.max(x), top + line_height),
+            caret: Point::new(x, top),
+            line_height,
+        }
+    }
+}
+
+/// A font from TrueType/OpenType (or collection) data, or `None` if the
+/// data isn't a font.
+pub fn load_font(data: Vec<u8>) -> Option<Font> {
+    let font = Font::new(Blob::new(Arc::new(data)), 0);
+    font_ref(&font)?;
+    Some(font)
+}
+
+fn font_ref(font: &Font) -> Option<FontRef<'_>> {
+    match FileRef::new(font.data.as_ref()).ok()? {
+        FileRef::Font(font) => Some(font),
+        FileRef::Collection(collection) => collection.get(font.index).ok(),
+    }
+}
+
+impl Shape for IrText {
+    fn contains(&self, px: f64, py: f64) -> bool {
+        self.bounds().contains(Point::new(px, py))
+    }
+
+    fn bounds(&self) -> Rect {
+        self.transform().transform_rect_bbox(self.layout().bounds)
+    }
+
+    fn outline(&self) -> BezPath {
+        self.bounds().to_path(OUTLINE_TOLERANCE)
+    }
+
+    fn draw(&self, scene: &mut Scene) {
+        let layout = self.layout();
+        scene
+            .draw_glyphs(&self.font)
+            .font_size(self.size.get() as f32)
+            .transform(self.transform())
+            .brush(self.color)
+            .draw(Fill::NonZero, layout.glyphs.into_iter());
+    }
+
+    fn node(&self) -> &Node {
+        &self.node
+    }
+
+    fn node_mut(&mut self) -> &mut Node {
+        &mut self.node
+    }
+
+    fn duplicate(&self) -> Box<dyn Shape> {
+        Box::new(Self {
+            node: self.node.duplicate(),
+            text: self.text.clone(),
+            font: self.font.clone(),
+            size: IrSignal::new(self.size.get()),
+            color: self.color,
+        })
+    }
+
+    fn signal(&self, name: &str) -> Option<IrSignal> {
+        match name {
+            "size" => Some(self.size),
+            _ => self.node.signal(name),
+        }
+    }
+
+    fn color(&self) -> Option<Color> {
+        Some(self.color)
+    }
+
+    fn as_text(&self) -> Option<&IrText> {
+        Some(self)
+    }
+
+    fn as_text_mut(&mut self) -> Option<&mut IrText> {
+        Some(self)
+    }
+}
+
+/// Blend mode by its CSS `mix-blend-mode` name, e.g. "multiply".
+pub fn parse_blend_mode(name: &str) -> Option<Mix> {
+    Some(match name {
+        "normal" => Mix::Normal,
+        "multiply" => Mix::Multiply,
+        "screen" => Mix::Screen,
+        "overlay" => Mix::Overlay,
+        "darken" => Mix::Darken,
+        "lighten" => Mix::Lighten,
+        "color-dodge" => Mix::ColorDodge,
+        "color-burn" => Mix::ColorBurn,
+        "hard-light" => Mix::HardLight,
+        "soft-light" => Mix::SoftLight,
+        "difference" => Mix::Difference,
+        "exclusion" => Mix::Exclusion,
+        "hue" => Mix::Hue,
+        "saturation" => Mix::Saturation,
+        "color" => Mix::Color,
+        "luminosity" => Mix::Luminosity,
+        _ => return None,
+    })
+}
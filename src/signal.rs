@@ -0,0 +1,27 @@
+use leptos::prelude::Get;
+use leptos::prelude::Set;
+
+use reactive_graph::signal::{signal, ReadSignal, WriteSignal};
+
+// Both halves are arena handles, so copying an IrSignal gives another view
+// onto the same value rather than a snapshot.
+#[derive(Clone, Copy)]
+pub struct IrSignal {
+    pub get: ReadSignal<f64>,
+    pub set: WriteSignal<f64>,
+}
+
+impl IrSignal {
+    pub fn new(value: f64) -> Self {
+        let (get, set) = signal(value);
+        Self { get, set }
+    }
+
+    pub fn get(&self) -> f64 {
+        self.get.get()
+    }
+
+    pub fn set(&self, value: f64) {
+        self.set.set(value);
+    }
+}
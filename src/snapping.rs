@@ -0,0 +1,342 @@
+// Snapping for drags: to a background grid, to user guide lines and to the
+// edges and centres of other shapes, with smart-guide lines to show why a
+// drag snapped.
+
+use vello::kurbo::{Affine, Line, Point, Rect, Stroke, Vec2};
+use vello::peniko::Color;
+use vello::Scene;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Guide {
+    // x position of a vertical line
+    Vertical(f64),
+    // y position of a horizontal line
+    Horizontal(f64),
+}
+
+/// Modifier that suspends snapping while held during a drag.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SnapBypass {
+    Alt,
+    Ctrl,
+    Shift,
+    Meta,
+}
+
+pub struct SnapSettings {
+    pub enabled: bool,
+    // grid spacing in world units; `None` disables grid snapping
+    pub grid_size: Option<f64>,
+    pub show_grid: bool,
+    pub snap_to_shapes: bool,
+    pub guides: Vec<Guide>,
+    // in canvas pixels, so snapping feels the same at any zoom
+    pub threshold: f64,
+    pub bypass: SnapBypass,
+}
+
+impl Default for SnapSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            grid_size: None,
+            show_grid: false,
+            snap_to_shapes: true,
+            guides: Vec::new(),
+            threshold: 6.0,
+            bypass: SnapBypass::Alt,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SnapResult {
+    // add to the unsnapped position
+    pub offset: Vec2,
+    // smart guides to draw, in world coordinates
+    pub lines: Vec<Line>,
+}
+
+// Where a snap on one axis came from.
+#[derive(Clone, Copy)]
+enum Target {
+    Grid,
+    Guide,
+    Shape(Rect),
+}
+
+struct AxisSnap {
+    // value on the axis the moving edge/centre snaps to
+    value: f64,
+    distance: f64,
+    target: Target,
+}
+
+/// Snap `moving` (the bounds of what is being dragged, at its unsnapped
+/// position) against the grid, the guides and `others`. `threshold` is in
+/// world units.
+pub fn snap(moving: Rect, others: &[Rect], settings: &SnapSettings, threshold: f64) -> SnapResult {
+    let xs = [moving.x0, moving.center().x, moving.x1];
+    let ys = [moving.y0, moving.center().y, moving.y1];
+
+    let mut best_x: Option<(f64, AxisSnap)> = None;
+    let mut best_y: Option<(f64, AxisSnap)> = None;
+
+    let consider = |best: &mut Option<(f64, AxisSnap)>, from: f64, value: f64, target| {
+        let distance = (value - from).abs();
+        if distance <= threshold && best.as_ref().is_none_or(|(_, b)| distance < b.distance) {
+            *best = Some((
+                from,
+                AxisSnap {
+                    value,
+                    distance,
+                    target,
+                },
+            ));
+        }
+    };
+
+    if let Some(size) = settings.grid_size.filter(|size| *size > 0.0) {
+        // only edges snap to the grid; centres landing on it would be surprising
+        for from in [moving.x0, moving.x1] {
+            consider(
+                &mut best_x,
+                from,
+                (from / size).round() * size,
+                Target::Grid,
+            );
+        }
+        for from in [moving.y0, moving.y1] {
+            consider(
+                &mut best_y,
+                from,
+                (from / size).round() * size,
+                Target::Grid,
+            );
+        }
+    }
+
+    for guide in &settings.guides {
+        match *guide {
+            Guide::Vertical(x) => {
+                for from in xs {
+                    consider(&mut best_x, from, x, Target::Guide);
+                }
+            }
+            Guide::Horizontal(y) => {
+                for from in ys {
+                    consider(&mut best_y, from, y, Target::Guide);
+                }
+            }
+        }
+    }
+
+    if settings.snap_to_shapes {
+        for other in others {
+            for value in [other.x0, other.center().x, other.x1] {
+                for from in xs {
+                    consider(&mut best_x, from, value, Target::Shape(*other));
+                }
+            }
+            for value in [other.y0, other.center().y, other.y1] {
+                for from in ys {
+                    consider(&mut best_y, from, value, Target::Shape(*other));
+                }
+            }
+        }
+    }
+
+    let offset = Vec2::new(
+        best_x
+            .as_ref()
+            .map_or(0.0, |(from, snap)| snap.value - from),
+        best_y
+            .as_ref()
+            .map_or(0.0, |(from, snap)| snap.value - from),
+    );
+    let snapped = moving + offset;
+
+    // only snaps to shapes get a smart guide; the grid and guides are visible already
+    let mut lines = Vec::new();
+    if let Some((
+        _,
+        AxisSnap {
+            value,
+            target: Target::Shape(other),
+            ..
+        },
+    )) = best_x
+    {
+        let y0 = snapped.y0.min(other.y0);
+        let y1 = snapped.y1.max(other.y1);
+        lines.push(Line::new((value, y0), (value, y1)));
+    }
+    if let Some((
+        _,
+        AxisSnap {
+            value,
+            target: Target::Shape(other),
+            ..
+        },
+    )) = best_y
+    {
+        let x0 = snapped.x0.min(other.x0);
+        let x1 = snapped.x1.max(other.x1);
+        lines.push(Line::new((x0, value), (x1, value)));
+    }
+
+    SnapResult { offset, lines }
+}
+
+const GRID_COLOR: Color = Color::from_rgba8(0, 0, 0, 24);
+const GUIDE_COLOR: Color = Color::from_rgba8(0, 150, 255, 200);
+const SMART_GUIDE_COLOR: Color = Color::from_rgba8(255, 0, 140, 255);
+
+/// Draw the grid in canvas space, so lines stay one pixel wide at any zoom.
+/// It goes behind the document. `view` maps world to canvas pixels.
+pub fn draw_grid(
+    scene: &mut Scene,
+    settings: &SnapSettings,
+    view: Affine,
+    width: f64,
+    height: f64,
+) {
+    if !settings.show_grid {
+        return;
+    }
+    let size = match settings.grid_size.filter(|size| *size > 0.0) {
+        Some(size) => size,
+        None => return,
+    };
+    // skip the grid when zoomed out so far it would be a solid wash
+    if size * view.as_coeffs()[0] < 4.0 {
+        return;
+    }
+    let stroke = Stroke::new(1.0);
+    let (top_left, bottom_right) = visible_corners(view, width, height);
+    let mut x = (top_left.x / size).floor() * size;
+    while x <= bottom_right.x {
+        let line = view * Line::new((x, top_left.y), (x, bottom_right.y));
+        scene.stroke(&stroke, Affine::IDENTITY, GRID_COLOR, None, &line);
+        x += size;
+    }
+    let mut y = (top_left.y / size).floor() * size;
+    while y <= bottom_right.y {
+        let line = view * Line::new((top_left.x, y), (bottom_right.x, y));
+        scene.stroke(&stroke, Affine::IDENTITY, GRID_COLOR, None, &line);
+        y += size;
+    }
+}
+
+/// Draw the guides and smart guides over the document, in canvas space like
+/// the grid.
+pub fn draw_overlay(
+    scene: &mut Scene,
+    settings: &SnapSettings,
+    smart_guides: &[Line],
+    view: Affine,
+    width: f64,
+    height: f64,
+) {
+    let stroke = Stroke::new(1.0);
+    let (top_left, bottom_right) = visible_corners(view, width, height);
+
+    for guide in &settings.guides {
+        let line = match *guide {
+            Guide::Vertical(x) => Line::new((x, top_left.y), (x, bottom_right.y)),
+            Guide::Horizontal(y) => Line::new((top_left.x, y), (bottom_right.x, y)),
+        };
+        scene.stroke(&stroke, Affine::IDENTITY, GUIDE_COLOR, None, &(view * line));
+    }
+
+    for line in smart_guides {
+        scene.stroke(
+            &stroke,
+            Affine::IDENTITY,
+            SMART_GUIDE_COLOR,
+            None,
+            &(view * *line),
+        );
+    }
+}
+
+// the world coordinates at the canvas's top-left and bottom-right corners
+fn visible_corners(view: Affine, width: f64, height: f64) -> (Point, Point) {
+    let inverse = view.inverse();
+    (inverse * Point::ZERO, inverse * Point::new(width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> SnapSettings {
+        SnapSettings {
+            snap_to_shapes: false,
+            ..SnapSettings::default()
+        }
+    }
+
+    #[test]
+    fn edges_snap_to_the_nearest_grid_line() {
+        let settings = SnapSettings {
+            grid_size: Some(10.0),
+            ..settings()
+        };
+        let result = snap(Rect::new(12.0, 27.0, 32.0, 45.0), &[], &settings, 4.0);
+        // 45 is 5 from the grid, past the threshold, so y snaps its top edge
+        assert_eq!(result.offset, Vec2::new(-2.0, 3.0));
+        assert!(result.lines.is_empty());
+
+        // centres don't snap to the grid
+        let result = snap(Rect::new(13.0, 13.0, 27.0, 27.0), &[], &settings, 2.0);
+        assert_eq!(result.offset, Vec2::ZERO);
+    }
+
+    #[test]
+    fn edges_and_centres_snap_to_guides() {
+        let settings = SnapSettings {
+            guides: vec![Guide::Vertical(100.0), Guide::Horizontal(50.0)],
+            ..settings()
+        };
+        let result = snap(Rect::new(88.0, 0.0, 108.0, 20.0), &[], &settings, 4.0);
+        assert_eq!(result.offset, Vec2::new(2.0, 0.0));
+        assert!(result.lines.is_empty());
+    }
+
+    #[test]
+    fn shapes_snap_with_a_smart_guide() {
+        let settings = SnapSettings::default();
+        let other = Rect::new(0.0, 0.0, 50.0, 50.0);
+
+        // left edge to the other's right edge
+        let result = snap(Rect::new(53.0, 20.0, 73.0, 40.0), &[other], &settings, 4.0);
+        assert_eq!(result.offset, Vec2::new(-3.0, 0.0));
+        assert_eq!(result.lines, [Line::new((50.0, 0.0), (50.0, 50.0))]);
+
+        // centre to centre, closer than either edge
+        let result = snap(Rect::new(60.0, 23.0, 80.0, 29.0), &[other], &settings, 4.0);
+        assert_eq!(result.offset, Vec2::new(0.0, -1.0));
+        assert_eq!(result.lines, [Line::new((0.0, 25.0), (80.0, 25.0))]);
+
+        let off = SnapSettings {
+            snap_to_shapes: false,
+            ..SnapSettings::default()
+        };
+        let result = snap(Rect::new(53.0, 20.0, 73.0, 40.0), &[other], &off, 4.0);
+        assert_eq!(result.offset, Vec2::ZERO);
+    }
+
+    #[test]
+    fn the_closest_target_wins() {
+        let settings = SnapSettings {
+            guides: vec![Guide::Vertical(51.0)],
+            ..SnapSettings::default()
+        };
+        let other = Rect::new(0.0, 0.0, 50.0, 50.0);
+        let result = snap(Rect::new(53.0, 60.0, 73.0, 80.0), &[other], &settings, 4.0);
+        assert_eq!(result.offset, Vec2::new(-2.0, 0.0));
+        // the guide is already visible, so there's no smart guide
+        assert!(result.lines.is_empty());
+    }
+}
@@ -0,0 +1,196 @@
+// A timeline of animation clips driven by an explicit clock, so playback can
+// be seeked and exported frame by frame with identical results every time.
+
+use crate::animation::Animation;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::editor::Editor;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::render::{HeadlessRenderer, ImageError, ImageParams, RgbaImage};
+
+struct Clip {
+    animation: Animation,
+    // seconds from the start of the timeline
+    start: f64,
+}
+
+pub struct Timeline {
+    // sorted by start, so later clips win where they overlap on a signal
+    clips: Vec<Clip>,
+    time: f64,
+    playing: bool,
+    rate: f64,
+    // wall-clock time of the last `advance`, while playing
+    last_clock: Option<f64>,
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self {
+            clips: Vec::new(),
+            time: 0.0,
+            playing: false,
+            rate: 1.0,
+            last_clock: None,
+        }
+    }
+}
+
+impl Timeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Schedule `animation` to start `start` seconds into the timeline.
+    pub fn add(&mut self, animation: Animation, start: f64) {
+        let index = self.clips.partition_point(|clip| clip.start <= start);
+        self.clips.insert(index, Clip { animation, start });
+    }
+
+    pub fn clear(&mut self) {
+        self.clips.clear();
+        self.time = 0.0;
+    }
+
+    /// End of the last clip, `None` if any clip repeats forever.
+    pub fn duration(&self) -> Option<f64> {
+        self.clips.iter().try_fold(0.0, |end: f64, clip| {
+            clip.animation
+                .total_duration()
+                .map(|total| end.max(clip.start + total))
+        })
+    }
+
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// Jump to `time` and write every clip's values into its signals. The
+    /// result depends only on `time`, never on earlier seeks: a signal takes
+    /// its value from the latest clip that has started, or, before any has,
+    /// from the start of the earliest one.
+    pub fn seek(&mut self, time: f64) {
+        self.time = time.max(0.0);
+        let started = self.clips.partition_point(|clip| clip.start <= self.time);
+        for clip in self.clips[started..].iter().rev() {
+            clip.animation.apply(0.0);
+        }
+        for clip in &self.clips[..started] {
+            clip.animation.apply(self.time - clip.start);
+        }
+    }
+
+    pub fn play(&mut self) {
+        self.playing = true;
+        self.last_clock = None;
+    }
+
+    pub fn pause(&mut self) {
+        self.playing = false;
+        self.last_clock = None;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    /// Playback speed; negative values play backwards.
+    pub fn set_rate(&mut self, rate: f64) {
+        self.rate = rate;
+    }
+
+    /// Move the playhead by `dt` seconds of timeline time scaled by the
+    /// playback rate, pausing at either end.
+    pub fn step(&mut self, dt: f64) {
+        let mut time = self.time + dt * self.rate;
+        if let Some(duration) = self.duration() {
+            if time >= duration {
+                time = duration;
+                self.pause();
+            }
+        }
+        if time <= 0.0 && self.rate < 0.0 {
+            time = 0.0;
+            self.pause();
+        }
+        self.seek(time);
+    }
+
+    /// Advance from a wall clock (seconds) while playing. Returns true while
+    /// still playing.
+    pub fn advance(&mut self, clock: f64) -> bool {
+        if !self.playing {
+            return false;
+        }
+        let dt = self.last_clock.map_or(0.0, |last| clock - last);
+        self.last_clock = Some(clock);
+        self.step(dt);
+        self.playing
+    }
+
+    /// Times of every frame at `fps` from 0 to the end of the timeline
+    /// (inclusive), or for `limit` seconds if it loops forever.
+    pub fn frame_times(&self, fps: f64, limit: f64) -> impl Iterator<Item = f64> {
+        let end = self.duration().unwrap_or(limit);
+        let count = if fps > 0.0 {
+            (end * fps + 1e-9).floor() as usize + 1
+        } else {
+            0
+        };
+        // multiply rather than accumulate so frame n is always exactly n / fps
+        (0..count).map(move |n| n as f64 / fps)
+    }
+}
+
+/// Renders an editor's timeline frame by frame through a headless renderer.
+/// Springs run on the wall clock, so spring followers are not part of the
+/// export; everything keyframed is.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct FrameRenderer<'a> {
+    editor: &'a mut Editor,
+    renderer: &'a mut HeadlessRenderer,
+    params: ImageParams,
+    times: std::vec::IntoIter<f64>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<'a> FrameRenderer<'a> {
+    /// Frames at `fps` over the whole timeline, or its first `limit` seconds
+    /// if it loops forever.
+    pub fn new(
+        editor: &'a mut Editor,
+        renderer: &'a mut HeadlessRenderer,
+        params: ImageParams,
+        fps: f64,
+        limit: f64,
+    ) -> Self {
+        let times: Vec<f64> = editor.timeline().frame_times(fps, limit).collect();
+        Self {
+            editor,
+            renderer,
+            params,
+            times: times.into_iter(),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Iterator for FrameRenderer<'_> {
+    type Item = Result<RgbaImage, ImageError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let time = self.times.next()?;
+        self.editor.seek_timeline(time);
+        Some(
+            self.renderer
+                .render_blocking(&self.editor.build_scene(), &self.params),
+        )
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.times.size_hint()
+    }
+}
@@ -0,0 +1,487 @@
+// Interaction tools. The active tool gets pointer events, and key presses
+// before the keymap does. The built-in tools select, draw shapes, draw
+// freehand, pan and type text; apps can add their own by implementing
+// `Tool` and registering it with the editor.
+
+use std::collections::HashMap;
+use std::f64::consts::FRAC_PI_4;
+
+use vello::kurbo::{
+    self, Affine, BezPath, Ellipse, Line, Point, Rect, Shape as _, Stroke, StrokeOpts, Vec2,
+};
+use vello::peniko::{Color, Fill};
+use vello::Scene;
+
+use crate::editor::{Editor, ShapeId};
+use crate::freehand::FreehandStroke;
+use crate::input::{Modifiers, PointerEvent, PointerPhase, PointerType, PRIMARY_BUTTON};
+use crate::shapes::{IrPath, IrRectangle, IrText, Shape};
+
+// freehand samples closer together than this, in canvas pixels, are merged
+const BRUSH_SAMPLE_SPACING: f64 = 2.0;
+
+// a shape drag shorter than this, in canvas pixels, is a click and draws nothing
+const MIN_SHAPE_DRAG: f64 = 3.0;
+
+const FLATTEN_TOLERANCE: f64 = 0.1;
+
+// text caret and the box around text being typed
+const TEXT_CARET_COLOR: Color = Color::from_rgba8(0, 150, 255, 255);
+
+pub trait Tool {
+    /// Handle one pointer event. Returns true if the scene needs redrawing.
+    fn pointer_event(&mut self, editor: &mut Editor, event: &PointerEvent) -> bool;
+
+    /// Handle a key press before the keymap sees it. Returns true if the
+    /// tool used the key.
+    fn key_down(&mut self, _editor: &mut Editor, _key: &str, _modifiers: Modifiers) -> bool {
+        false
+    }
+
+    /// Draw previews over the document, in canvas pixels.
+    fn draw_overlay(&self, _editor: &Editor, _scene: &mut Scene) {}
+
+    /// Another tool is taking over: finish or drop any work in progress.
+    fn deactivate(&mut self, _editor: &mut Editor) {}
+}
+
+/// The tools every editor starts with, by name.
+pub fn built_in() -> Vec<(&'static str, Box<dyn Tool>)> {
+    vec![
+        ("select", Box::new(SelectTool)),
+        ("rectangle", Box::new(ShapeTool::new(ShapeKind::Rectangle))),
+        ("ellipse", Box::new(ShapeTool::new(ShapeKind::Ellipse))),
+        ("line", Box::new(ShapeTool::new(ShapeKind::Line))),
+        ("pen", Box::new(PenTool::default())),
+        ("hand", Box::new(HandTool::default())),
+        ("text", Box::new(TextTool::default())),
+    ]
+}
+
+/// Selects, drags and edits shapes; see `Editor::select_pointer_event`.
+pub struct SelectTool;
+
+impl Tool for SelectTool {
+    fn pointer_event(&mut self, editor: &mut Editor, event: &PointerEvent) -> bool {
+        editor.select_pointer_event(event)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShapeKind {
+    Rectangle,
+    Ellipse,
+    Line,
+}
+
+/// Draws a rectangle or ellipse from corner to corner, or a line from end
+/// to end, in the brush color. Shift makes squares and circles, and keeps
+/// lines to multiples of 45°.
+pub struct ShapeTool {
+    kind: ShapeKind,
+    drag: Option<ShapeDrag>,
+}
+
+struct ShapeDrag {
+    pointer_id: i32,
+    // world coordinates
+    start: Point,
+    end: Point,
+}
+
+impl ShapeTool {
+    pub fn new(kind: ShapeKind) -> Self {
+        Self { kind, drag: None }
+    }
+
+    // The shape the drag in progress would make, in world coordinates.
+    fn outline(&self, editor: &Editor, drag: &ShapeDrag) -> BezPath {
+        let rect = Rect::from_points(drag.start, drag.end);
+        match self.kind {
+            ShapeKind::Rectangle => rect.to_path(FLATTEN_TOLERANCE),
+            ShapeKind::Ellipse => Ellipse::from_rect(rect).to_path(FLATTEN_TOLERANCE),
+            ShapeKind::Line => kurbo::stroke(
+                Line::new(drag.start, drag.end).path_elements(FLATTEN_TOLERANCE),
+                &Stroke::new(editor.brush().width),
+                &StrokeOpts::default(),
+                FLATTEN_TOLERANCE,
+            ),
+        }
+    }
+
+    fn constrain(&self, start: Point, end: Point, modifiers: Modifiers) -> Point {
+        if !modifiers.shift {
+            return end;
+        }
+        let delta = end - start;
+        match self.kind {
+            ShapeKind::Rectangle | ShapeKind::Ellipse => {
+                let size = delta.x.abs().max(delta.y.abs());
+                start + Vec2::new(size.copysign(delta.x), size.copysign(delta.y))
+            }
+            ShapeKind::Line => {
+                let angle = (delta.atan2() / FRAC_PI_4).round() * FRAC_PI_4;
+                start + Vec2::from_angle(angle) * delta.hypot()
+            }
+        }
+    }
+
+    fn finish(&self, editor: &mut Editor, drag: &ShapeDrag) {
+        if (drag.end - drag.start).hypot() * editor.zoom() < MIN_SHAPE_DRAG {
+            return;
+        }
+        let color = editor.brush().color;
+        let shape: Box<dyn Shape> = match self.kind {
+            ShapeKind::Rectangle => {
+                let rect = Rect::from_points(drag.start, drag.end);
+                Box::new(IrRectangle::new(
+                    rect.x0,
+                    rect.y0,
+                    rect.width(),
+                    rect.height(),
+                    color,
+                ))
+            }
+            _ => Box::new(IrPath::from_outline(
+                self.outline(editor, drag),
+                Fill::NonZero,
+                color,
+            )),
+        };
+        let id = editor.create_shape(shape);
+        editor.set_selection(vec![id]);
+    }
+}
+
+impl Tool for ShapeTool {
+    fn pointer_event(&mut self, editor: &mut Editor, event: &PointerEvent) -> bool {
+        let world = editor.screen_to_world(event.position());
+        match event.phase {
+            PointerPhase::Down if event.buttons & PRIMARY_BUTTON != 0 => {
+                self.drag = Some(ShapeDrag {
+                    pointer_id: event.pointer_id,
+                    start: world,
+                    end: world,
+                });
+                true
+            }
+            PointerPhase::Down => false,
+            PointerPhase::Move => {
+                let start = match &self.drag {
+                    Some(drag) if drag.pointer_id == event.pointer_id => drag.start,
+                    _ => return false,
+                };
+                let end = self.constrain(start, world, event.modifiers);
+                self.drag.as_mut().unwrap().end = end;
+                true
+            }
+            PointerPhase::Up | PointerPhase::Cancel => {
+                match self.drag.take() {
+                    Some(drag) if drag.pointer_id == event.pointer_id => {
+                        if event.phase == PointerPhase::Up {
+                            self.finish(editor, &drag);
+                        }
+                        true
+                    }
+                    // another pointer's release leaves the drag going
+                    other => {
+                        self.drag = other;
+                        false
+                    }
+                }
+            }
+        }
+    }
+
+    fn draw_overlay(&self, editor: &Editor, scene: &mut Scene) {
+        if let Some(drag) = &self.drag {
+            scene.fill(
+                Fill::NonZero,
+                editor.view_transform(),
+                editor.brush().color,
+                None,
+                &self.outline(editor, drag),
+            );
+        }
+    }
+
+    fn deactivate(&mut self, _editor: &mut Editor) {
+        self.drag = None;
+    }
+}
+
+/// Draws freehand strokes with the editor's brush; see `freehand`.
+#[derive(Default)]
+pub struct PenTool {
+    // strokes being drawn, by pointer id
+    strokes: HashMap<i32, FreehandStroke>,
+}
+
+// pen pressure, if the brush uses it; anything else draws at full width
+fn brush_pressure(editor: &Editor, event: &PointerEvent) -> f64 {
+    if editor.brush().pressure && event.pointer_type == PointerType::Pen {
+        event.pressure
+    } else {
+        1.0
+    }
+}
+
+impl Tool for PenTool {
+    fn pointer_event(&mut self, editor: &mut Editor, event: &PointerEvent) -> bool {
+        let world = editor.screen_to_world(event.position());
+        let pressure = brush_pressure(editor, event);
+        match event.phase {
+            PointerPhase::Down if event.buttons & PRIMARY_BUTTON != 0 => {
+                let mut stroke = FreehandStroke::new(
+                    editor.brush().smoothing,
+                    BRUSH_SAMPLE_SPACING / editor.zoom(),
+                );
+                stroke.push(world, pressure);
+                self.strokes.insert(event.pointer_id, stroke);
+                true
+            }
+            PointerPhase::Down => false,
+            PointerPhase::Move => match self.strokes.get_mut(&event.pointer_id) {
+                Some(stroke) => {
+                    stroke.push(world, pressure);
+                    true
+                }
+                None => false,
+            },
+            PointerPhase::Up | PointerPhase::Cancel => {
+                let stroke = match self.strokes.remove(&event.pointer_id) {
+                    Some(stroke) => stroke,
+                    None => return false,
+                };
+                // a cancelled stroke is dropped
+                if event.phase == PointerPhase::Cancel {
+                    return true;
+                }
+                let brush = *editor.brush();
+                if let Some(outline) = stroke.finish(brush.width, brush.tolerance / editor.zoom()) {
+                    editor.create_shape(Box::new(IrPath::from_outline(
+                        outline,
+                        Fill::NonZero,
+                        brush.color,
+                    )));
+                }
+                true
+            }
+        }
+    }
+
+    fn draw_overlay(&self, editor: &Editor, scene: &mut Scene) {
+        let brush = editor.brush();
+        for stroke in self.strokes.values() {
+            scene.fill(
+                Fill::NonZero,
+                editor.view_transform(),
+                brush.color,
+                None,
+                &stroke.outline(brush.width),
+            );
+        }
+    }
+
+    fn deactivate(&mut self, _editor: &mut Editor) {
+        self.strokes.clear();
+    }
+}
+
+/// Drags the view around.
+#[derive(Default)]
+pub struct HandTool {
+    // the pointer doing the dragging and where it was last, in canvas pixels
+    grab: Option<(i32, Point)>,
+}
+
+impl Tool for HandTool {
+    fn pointer_event(&mut self, editor: &mut Editor, event: &PointerEvent) -> bool {
+        match event.phase {
+            PointerPhase::Down => {
+                if self.grab.is_none() {
+                    self.grab = Some((event.pointer_id, event.position()));
+                }
+                false
+            }
+            PointerPhase::Move => match self.grab {
+                Some((id, last)) if id == event.pointer_id => {
+                    editor.pan_by(event.position() - last);
+                    self.grab = Some((id, event.position()));
+                    true
+                }
+                _ => false,
+            },
+            PointerPhase::Up | PointerPhase::Cancel => {
+                if matches!(self.grab, Some((id, _)) if id == event.pointer_id) {
+                    self.grab = None;
+                }
+                false
+            }
+        }
+    }
+
+    fn deactivate(&mut self, _editor: &mut Editor) {
+        self.grab = None;
+    }
+}
+
+/// Click to start typing, or click existing text to edit it. Enter starts a
+/// new line; escape, clicking elsewhere or switching tools finishes. Needs a
+/// font set with `Editor::set_text_style`.
+#[derive(Default)]
+pub struct TextTool {
+    typing: Option<Typing>,
+}
+
+enum Typing {
+    // clicked on empty canvas; the shape is made with the first character
+    New { at: Point },
+    // the text as it was before, for undo; `None` if it is new
+    Shape { id: ShapeId, before: Option<String> },
+}
+
+impl TextTool {
+    fn finish(&mut self, editor: &mut Editor) {
+        if let Some(Typing::Shape {
+            id,
+            before: Some(before),
+        }) = self.typing.take()
+        {
+            editor.record_text_change(id, before);
+        }
+    }
+
+    // run `edit` on the text being typed, making it first if need be
+    fn edit(&mut self, editor: &mut Editor, edit: impl FnOnce(&mut String)) -> bool {
+        let id = match self.typing {
+            Some(Typing::Shape { id, .. }) => id,
+            Some(Typing::New { at }) => {
+                let mut text = String::new();
+                edit(&mut text);
+                if text.is_empty() {
+                    return false;
+                }
+                let (font, size) = match editor.text_style() {
+                    Some((font, size)) => (font.clone(), size),
+                    None => return false,
+                };
+                let color = editor.brush().color;
+                let shape = IrText::new(at.x, at.y, &text, font, size, color);
+                // typing after this changes the inserted shape, so undo
+                // takes away the whole text at once
+                let id = editor.create_shape(Box::new(shape));
+                self.typing = Some(Typing::Shape { id, before: None });
+                return true;
+            }
+            None => return false,
+        };
+        match editor.shape_mut(id).and_then(|shape| shape.as_text_mut()) {
+            Some(shape) => {
+                edit(&mut shape.text);
+                true
+            }
+            None => {
+                self.typing = None;
+                false
+            }
+        }
+    }
+}
+
+impl Tool for TextTool {
+    fn pointer_event(&mut self, editor: &mut Editor, event: &PointerEvent) -> bool {
+        if event.phase != PointerPhase::Down || event.buttons & PRIMARY_BUTTON == 0 {
+            return false;
+        }
+        self.finish(editor);
+        let world = editor.screen_to_world(event.position());
+        let hit = editor
+            .shape_at(world.x, world.y)
+            .filter(|id| editor.shape(*id).and_then(|s| s.as_text()).is_some());
+        self.typing = match hit {
+            Some(id) => {
+                let text = editor.shape(id).unwrap().as_text().unwrap().text.clone();
+                editor.set_selection(vec![id]);
+                Some(Typing::Shape {
+                    id,
+                    before: Some(text),
+                })
+            }
+            None if editor.text_style().is_some() => {
+                editor.set_selection(Vec::new());
+                Some(Typing::New { at: world })
+            }
+            None => None,
+        };
+        true
+    }
+
+    fn key_down(&mut self, editor: &mut Editor, key: &str, modifiers: Modifiers) -> bool {
+        // shortcuts like undo still go to the keymap
+        if self.typing.is_none() || modifiers.ctrl || modifiers.meta {
+            return false;
+        }
+        match key {
+            "Escape" => {
+                self.finish(editor);
+                true
+            }
+            "Enter" => {
+                self.edit(editor, |text| text.push('\n'));
+                true
+            }
+            "Backspace" => {
+                self.edit(editor, |text| {
+                    text.pop();
+                });
+                true
+            }
+            _ if key.chars().count() == 1 => {
+                self.edit(editor, |text| text.push_str(key));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn draw_overlay(&self, editor: &Editor, scene: &mut Scene) {
+        let view = editor.view_transform();
+        let (caret, height, transform) = match &self.typing {
+            Some(Typing::New { at }) => match editor.text_style() {
+                Some((_, size)) => (*at, size, view),
+                None => return,
+            },
+            Some(Typing::Shape { id, .. }) => {
+                let shape = match editor.shape(*id).and_then(|s| s.as_text()) {
+                    Some(shape) => shape,
+                    None => return,
+                };
+                let layout = shape.layout();
+                let transform = view * editor.parent_transform(*id) * shape.transform();
+                scene.stroke(
+                    &Stroke::new(1.0).with_dashes(0.0, [4.0, 4.0]),
+                    Affine::IDENTITY,
+                    TEXT_CARET_COLOR,
+                    None,
+                    &transform.transform_rect_bbox(layout.bounds),
+                );
+                (layout.caret, layout.line_height, transform)
+            }
+            None => return,
+        };
+        let top = transform * caret;
+        let bottom = transform * (caret + Vec2::new(0.0, height));
+        scene.stroke(
+            &Stroke::new(1.5),
+            Affine::IDENTITY,
+            TEXT_CARET_COLOR,
+            None,
+            &Line::new(top, bottom),
+        );
+    }
+
+    fn deactivate(&mut self, editor: &mut Editor) {
+        self.finish(editor);
+    }
+}
@@ -0,0 +1,2026 @@
+// wasm-bindgen surface: everything that needs the DOM or a canvas surface.
+
+use std::cell::{RefCell, RefMut};
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::ops::{Deref, DerefMut};
+use std::rc::{Rc, Weak};
+
+use vello::{
+    kurbo::{BezPath, Point, Rect},
+    peniko::{Color, Fill},
+    wgpu, AaConfig, AaSupport, RenderParams, Renderer, RendererOptions,
+};
+use wasm_bindgen::prelude::*;
+use web_sys::{HtmlCanvasElement, Window};
+// use web_sys::VideoEncoder;
+
+use crate::animation::{Animation, Easing, Keyframe, Repeat, Track};
+use crate::arrange::Alignment;
+use crate::boolean::BooleanOp;
+use crate::connector::{Arrowhead, Attachment, Endpoint, IrConnector, Routing};
+use crate::constraints::{Axis, Constraint, Extent};
+use crate::editor::{Editor, EditorEvent};
+use crate::graph_layout::{GraphLayout, LayoutAlgorithm};
+use crate::input::{Modifiers, PointerEvent, PointerPhase, PointerType};
+use crate::keymap::{Action, KeyChord};
+use crate::layout::{Align, Grid, Justify, Layout, Stack};
+use crate::path_edit::PointKind;
+use crate::physics::SpringConfig;
+use crate::recording::{
+    Codec, EncodedChunk, FlushFuture, FrameEncoder, Recorder, RecordingError, RecordingSettings,
+};
+use crate::render::{self, ImageParams, RgbaImage};
+use crate::shapes::{self, IrCircle, IrPath, IrRectangle};
+use crate::snapping::{Guide, SnapBypass};
+use crate::tools::Tool;
+
+#[wasm_bindgen]
+extern "C" {
+    fn alert(s: &str);
+
+    #[wasm_bindgen(js_namespace = console)]
+    fn log(s: &str);
+}
+
+#[wasm_bindgen]
+pub fn greet(name: &str) -> String {
+    format!("Hello from rust!, {}!", name)
+}
+
+macro_rules! console_log {
+    // Note that this is using the `log` function imported above during
+    // `bare_bones`
+    ($($t:tt)*) => (log(&format_args!($($t)*).to_string()))
+}
+
+// what `VelloContext::on` accepts, as named by `EditorEvent::name`
+const EVENT_NAMES: [&str; 5] = ["click", "drag_start", "drag_end", "select", "change"];
+
+// Called when the Wasm module is instantiated
+#[wasm_bindgen(start)]
+fn main() -> Result<(), JsValue> {
+    // Use `web_sys`'s global `window` function to get a handle on the global
+    // window object.
+    let window = web_sys::window().expect("no global `window` exists");
+    let document = window.document().expect("should have a document on window");
+    let body = document.body().expect("document should have a body");
+
+    // Manufacture the element we're gonna append
+    let val = document.create_element("p")?;
+    val.set_inner_html("My message yay!!");
+
+    body.append_child(&val)?;
+
+    Ok(())
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Antialiasing {
+    Area,
+    Msaa8,
+    Msaa16,
+}
+
+impl From<Antialiasing> for AaConfig {
+    fn from(method: Antialiasing) -> Self {
+        match method {
+            Antialiasing::Area => AaConfig::Area,
+            Antialiasing::Msaa8 => AaConfig::Msaa8,
+            Antialiasing::Msaa16 => AaConfig::Msaa16,
+        }
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PresentMode {
+    AutoVsync,
+    AutoNoVsync,
+    Fifo,
+    Immediate,
+    Mailbox,
+}
+
+impl From<PresentMode> for wgpu::PresentMode {
+    fn from(mode: PresentMode) -> Self {
+        match mode {
+            PresentMode::AutoVsync => wgpu::PresentMode::AutoVsync,
+            PresentMode::AutoNoVsync => wgpu::PresentMode::AutoNoVsync,
+            PresentMode::Fifo => wgpu::PresentMode::Fifo,
+            PresentMode::Immediate => wgpu::PresentMode::Immediate,
+            PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+        }
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PowerPreference {
+    Default,
+    LowPower,
+    HighPerformance,
+}
+
+impl From<PowerPreference> for wgpu::PowerPreference {
+    fn from(preference: PowerPreference) -> Self {
+        match preference {
+            PowerPreference::Default => wgpu::PowerPreference::None,
+            PowerPreference::LowPower => wgpu::PowerPreference::LowPower,
+            PowerPreference::HighPerformance => wgpu::PowerPreference::HighPerformance,
+        }
+    }
+}
+
+/// Settings for `VelloContext::create`. The defaults match what the context
+/// always used: a light grey background, MSAA 8x and vsync.
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct VelloContextOptions {
+    background: Color,
+    antialiasing: Antialiasing,
+    present_mode: PresentMode,
+    power_preference: PowerPreference,
+}
+
+impl Default for VelloContextOptions {
+    fn default() -> Self {
+        Self {
+            background: Color::from_rgb8(240, 240, 240),
+            antialiasing: Antialiasing::Msaa8,
+            present_mode: PresentMode::AutoVsync,
+            power_preference: PowerPreference::Default,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl VelloContextOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> VelloContextOptions {
+        Self::default()
+    }
+
+    pub fn set_background(&mut self, r: u8, g: u8, b: u8, a: u8) {
+        self.background = Color::from_rgba8(r, g, b, a);
+    }
+
+    pub fn set_transparent_background(&mut self) {
+        self.background = Color::TRANSPARENT;
+    }
+
+    pub fn set_antialiasing(&mut self, method: Antialiasing) {
+        self.antialiasing = method;
+    }
+
+    pub fn set_present_mode(&mut self, mode: PresentMode) {
+        self.present_mode = mode;
+    }
+
+    pub fn set_power_preference(&mut self, preference: PowerPreference) {
+        self.power_preference = preference;
+    }
+}
+
+struct RenderState<'s> {
+    // SAFETY: We MUST drop the surface before the `window`, so the fields
+    // must be in this order
+    surface: wgpu::Surface<'s>,
+    config: wgpu::SurfaceConfiguration,
+    window: Window,
+}
+
+// Shared between the JS-owned `VelloContext` and any `ShapeHandle`s it hands
+// out, which only keep a weak reference.
+struct CanvasContext {
+    editor: Editor,
+    canvas: HtmlCanvasElement,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    state: RenderState<'static>,
+    renderer: Renderer,
+    background: Color,
+    antialiasing: AaConfig,
+    // `requestAnimationFrame` callback, kept alive here while animations run
+    frame_callback: Option<Rc<Closure<dyn FnMut(f64)>>>,
+    frame_requested: bool,
+    recording: Option<Recorder<WebCodecsEncoder>>,
+    // JS callbacks registered with `on`, and per-shape `on_click` ones; they
+    // are released along with the context
+    listeners: Vec<(String, js_sys::Function)>,
+    click_listeners: HashMap<usize, Vec<js_sys::Function>>,
+}
+
+#[wasm_bindgen]
+pub struct VelloContext {
+    inner: Rc<RefCell<CanvasContext>>,
+}
+
+#[wasm_bindgen]
+pub struct ShapeHandle {
+    id: usize,
+    context: Weak<RefCell<CanvasContext>>,
+}
+
+/// An axis-aligned box, returned by `ShapeHandle::get_bounds`.
+#[wasm_bindgen]
+#[derive(Clone, Copy)]
+pub struct Bounds {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl From<Rect> for Bounds {
+    fn from(rect: Rect) -> Self {
+        Self {
+            x: rect.x0,
+            y: rect.y0,
+            width: rect.width(),
+            height: rect.height(),
+        }
+    }
+}
+
+/// What `VelloContext::on` and `ShapeHandle::on_click` listeners are called
+/// with. `x` and `y` are where the pointer was, in world coordinates, for
+/// "click", "drag_start" and "drag_end", and 0 otherwise.
+#[wasm_bindgen]
+pub struct CanvasEvent {
+    name: &'static str,
+    shapes: Vec<u32>,
+    pub x: f64,
+    pub y: f64,
+}
+
+#[wasm_bindgen]
+impl CanvasEvent {
+    /// "click", "drag_start", "drag_end", "select" or "change".
+    #[wasm_bindgen(getter, js_name = type)]
+    pub fn name(&self) -> String {
+        self.name.to_string()
+    }
+
+    /// Ids of the shapes clicked, dragged or now selected, matching
+    /// `ShapeHandle::id`; empty for "change".
+    #[wasm_bindgen(getter)]
+    pub fn shapes(&self) -> Vec<u32> {
+        self.shapes.clone()
+    }
+}
+
+impl From<&EditorEvent> for CanvasEvent {
+    fn from(event: &EditorEvent) -> Self {
+        let (shapes, position) = match event {
+            EditorEvent::Click { shape, position } => (vec![*shape], Some(*position)),
+            EditorEvent::DragStart { shapes, position }
+            | EditorEvent::DragEnd { shapes, position } => (shapes.clone(), Some(*position)),
+            EditorEvent::Select { shapes } => (shapes.clone(), None),
+            EditorEvent::Change => (Vec::new(), None),
+        };
+        let position = position.unwrap_or(Point::ORIGIN);
+        Self {
+            name: event.name(),
+            shapes: shapes.into_iter().map(|id| id as u32).collect(),
+            x: position.x,
+            y: position.y,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl ShapeHandle {
+    /// Tween `property` (e.g. "x", "rotation", "width", "radius") to `value`
+    /// over `duration_ms`. `easing` takes CSS-style names such as "ease-out",
+    /// "elastic" or "cubic-bezier(0.2, 0, 0, 1)". Returns an animation id for
+    /// `VelloContext::stop_animation`.
+    pub fn animate_to(
+        &self,
+        property: &str,
+        value: f64,
+        duration_ms: f64,
+        easing: &str,
+    ) -> Result<u32, JsValue> {
+        let easing = Easing::parse(easing)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown easing: {}", easing)))?;
+        self.play(property, |signal| {
+            Animation::tween(signal, value, duration_ms / 1000.0, easing)
+        })
+    }
+
+    /// Play keyframes on `property`: `times_ms[i]` maps to `values[i]`, each
+    /// segment eased with `easing`. `repeat` is "once", "loop", "ping-pong",
+    /// optionally with a count such as "loop:3".
+    pub fn animate_keyframes(
+        &self,
+        property: &str,
+        times_ms: Vec<f64>,
+        values: Vec<f64>,
+        easing: &str,
+        repeat: &str,
+    ) -> Result<u32, JsValue> {
+        if times_ms.len() != values.len() || times_ms.is_empty() {
+            return Err(JsValue::from_str(
+                "times and values must be non-empty and the same length",
+            ));
+        }
+        let easing = Easing::parse(easing)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown easing: {}", easing)))?;
+        let repeat = Repeat::parse(repeat)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown repeat mode: {}", repeat)))?;
+        self.play(property, |signal| {
+            let keyframes = times_ms
+                .iter()
+                .zip(&values)
+                .map(|(time, value)| Keyframe {
+                    time: time / 1000.0,
+                    value: *value,
+                    easing,
+                })
+                .collect();
+            Animation::new(vec![Track::new(signal, keyframes)]).with_repeat(repeat)
+        })
+    }
+
+    /// Schedule keyframes on `property` in the context's timeline, starting
+    /// `start_ms` into it. Unlike `animate_keyframes` this only plays when
+    /// the timeline does, and can be seeked and exported.
+    pub fn timeline_keyframes(
+        &self,
+        property: &str,
+        times_ms: Vec<f64>,
+        values: Vec<f64>,
+        easing: &str,
+        repeat: &str,
+        start_ms: f64,
+    ) -> Result<(), JsValue> {
+        if times_ms.len() != values.len() || times_ms.is_empty() {
+            return Err(JsValue::from_str(
+                "times and values must be non-empty and the same length",
+            ));
+        }
+        let easing = Easing::parse(easing)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown easing: {}", easing)))?;
+        let repeat = Repeat::parse(repeat)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown repeat mode: {}", repeat)))?;
+        let context = self
+            .context
+            .upgrade()
+            .ok_or_else(|| JsValue::from_str("Context no longer exists"))?;
+        let mut context = Editing::new(&context);
+        let signal = context
+            .editor
+            .shape(self.id)
+            .ok_or_else(|| JsValue::from_str("Shape not found"))?
+            .signal(property)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown property: {}", property)))?;
+        let keyframes = times_ms
+            .iter()
+            .zip(&values)
+            .map(|(time, value)| Keyframe {
+                time: time / 1000.0,
+                value: *value,
+                easing,
+            })
+            .collect();
+        let animation = Animation::new(vec![Track::new(signal, keyframes)]).with_repeat(repeat);
+        context
+            .editor
+            .timeline_mut()
+            .add(animation, start_ms / 1000.0);
+        Ok(())
+    }
+
+    fn play(
+        &self,
+        property: &str,
+        build: impl FnOnce(crate::signal::IrSignal) -> Animation,
+    ) -> Result<u32, JsValue> {
+        let context = self
+            .context
+            .upgrade()
+            .ok_or_else(|| JsValue::from_str("Context no longer exists"))?;
+        let id = {
+            let mut inner = Editing::new(&context);
+            let signal = inner
+                .editor
+                .shape(self.id)
+                .ok_or_else(|| JsValue::from_str("Shape not found"))?
+                .signal(property)
+                .ok_or_else(|| JsValue::from_str(&format!("Unknown property: {}", property)))?;
+            inner.editor.animate(build(signal))
+        };
+        request_frame(&context);
+        Ok(id)
+    }
+
+    pub fn follow(&self, other: &ShapeHandle) -> Result<(), JsValue> {
+        if let Some(context) = self.context.upgrade() {
+            let mut context = Editing::new(&context);
+            if context.editor.follow(self.id, other.id) {
+                context.render();
+                Ok(())
+            } else {
+                Err(JsValue::from_str("Shape not found"))
+            }
+        } else {
+            Err(JsValue::from_str("Context no longer exists"))
+        }
+    }
+
+    /// Follow `other` with spring dynamics, lagging behind as it moves.
+    pub fn follow_spring(
+        &self,
+        other: &ShapeHandle,
+        stiffness: f64,
+        damping: f64,
+        mass: f64,
+    ) -> Result<(), JsValue> {
+        if let Some(context) = self.context.upgrade() {
+            let mut context = Editing::new(&context);
+            let config = SpringConfig {
+                stiffness,
+                damping,
+                mass,
+            };
+            if context.editor.follow_with_spring(self.id, other.id, config) {
+                context.render();
+                Ok(())
+            } else {
+                Err(JsValue::from_str("Shape not found"))
+            }
+        } else {
+            Err(JsValue::from_str("Context no longer exists"))
+        }
+    }
+
+    /// Pull `property` towards `value` on a spring.
+    pub fn spring_to(
+        &self,
+        property: &str,
+        value: f64,
+        stiffness: f64,
+        damping: f64,
+        mass: f64,
+    ) -> Result<(), JsValue> {
+        let context = self
+            .context
+            .upgrade()
+            .ok_or_else(|| JsValue::from_str("Context no longer exists"))?;
+        let config = SpringConfig {
+            stiffness,
+            damping,
+            mass,
+        };
+        if !Editing::new(&context)
+            .editor
+            .spring_to(self.id, property, value, config)
+        {
+            return Err(JsValue::from_str(&format!(
+                "Shape not found or unknown property: {}",
+                property
+            )));
+        }
+        request_frame(&context);
+        Ok(())
+    }
+
+    /// Set `property` right away, as one undo step. Groups also have an
+    /// "opacity" property.
+    pub fn set_property(&self, property: &str, value: f64) -> Result<(), JsValue> {
+        self.update(
+            &format!("Shape not found or unknown property: {}", property),
+            |editor, id| editor.set_property(id, property, value),
+        )
+    }
+
+    /// Blend a group onto what is below it with a CSS `mix-blend-mode` such
+    /// as "multiply", "screen" or "overlay".
+    pub fn set_blend_mode(&self, mode: &str) -> Result<(), JsValue> {
+        let blend = shapes::parse_blend_mode(mode)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown blend mode: {}", mode)))?;
+        self.update("Not a group", |editor, id| editor.set_blend_mode(id, blend))
+    }
+
+    /// Show this path's anchors and handles for node editing.
+    pub fn edit_points(&self) -> Result<(), JsValue> {
+        self.update("Not a path", |editor, id| editor.enter_path_editing(id))
+    }
+
+    /// Clip the rest of this group with `mask`, one of its children.
+    pub fn set_mask(&self, mask: &ShapeHandle) -> Result<(), JsValue> {
+        self.update("Mask must be a child of this group", |editor, id| {
+            editor.set_mask(id, Some(mask.id))
+        })
+    }
+
+    pub fn clear_mask(&self) -> Result<(), JsValue> {
+        self.update("Not a group", |editor, id| editor.set_mask(id, None))
+    }
+
+    /// "straight", "curved" or "orthogonal".
+    pub fn set_routing(&self, routing: &str) -> Result<(), JsValue> {
+        let routing = Routing::parse(routing)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown routing: {}", routing)))?;
+        self.update("Not a connector", |editor, id| {
+            editor.update_connector(id, |connector| connector.routing = routing)
+        })
+    }
+
+    /// Each of "none", "triangle", "open" or "circle".
+    pub fn set_arrowheads(&self, start: &str, end: &str) -> Result<(), JsValue> {
+        let parse = |name: &str| {
+            Arrowhead::parse(name)
+                .ok_or_else(|| JsValue::from_str(&format!("Unknown arrowhead: {}", name)))
+        };
+        let (start, end) = (parse(start)?, parse(end)?);
+        self.update("Not a connector", |editor, id| {
+            editor.update_connector(id, |connector| {
+                connector.start_arrow = start;
+                connector.end_arrow = end;
+            })
+        })
+    }
+
+    /// Pin the "source" or "target" end of a connector to a point on that
+    /// shape's bounds, as fractions of its width and height: (1, 0.5) is
+    /// the middle of the right edge.
+    pub fn set_port(&self, end: &str, fx: f64, fy: f64) -> Result<(), JsValue> {
+        self.set_attachment(end, Attachment::Port(fx, fy))
+    }
+
+    /// Let the "source" or "target" end of a connector slide around the
+    /// shape's border again.
+    pub fn attach_to_border(&self, end: &str) -> Result<(), JsValue> {
+        self.set_attachment(end, Attachment::Border)
+    }
+
+    fn set_attachment(&self, end: &str, attachment: Attachment) -> Result<(), JsValue> {
+        let source = match end {
+            "source" => true,
+            "target" => false,
+            _ => {
+                return Err(JsValue::from_str(&format!(
+                    "Unknown connector end: {}",
+                    end
+                )))
+            }
+        };
+        self.update("Not a connector", |editor, id| {
+            editor.update_connector(id, |connector| {
+                let endpoint = if source {
+                    &mut connector.source
+                } else {
+                    &mut connector.target
+                };
+                endpoint.attachment = attachment;
+            })
+        })
+    }
+
+    /// Line width and color of a connector and its arrowheads.
+    pub fn set_stroke(&self, width: f64, r: u8, g: u8, b: u8, a: u8) -> Result<(), JsValue> {
+        self.update("Not a connector", |editor, id| {
+            editor.update_connector(id, |connector| {
+                connector.width = width;
+                connector.color = Color::from_rgba8(r, g, b, a);
+            })
+        })
+    }
+
+    /// Label a connector halfway along, in the font from `set_font` and the
+    /// brush color. An empty `text` removes the label.
+    pub fn set_label(&self, text: &str) -> Result<(), JsValue> {
+        self.update("Not a connector, or no font set", |editor, id| {
+            editor.set_connector_label(id, text)
+        })
+    }
+
+    // Run an edit against this shape and redraw, or fail with `refused` if
+    // the editor turned it down.
+    fn update(
+        &self,
+        refused: &str,
+        edit: impl FnOnce(&mut Editor, usize) -> bool,
+    ) -> Result<(), JsValue> {
+        let context = self
+            .context
+            .upgrade()
+            .ok_or_else(|| JsValue::from_str("Context no longer exists"))?;
+        let mut context = Editing::new(&context);
+        if !edit(&mut context.editor, self.id) {
+            return Err(JsValue::from_str(refused));
+        }
+        context.render();
+        Ok(())
+    }
+
+    /// Identifies the shape in `CanvasEvent::shapes`.
+    #[wasm_bindgen(getter)]
+    pub fn id(&self) -> u32 {
+        self.id as u32
+    }
+
+    /// Call `listener` with a `CanvasEvent` whenever this shape is clicked
+    /// without being dragged.
+    pub fn on_click(&self, listener: js_sys::Function) -> Result<(), JsValue> {
+        let context = self
+            .context
+            .upgrade()
+            .ok_or_else(|| JsValue::from_str("Context no longer exists"))?;
+        let mut context = context.borrow_mut();
+        context
+            .click_listeners
+            .entry(self.id)
+            .or_default()
+            .push(listener);
+        Ok(())
+    }
+
+    /// Drop every `on_click` listener of this shape.
+    pub fn off_click(&self) {
+        if let Some(context) = self.context.upgrade() {
+            context.borrow_mut().click_listeners.remove(&self.id);
+        }
+    }
+
+    /// Bounding box in world coordinates.
+    pub fn get_bounds(&self) -> Result<Bounds, JsValue> {
+        self.query(|editor, id| editor.world_bounds(id).map(Bounds::from))
+    }
+
+    /// Bounding box in the coordinates of the group holding the shape, the
+    /// same as `get_bounds` for top-level shapes.
+    pub fn get_local_bounds(&self) -> Result<Bounds, JsValue> {
+        self.query(|editor, id| editor.shape(id).map(|shape| shape.bounds().into()))
+    }
+
+    /// How far a world point is from the shape's outline, inside or out.
+    pub fn distance_to(&self, x: f64, y: f64) -> Result<f64, JsValue> {
+        self.query(|editor, id| editor.distance_to_outline(id, Point::new(x, y)))
+    }
+
+    // Ask the editor about this shape, failing if it is gone.
+    fn query<T>(&self, ask: impl FnOnce(&Editor, usize) -> Option<T>) -> Result<T, JsValue> {
+        let context = self
+            .context
+            .upgrade()
+            .ok_or_else(|| JsValue::from_str("Context no longer exists"))?;
+        let context = context.borrow();
+        ask(&context.editor, self.id).ok_or_else(|| JsValue::from_str("Shape not found"))
+    }
+
+    pub fn unfollow(&self) -> Result<(), JsValue> {
+        if let Some(context) = self.context.upgrade() {
+            let mut context = Editing::new(&context);
+            if context.editor.unfollow(self.id) {
+                context.render();
+                Ok(())
+            } else {
+                Err(JsValue::from_str("Shape not found"))
+            }
+        } else {
+            Err(JsValue::from_str("Context no longer exists"))
+        }
+    }
+}
+
+// JS-facing methods take flat arguments rather than option objects
+#[allow(clippy::too_many_arguments)]
+#[wasm_bindgen]
+impl VelloContext {
+    #[wasm_bindgen]
+    pub async fn create(
+        canvas_id: &str,
+        options: Option<VelloContextOptions>,
+    ) -> Result<VelloContext, JsValue> {
+        let options = options.unwrap_or_default();
+        let window = web_sys::window().unwrap();
+        let document = window.document().unwrap();
+        let canvas = document
+            .get_element_by_id(canvas_id)
+            .unwrap()
+            .dyn_into::<HtmlCanvasElement>()
+            .unwrap();
+
+        let width = canvas.width();
+        let height = canvas.height();
+
+        // vello's `RenderContext` always asks for the default adapter, so the
+        // surface and device are set up by hand to honour the power preference
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let surface = instance
+            .create_surface(wgpu::SurfaceTarget::Canvas(canvas.clone()))
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        let (adapter, device, queue) = render::request_device(
+            &instance,
+            options.power_preference.into(),
+            false,
+            Some(&surface),
+        )
+        .await
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+        let capabilities = surface.get_capabilities(&adapter);
+        let format = capabilities
+            .formats
+            .into_iter()
+            .find(|format| {
+                matches!(
+                    format,
+                    wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Bgra8Unorm
+                )
+            })
+            .ok_or_else(|| JsValue::from_str("Surface doesn't support an 8-bit RGBA format"))?;
+        // premultiplied lets a transparent background show the page through
+        let alpha_mode = if capabilities
+            .alpha_modes
+            .contains(&wgpu::CompositeAlphaMode::PreMultiplied)
+        {
+            wgpu::CompositeAlphaMode::PreMultiplied
+        } else {
+            wgpu::CompositeAlphaMode::Auto
+        };
+
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width,
+            height,
+            present_mode: options.present_mode.into(),
+            desired_maximum_frame_latency: 2,
+            alpha_mode,
+            view_formats: vec![],
+        };
+        surface.configure(&device, &config);
+
+        let render_state = RenderState {
+            surface,
+            config,
+            window,
+        };
+        // hmm interesting. I move stuff into the struct, and then I
+        // can't access the stuff outside of the struct anymore.
+
+        let renderer = Renderer::new(
+            &device,
+            RendererOptions {
+                surface_format: Some(render_state.config.format),
+                use_cpu: false,
+                antialiasing_support: AaSupport::all(),
+                // vello compiles shaders on the calling thread on wasm
+                // whatever this says
+                num_init_threads: NonZeroUsize::new(1),
+            },
+        )
+        .expect("Failed to create renderer");
+
+        console_log!("renderer created");
+
+        let inner = Rc::new(RefCell::new(CanvasContext {
+            editor: Editor::new(),
+            canvas,
+            device,
+            queue,
+            state: render_state,
+            renderer,
+            background: options.background,
+            antialiasing: options.antialiasing.into(),
+            frame_callback: None,
+            frame_requested: false,
+            recording: None,
+            listeners: Vec::new(),
+            click_listeners: HashMap::new(),
+        }));
+
+        Ok(VelloContext { inner })
+    }
+
+    /// Switch antialiasing at runtime, e.g. `Area` while dragging and
+    /// `Msaa16` once the pointer is released.
+    pub fn set_antialiasing(&self, method: Antialiasing) {
+        let mut context = self.inner.borrow_mut();
+        context.antialiasing = method.into();
+        context.render();
+    }
+
+    pub fn set_background(&self, r: u8, g: u8, b: u8, a: u8) {
+        let mut context = self.inner.borrow_mut();
+        context.background = Color::from_rgba8(r, g, b, a);
+        context.render();
+    }
+
+    pub fn set_transparent_background(&self) {
+        let mut context = self.inner.borrow_mut();
+        context.background = Color::TRANSPARENT;
+        context.render();
+    }
+
+    pub fn set_present_mode(&self, mode: PresentMode) {
+        let mut context = self.inner.borrow_mut();
+        context.state.config.present_mode = mode.into();
+        context.configure_surface();
+        context.render();
+    }
+
+    pub fn add_rectangle(
+        &self,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        r: u8,
+        g: u8,
+        b: u8,
+        a: u8,
+    ) -> ShapeHandle {
+        let mut context = self.edit();
+        let id = context.editor.add_shape(Box::new(IrRectangle::new(
+            x,
+            y,
+            width,
+            height,
+            Color::from_rgba8(r, g, b, a),
+        )));
+
+        context.render();
+
+        self.handle(id)
+    }
+
+    pub fn add_circle(
+        &self,
+        x: f64,
+        y: f64,
+        radius: f64,
+        r: u8,
+        g: u8,
+        b: u8,
+        a: u8,
+    ) -> ShapeHandle {
+        let mut context = self.edit();
+        let id = context.editor.add_shape(Box::new(IrCircle::new(
+            x,
+            y,
+            radius,
+            Color::from_rgba8(r, g, b, a),
+        )));
+
+        context.render();
+
+        self.handle(id)
+    }
+
+    /// Add a shape from SVG path data (the `d` attribute), placed at the
+    /// top-left of its bounds.
+    pub fn add_path(
+        &self,
+        svg_path: &str,
+        even_odd: bool,
+        r: u8,
+        g: u8,
+        b: u8,
+        a: u8,
+    ) -> Result<ShapeHandle, JsValue> {
+        let path = BezPath::from_svg(svg_path)
+            .map_err(|e| JsValue::from_str(&format!("Invalid path data: {}", e)))?;
+        let fill = if even_odd {
+            Fill::EvenOdd
+        } else {
+            Fill::NonZero
+        };
+
+        let mut context = self.edit();
+        let id = context.editor.add_shape(Box::new(IrPath::from_outline(
+            path,
+            fill,
+            Color::from_rgba8(r, g, b, a),
+        )));
+        context.render();
+        Ok(self.handle(id))
+    }
+
+    /// Link two shapes with a connector that follows them around. `routing`
+    /// is "straight", "curved" or "orthogonal"; both ends attach wherever
+    /// the connector crosses the shapes' bounds until given a port.
+    pub fn add_connector(
+        &self,
+        source: &ShapeHandle,
+        target: &ShapeHandle,
+        routing: &str,
+    ) -> Result<ShapeHandle, JsValue> {
+        let routing = Routing::parse(routing)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown routing: {}", routing)))?;
+        let connector = IrConnector::new(
+            Endpoint::border(source.id),
+            Endpoint::border(target.id),
+            routing,
+        );
+        let mut context = self.edit();
+        let id = context
+            .editor
+            .connect(connector)
+            .ok_or_else(|| JsValue::from_str("Shape not found"))?;
+        context.render();
+        Ok(self.handle(id))
+    }
+
+    /// Unified entry point for DOM pointer events. `event_type` and
+    /// `pointer_type` are passed straight from the event (`e.type`,
+    /// `e.pointerType`), `x`/`y` are canvas pixels and `modifiers` is a
+    /// bitmask of 1 = shift, 2 = ctrl, 4 = alt, 8 = meta. `timestamp` is
+    /// `e.timeStamp` in milliseconds.
+    pub fn handle_pointer_event(
+        &self,
+        event_type: &str,
+        pointer_id: i32,
+        pointer_type: &str,
+        x: f64,
+        y: f64,
+        buttons: u16,
+        modifiers: u8,
+        pressure: f64,
+        timestamp: f64,
+    ) {
+        let phase = match PointerPhase::parse(event_type) {
+            Some(phase) => phase,
+            None => return,
+        };
+        let event = PointerEvent {
+            pointer_id,
+            pointer_type: PointerType::parse(pointer_type),
+            phase,
+            x,
+            y,
+            buttons,
+            modifiers: Modifiers::from_bits(modifiers),
+            pressure,
+            time: timestamp / 1000.0,
+        };
+
+        let animating = {
+            let mut context = self.edit();
+            if context.editor.handle_pointer_event(&event) {
+                context.render();
+            }
+            context.editor.is_animating()
+        };
+        // spring followers and thrown shapes keep moving after the event
+        if animating {
+            request_frame(&self.inner);
+        }
+    }
+
+    /// Call `listener` with a `CanvasEvent` on every `event`: "click",
+    /// "drag_start", "drag_end", "select" or "change" (an edit, undo or
+    /// redo). Events are delivered once the pointer, mouse, key,
+    /// double-click, undo or redo call causing them has finished, so
+    /// listeners may call back into the context.
+    pub fn on(&self, event: &str, listener: js_sys::Function) -> Result<(), JsValue> {
+        if !EVENT_NAMES.contains(&event) {
+            return Err(JsValue::from_str(&format!("Unknown event: {}", event)));
+        }
+        self.inner
+            .borrow_mut()
+            .listeners
+            .push((event.to_string(), listener));
+        Ok(())
+    }
+
+    /// Drop every listener of `event`.
+    pub fn off(&self, event: &str) {
+        self.inner
+            .borrow_mut()
+            .listeners
+            .retain(|(name, _)| name != event);
+    }
+
+    /// Forward a DOM `keydown`. `key` is `KeyboardEvent.key` and `modifiers`
+    /// uses the same bits as `handle_pointer_event`. Returns the name of the
+    /// action that ran, so the page can `preventDefault()` and react to its
+    /// own custom actions, or `undefined` if the key isn't bound.
+    pub fn handle_key_down(&self, key: &str, modifiers: u8) -> Option<String> {
+        let outcome = {
+            let mut context = self.edit();
+            let outcome = context
+                .editor
+                .handle_key_down(key, Modifiers::from_bits(modifiers))?;
+            if outcome.changed {
+                context.render();
+            }
+            outcome
+        };
+        Some(outcome.action.name())
+    }
+
+    pub fn handle_key_up(&self, key: &str) {
+        self.edit().editor.handle_key_up(key);
+    }
+
+    /// Bind a chord such as `"ctrl+shift+z"` to an action: one of `delete`,
+    /// `deselect`, `select_all`, `undo`, `redo`, `duplicate`, `group`,
+    /// `ungroup`, `nudge:dx,dy`, `tool:name`,
+    /// or any other name, which `handle_key_down` hands back to the page.
+    /// `tool_input` is what it reports for keys a tool used, so it can't be
+    /// bound.
+    pub fn bind_key(&self, chord: &str, action: &str) -> Result<(), JsValue> {
+        let chord = KeyChord::parse(chord)
+            .ok_or_else(|| JsValue::from_str(&format!("Invalid key chord: {}", chord)))?;
+        let action = Action::parse(action)
+            .ok_or_else(|| JsValue::from_str(&format!("Can't bind to {}", action)))?;
+        self.edit().editor.keymap_mut().bind(chord, action);
+        Ok(())
+    }
+
+    pub fn unbind_key(&self, chord: &str) -> Result<(), JsValue> {
+        let chord = KeyChord::parse(chord)
+            .ok_or_else(|| JsValue::from_str(&format!("Invalid key chord: {}", chord)))?;
+        self.edit().editor.keymap_mut().unbind(&chord);
+        Ok(())
+    }
+
+    pub fn undo(&self) -> bool {
+        let changed = {
+            let mut context = self.edit();
+            let changed = context.editor.undo();
+            if changed {
+                context.render();
+            }
+            changed
+        };
+        changed
+    }
+
+    pub fn redo(&self) -> bool {
+        let changed = {
+            let mut context = self.edit();
+            let changed = context.editor.redo();
+            if changed {
+                context.render();
+            }
+            changed
+        };
+        changed
+    }
+
+    /// Group the selected shapes; `undefined` if nothing is selected.
+    pub fn group_selection(&self) -> Option<ShapeHandle> {
+        let mut context = self.edit();
+        let group = context.editor.group_selection()?;
+        context.render();
+        Some(self.handle(group))
+    }
+
+    /// Group the selection and use its bottom-most shape as a clip mask.
+    pub fn mask_selection(&self) -> Option<ShapeHandle> {
+        let mut context = self.edit();
+        let group = context.editor.mask_selection()?;
+        context.render();
+        Some(self.handle(group))
+    }
+
+    /// Group the selection into a stack along `axis` ("horizontal" or
+    /// "vertical"). `justify` is "start", "center", "end" or
+    /// "space-between"; `align` places children across their line, "start",
+    /// "center" or "end". Set the handle's "width" or "height" to resize it.
+    pub fn stack_selection(
+        &self,
+        axis: &str,
+        gap: f64,
+        padding: f64,
+        justify: &str,
+        align: &str,
+        wrap: bool,
+    ) -> Result<Option<ShapeHandle>, JsValue> {
+        let axis = Axis::parse(axis)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown axis: {}", axis)))?;
+        let justify = Justify::parse(justify)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown justify: {}", justify)))?;
+        let align = Align::parse(align)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown alignment: {}", align)))?;
+        let layout = Layout::Stack(Stack {
+            axis,
+            gap,
+            padding,
+            justify,
+            align,
+            wrap,
+        });
+        self.contain_selection(layout)
+    }
+
+    /// Group the selection into a grid of `columns` equal columns, children
+    /// placed in their cells by `align` ("start", "center" or "end").
+    pub fn grid_selection(
+        &self,
+        columns: usize,
+        gap: f64,
+        padding: f64,
+        align: &str,
+    ) -> Result<Option<ShapeHandle>, JsValue> {
+        let align = Align::parse(align)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown alignment: {}", align)))?;
+        let layout = Layout::Grid(Grid {
+            columns,
+            gap,
+            padding,
+            align,
+        });
+        self.contain_selection(layout)
+    }
+
+    /// Replace the selected shapes with their "union", "intersect",
+    /// "subtract" or "xor". Returns `undefined` if fewer than two shapes
+    /// are selected or nothing is left.
+    pub fn combine_selection(&self, op: &str) -> Result<Option<ShapeHandle>, JsValue> {
+        let op = BooleanOp::parse(op)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown boolean operation: {}", op)))?;
+        let mut context = self.edit();
+        let selection = context.editor.selection().to_vec();
+        let result = match context.editor.combine(&selection, op) {
+            Some(result) => result,
+            None => return Ok(None),
+        };
+        context.render();
+        Ok(Some(self.handle(result)))
+    }
+
+    /// Line up the selected shapes' "left", "center" or "right" edges, or
+    /// their "top", "middle" or "bottom", with the outermost of them.
+    pub fn align_selection(&self, alignment: &str) -> Result<bool, JsValue> {
+        let alignment = Alignment::parse(alignment)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown alignment: {}", alignment)))?;
+        let mut context = self.edit();
+        let selection = context.editor.selection().to_vec();
+        let changed = context.editor.align(&selection, alignment);
+        if changed {
+            context.render();
+        }
+        Ok(changed)
+    }
+
+    /// Space the selected shapes out along `axis` ("horizontal" or
+    /// "vertical"), `spacing` apart, or evenly between the outermost two if
+    /// `spacing` is undefined.
+    pub fn distribute_selection(&self, axis: &str, spacing: Option<f64>) -> Result<bool, JsValue> {
+        let axis = Axis::parse(axis)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown axis: {}", axis)))?;
+        let mut context = self.edit();
+        let selection = context.editor.selection().to_vec();
+        let changed = context.editor.distribute(&selection, axis, spacing);
+        if changed {
+            context.render();
+        }
+        Ok(changed)
+    }
+
+    /// Arrange the selected shapes, or every shape if none are selected, as
+    /// a graph of the connectors between them. `algorithm` is "layered",
+    /// "force", "tree" or "radial"; gaps are in world units. A positive
+    /// `duration_ms` animates the move with `easing`.
+    pub fn layout_graph(
+        &self,
+        algorithm: &str,
+        node_gap: f64,
+        layer_gap: f64,
+        duration_ms: f64,
+        easing: &str,
+    ) -> Result<bool, JsValue> {
+        let algorithm = LayoutAlgorithm::parse(algorithm)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown layout: {}", algorithm)))?;
+        let easing = Easing::parse(easing)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown easing: {}", easing)))?;
+        let layout = GraphLayout {
+            algorithm,
+            node_gap,
+            layer_gap,
+        };
+        let transition = (duration_ms > 0.0).then(|| (duration_ms / 1000.0, easing));
+        let changed = {
+            let mut context = self.edit();
+            let mut ids = context.editor.selection().to_vec();
+            if ids.is_empty() {
+                ids = context.editor.shape_ids().collect();
+            }
+            let changed = context.editor.layout_graph(&ids, &layout, transition);
+            if changed {
+                context.render();
+            }
+            changed
+        };
+        if changed && transition.is_some() {
+            request_frame(&self.inner);
+        }
+        Ok(changed)
+    }
+
+    /// Constrain the selected shapes, in selection order, along `axis`
+    /// ("horizontal" or "vertical"). `kind` is "align-start" (left or top
+    /// edges), "align-center", "equal-size", "equal-spacing",
+    /// "fixed-distance" (each shape `value` after the one before) or
+    /// "keep-inside" (the rest within the first, `value` from its edges).
+    /// The constraint holds from then on, whichever shape moves. Returns an
+    /// id for `remove_constraint`.
+    pub fn add_constraint(&self, kind: &str, axis: &str, value: f64) -> Result<u32, JsValue> {
+        let axis = Axis::parse(axis)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown axis: {}", axis)))?;
+        let mut context = self.edit();
+        let extents: Vec<Extent> = context
+            .editor
+            .selection()
+            .iter()
+            .filter_map(|id| context.editor.extent(*id, axis))
+            .collect();
+        if extents.len() < 2 {
+            return Err(JsValue::from_str("Select at least two shapes"));
+        }
+        let constraint = match kind {
+            "align-start" => Constraint::align_start(&extents),
+            "align-center" => Constraint::align_center(&extents),
+            "equal-size" => Constraint::equal_size(&extents),
+            "equal-spacing" => Constraint::equal_spacing(&extents),
+            "fixed-distance" => {
+                let parts = extents
+                    .windows(2)
+                    .map(|pair| Constraint::fixed_distance(&pair[0], &pair[1], value));
+                Constraint::all(parts)
+            }
+            "keep-inside" => {
+                let parts = extents[1..]
+                    .iter()
+                    .map(|child| Constraint::keep_inside(child, &extents[0], value));
+                Constraint::all(parts)
+            }
+            _ => return Err(JsValue::from_str(&format!("Unknown constraint: {}", kind))),
+        };
+        let id = context
+            .editor
+            .add_constraint(constraint)
+            .ok_or_else(|| JsValue::from_str("Conflicts with an existing constraint"))?;
+        context.render();
+        Ok(id)
+    }
+
+    pub fn remove_constraint(&self, id: u32) -> bool {
+        let mut context = self.edit();
+        if !context.editor.remove_constraint(id) {
+            return false;
+        }
+        context.render();
+        true
+    }
+
+    /// Shapes lying wholly inside a world-space rectangle: the entered
+    /// group's children, or top-level shapes.
+    pub fn shapes_in_rect(&self, x: f64, y: f64, width: f64, height: f64) -> js_sys::Array {
+        let rect = Rect::new(x, y, x + width, y + height);
+        let ids = self.inner.borrow().editor.shapes_in_rect(rect);
+        self.handles(ids)
+    }
+
+    /// Shapes touched by SVG path data in world coordinates, e.g. a lasso.
+    /// Open paths are closed as if filled.
+    pub fn shapes_intersecting_path(&self, svg_path: &str) -> Result<js_sys::Array, JsValue> {
+        let path = BezPath::from_svg(svg_path)
+            .map_err(|e| JsValue::from_str(&format!("Invalid path data: {}", e)))?;
+        let ids = self.inner.borrow().editor.shapes_intersecting_path(&path);
+        Ok(self.handles(ids))
+    }
+
+    /// The shape closest to a world point, within `max_distance`; shapes
+    /// containing the point come first.
+    pub fn nearest_shape(&self, x: f64, y: f64, max_distance: f64) -> Option<ShapeHandle> {
+        let nearest = self
+            .inner
+            .borrow()
+            .editor
+            .nearest_shape(Point::new(x, y), max_distance);
+        nearest.map(|(id, _)| self.handle(id))
+    }
+
+    /// Dissolve the selected groups and return handles to their children.
+    pub fn ungroup(&self) -> js_sys::Array {
+        let mut context = self.edit();
+        let children = context.editor.ungroup();
+        if !children.is_empty() {
+            context.render();
+        }
+        self.handles(children)
+    }
+
+    /// Forward `dblclick` here to enter the group under the pointer.
+    pub fn handle_double_click(&self, x: f64, y: f64) -> bool {
+        let entered = {
+            let mut context = self.edit();
+            let entered = context.editor.handle_double_click(x, y);
+            if entered {
+                context.render();
+            }
+            entered
+        };
+        entered
+    }
+
+    pub fn exit_group(&self) -> bool {
+        let mut context = self.edit();
+        let exited = context.editor.exit_group();
+        if exited {
+            context.render();
+        }
+        exited
+    }
+
+    pub fn exit_path_editing(&self) -> bool {
+        let mut context = self.edit();
+        let exited = context.editor.exit_path_editing();
+        if exited {
+            context.render();
+        }
+        exited
+    }
+
+    /// Insert a point on the edited path's outline under a canvas point.
+    pub fn insert_path_point(&self, x: f64, y: f64) -> bool {
+        let mut context = self.edit();
+        let inserted = context.editor.insert_path_point(x, y);
+        if inserted {
+            context.render();
+        }
+        inserted
+    }
+
+    pub fn delete_path_point(&self) -> bool {
+        let mut context = self.edit();
+        let deleted = context.editor.delete_path_point();
+        if deleted {
+            context.render();
+        }
+        deleted
+    }
+
+    /// Make the selected point of the edited path a "corner", "smooth" or
+    /// "symmetric" point. Returns false if no point is selected.
+    pub fn set_path_point_kind(&self, kind: &str) -> Result<bool, JsValue> {
+        let kind = PointKind::parse(kind)
+            .ok_or_else(|| JsValue::from_str(&format!("Unknown point kind: {}", kind)))?;
+        let mut context = self.edit();
+        let changed = context.editor.set_path_point_kind(kind);
+        if changed {
+            context.render();
+        }
+        Ok(changed)
+    }
+
+    pub fn set_snapping(&self, enabled: bool) {
+        self.edit().editor.snapping_mut().enabled = enabled;
+    }
+
+    /// Grid spacing in world units; 0 turns grid snapping off. `visible`
+    /// draws the grid behind the shapes.
+    pub fn set_grid(&self, size: f64, visible: bool) {
+        let mut context = self.edit();
+        let snapping = context.editor.snapping_mut();
+        snapping.grid_size = if size > 0.0 { Some(size) } else { None };
+        snapping.show_grid = visible;
+        context.render();
+    }
+
+    pub fn set_snap_to_shapes(&self, enabled: bool) {
+        self.edit().editor.snapping_mut().snap_to_shapes = enabled;
+    }
+
+    /// Snap distance in canvas pixels.
+    pub fn set_snap_threshold(&self, pixels: f64) {
+        self.edit().editor.snapping_mut().threshold = pixels.max(0.0);
+    }
+
+    /// Which modifier suspends snapping while held: "alt", "ctrl", "shift"
+    /// or "meta".
+    pub fn set_snap_bypass_key(&self, key: &str) -> Result<(), JsValue> {
+        let bypass = match key {
+            "alt" => SnapBypass::Alt,
+            "ctrl" => SnapBypass::Ctrl,
+            "shift" => SnapBypass::Shift,
+            "meta" => SnapBypass::Meta,
+            _ => return Err(JsValue::from_str(&format!("Unknown modifier: {}", key))),
+        };
+        self.edit().editor.snapping_mut().bypass = bypass;
+        Ok(())
+    }
+
+    /// Add a guide line at `position` (world units); returns its index.
+    pub fn add_guide(&self, vertical: bool, position: f64) -> usize {
+        let mut context = self.edit();
+        let guides = &mut context.editor.snapping_mut().guides;
+        guides.push(if vertical {
+            Guide::Vertical(position)
+        } else {
+            Guide::Horizontal(position)
+        });
+        let index = guides.len() - 1;
+        context.render();
+        index
+    }
+
+    pub fn remove_guide(&self, index: usize) {
+        let mut context = self.edit();
+        let guides = &mut context.editor.snapping_mut().guides;
+        if index < guides.len() {
+            guides.remove(index);
+            context.render();
+        }
+    }
+
+    pub fn clear_guides(&self) {
+        let mut context = self.edit();
+        context.editor.snapping_mut().guides.clear();
+        context.render();
+    }
+
+    /// Switch to one of `select`, `rectangle`, `ellipse`, `line`, `pen`,
+    /// `hand`, `text`, or a tool registered from Rust.
+    pub fn set_tool(&self, name: &str) -> Result<(), JsValue> {
+        let mut context = self.edit();
+        if !context.editor.set_tool(name) {
+            return Err(JsValue::from_str(&format!("Unknown tool: {}", name)));
+        }
+        context.render();
+        Ok(())
+    }
+
+    pub fn active_tool(&self) -> String {
+        self.inner.borrow().editor.active_tool().to_string()
+    }
+
+    /// Font data (TrueType, OpenType or a collection) and size, in world
+    /// units, for the text tool.
+    pub fn set_font(&self, data: Vec<u8>, size: f64) -> Result<(), JsValue> {
+        let font = shapes::load_font(data).ok_or_else(|| JsValue::from_str("Invalid font data"))?;
+        self.edit().editor.set_text_style(font, size);
+        Ok(())
+    }
+
+    /// Width in world units at full pressure, and color, of new strokes,
+    /// lines and shapes.
+    pub fn set_brush(&self, width: f64, r: u8, g: u8, b: u8, a: u8) {
+        let mut context = self.edit();
+        let brush = context.editor.brush_mut();
+        brush.width = width;
+        brush.color = Color::from_rgba8(r, g, b, a);
+    }
+
+    /// From 0, following the pointer exactly, towards 1 for smoother but
+    /// lagging strokes.
+    pub fn set_brush_smoothing(&self, smoothing: f64) {
+        self.edit().editor.brush_mut().smoothing = smoothing.clamp(0.0, 1.0);
+    }
+
+    /// How far, in canvas pixels, finished strokes may stray from the
+    /// pointer's path in exchange for fewer curves.
+    pub fn set_brush_tolerance(&self, pixels: f64) {
+        self.edit().editor.brush_mut().tolerance = pixels.max(0.01);
+    }
+
+    /// Whether pen pressure varies the stroke width.
+    pub fn set_brush_pressure(&self, enabled: bool) {
+        self.edit().editor.brush_mut().pressure = enabled;
+    }
+
+    /// Let dragged shapes keep their momentum when released, slowing with
+    /// `friction` (per second; around 4-8 feels natural). 0 turns it off.
+    pub fn set_drag_momentum(&self, friction: f64) {
+        self.edit().editor.set_drag_momentum(Some(friction));
+    }
+
+    pub fn timeline_play(&self) {
+        self.edit().editor.timeline_mut().play();
+        request_frame(&self.inner);
+    }
+
+    pub fn timeline_pause(&self) {
+        self.edit().editor.timeline_mut().pause();
+    }
+
+    pub fn timeline_seek(&self, time_ms: f64) {
+        let mut context = self.edit();
+        context.editor.seek_timeline(time_ms / 1000.0);
+        context.render();
+    }
+
+    pub fn timeline_time(&self) -> f64 {
+        self.inner.borrow().editor.timeline().time() * 1000.0
+    }
+
+    /// Length in milliseconds, `undefined` if something loops forever.
+    pub fn timeline_duration(&self) -> Option<f64> {
+        self.inner
+            .borrow()
+            .editor
+            .timeline()
+            .duration()
+            .map(|d| d * 1000.0)
+    }
+
+    pub fn set_playback_rate(&self, rate: f64) {
+        self.edit().editor.timeline_mut().set_rate(rate);
+    }
+
+    /// Render every timeline frame at `fps` offscreen and resolve to an
+    /// array of RGBA `Uint8Array`s. `max_ms` bounds timelines that loop
+    /// forever. The playhead is restored afterwards.
+    pub fn export_frames(
+        &self,
+        fps: f64,
+        width: u32,
+        height: u32,
+        scale: f64,
+        max_ms: f64,
+    ) -> js_sys::Promise {
+        let inner = self.inner.clone();
+        wasm_bindgen_futures::future_to_promise(async move {
+            let (times, resume_at): (Vec<f64>, f64) = {
+                let mut context = inner.borrow_mut();
+                let timeline = context.editor.timeline_mut();
+                timeline.pause();
+                (
+                    timeline.frame_times(fps, max_ms / 1000.0).collect(),
+                    timeline.time(),
+                )
+            };
+
+            let frames = js_sys::Array::new();
+            for time in times {
+                let image = {
+                    let mut context = inner.borrow_mut();
+                    context.editor.seek_timeline(time);
+                    context.render_offscreen(width, height, scale, false)
+                };
+                let image = image.await.map_err(|e| JsValue::from_str(&e.to_string()))?;
+                frames.push(&js_sys::Uint8Array::from(&image.data[..]));
+            }
+
+            let mut context = inner.borrow_mut();
+            context.editor.seek_timeline(resume_at);
+            context.render();
+            Ok(frames.into())
+        })
+    }
+
+    pub fn stop_animation(&self, id: u32) -> bool {
+        self.edit().editor.stop_animation(id)
+    }
+
+    pub fn stop_all_animations(&self) {
+        self.edit().editor.animator_mut().stop_all();
+    }
+
+    pub fn handle_mouse_down(&self, x: f64, y: f64) {
+        self.edit().editor.handle_mouse_down(x, y);
+    }
+
+    pub fn handle_mouse_move(&self, x: f64, y: f64) {
+        {
+            let mut context = self.edit();
+            if context.editor.handle_mouse_move(x, y) {
+                context.render();
+            }
+        }
+    }
+
+    pub fn handle_mouse_up(&self) {
+        self.edit().editor.handle_mouse_up();
+    }
+
+    /// Render the scene offscreen and resolve to a `Uint8Array` of RGBA pixels
+    /// (`width * height * 4` bytes, row-major, not premultiplied).
+    pub fn render_to_image(
+        &self,
+        width: u32,
+        height: u32,
+        scale: f64,
+        transparent: bool,
+    ) -> js_sys::Promise {
+        let image = self
+            .inner
+            .borrow_mut()
+            .render_offscreen(width, height, scale, transparent);
+        wasm_bindgen_futures::future_to_promise(async move {
+            let image = image.await.map_err(|e| JsValue::from_str(&e.to_string()))?;
+            Ok(js_sys::Uint8Array::from(&image.data[..]).into())
+        })
+    }
+
+    /// Same as `render_to_image` but resolves to PNG-encoded bytes, ready to
+    /// be wrapped in a `Blob` for thumbnails or "download as image".
+    pub fn render_to_png(
+        &self,
+        width: u32,
+        height: u32,
+        scale: f64,
+        transparent: bool,
+    ) -> js_sys::Promise {
+        let image = self
+            .inner
+            .borrow_mut()
+            .render_offscreen(width, height, scale, transparent);
+        wasm_bindgen_futures::future_to_promise(async move {
+            let png = image
+                .await
+                .and_then(|image| image.to_png())
+                .map_err(|e| JsValue::from_str(&e.to_string()))?;
+            Ok(js_sys::Uint8Array::from(&png[..]).into())
+        })
+    }
+
+    /// Start encoding every rendered frame, at most `fps` per second, with
+    /// WebCodecs. `codec` is "vp8" or "vp9"; the canvas size is fixed for
+    /// the length of the recording.
+    pub fn start_recording(&self, fps: f64, bitrate: f64, codec: &str) -> Result<(), JsValue> {
+        let codec = Codec::parse(codec).ok_or_else(|| {
+            JsValue::from_str(&RecordingError::UnsupportedCodec(codec.to_string()).to_string())
+        })?;
+        let mut context = self.inner.borrow_mut();
+        if context.recording.is_some() {
+            return Err(JsValue::from_str("Already recording"));
+        }
+        let (width, height) = (context.canvas.width(), context.canvas.height());
+        let settings = RecordingSettings {
+            fps,
+            bitrate,
+            codec,
+        };
+        let encoder = WebCodecsEncoder::new(&settings, width, height)?;
+        context.recording = Some(Recorder::new(encoder, settings, width, height));
+        // capture the first frame right away rather than on the next change
+        context.render();
+        Ok(())
+    }
+
+    /// Stop recording and resolve to a `video/webm` `Blob`.
+    pub fn stop_recording(&self) -> js_sys::Promise {
+        let recording = self.inner.borrow_mut().recording.take();
+        wasm_bindgen_futures::future_to_promise(async move {
+            let recorder = recording.ok_or_else(|| JsValue::from_str("Not recording"))?;
+            let webm = recorder
+                .finish(js_sys::Date::now() / 1000.0)
+                .await
+                .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+            let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(&webm[..]));
+            let options = web_sys::BlobPropertyBag::new();
+            options.set_type("video/webm");
+            let blob = web_sys::Blob::new_with_u8_array_sequence_and_options(&parts, &options)?;
+            Ok(blob.into())
+        })
+    }
+
+    fn contain_selection(&self, layout: Layout) -> Result<Option<ShapeHandle>, JsValue> {
+        let mut context = self.edit();
+        let container = match context.editor.contain_selection(layout) {
+            Some(container) => container,
+            None => return Ok(None),
+        };
+        context.render();
+        Ok(Some(self.handle(container)))
+    }
+
+    fn handle(&self, id: usize) -> ShapeHandle {
+        ShapeHandle {
+            id,
+            context: Rc::downgrade(&self.inner),
+        }
+    }
+
+    fn handles(&self, ids: Vec<usize>) -> js_sys::Array {
+        ids.into_iter()
+            .map(|id| JsValue::from(self.handle(id)))
+            .collect()
+    }
+}
+
+// Schedule one animation frame unless one is already pending. Each frame
+// advances the editor's animations and schedules the next while any remain.
+fn request_frame(context: &Rc<RefCell<CanvasContext>>) {
+    let mut inner = context.borrow_mut();
+    if inner.frame_requested {
+        return;
+    }
+    let callback = inner
+        .frame_callback
+        .get_or_insert_with(|| {
+            let weak = Rc::downgrade(context);
+            Rc::new(Closure::wrap(Box::new(move |timestamp: f64| {
+                if let Some(context) = weak.upgrade() {
+                    let animating = {
+                        let mut inner = context.borrow_mut();
+                        inner.frame_requested = false;
+                        let animating = inner.editor.tick(timestamp / 1000.0);
+                        inner.render();
+                        animating
+                    };
+                    dispatch_events(&context);
+                    if animating {
+                        request_frame(&context);
+                    }
+                }
+            }) as Box<dyn FnMut(f64)>))
+        })
+        .clone();
+    inner.frame_requested = true;
+    let function: &JsValue = (*callback).as_ref();
+    inner
+        .state
+        .window
+        .request_animation_frame(function.unchecked_ref())
+        .expect("requestAnimationFrame failed");
+}
+
+// Hand the editor's new events to JS listeners. The context is released
+// first, so listeners can call back into it.
+fn dispatch_events(context: &Rc<RefCell<CanvasContext>>) {
+    let calls: Vec<(js_sys::Function, JsValue)> = {
+        let mut inner = context.borrow_mut();
+        let mut calls = Vec::new();
+        for event in inner.editor.take_events() {
+            let payload = JsValue::from(CanvasEvent::from(&event));
+            let clicked = match &event {
+                EditorEvent::Click { shape, .. } => inner.click_listeners.get(shape),
+                _ => None,
+            };
+            let listeners = inner
+                .listeners
+                .iter()
+                .filter(|(name, _)| name == event.name())
+                .map(|(_, listener)| listener)
+                .chain(clicked.into_iter().flatten());
+            calls.extend(listeners.map(|listener| (listener.clone(), payload.clone())));
+        }
+        calls
+    };
+    for (listener, payload) in calls {
+        if let Err(error) = listener.call1(&JsValue::NULL, &payload) {
+            console_log!("event listener failed: {:?}", error);
+        }
+    }
+}
+
+// Mutable access to the context for an edit made through the API. Events
+// the edit raised are dispatched once the context is released again.
+struct Editing<'a> {
+    context: &'a Rc<RefCell<CanvasContext>>,
+    inner: Option<RefMut<'a, CanvasContext>>,
+}
+
+impl<'a> Editing<'a> {
+    fn new(context: &'a Rc<RefCell<CanvasContext>>) -> Self {
+        Self {
+            context,
+            inner: Some(context.borrow_mut()),
+        }
+    }
+}
+
+impl Deref for Editing<'_> {
+    type Target = CanvasContext;
+
+    fn deref(&self) -> &CanvasContext {
+        self.inner.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for Editing<'_> {
+    fn deref_mut(&mut self) -> &mut CanvasContext {
+        self.inner.as_mut().unwrap()
+    }
+}
+
+impl Drop for Editing<'_> {
+    fn drop(&mut self) {
+        self.inner = None;
+        dispatch_events(self.context);
+    }
+}
+
+impl VelloContext {
+    fn edit(&self) -> Editing<'_> {
+        Editing::new(&self.inner)
+    }
+
+    /// Add a custom tool, selectable from JS by `name` with `set_tool`.
+    pub fn register_tool(&self, name: &str, tool: Box<dyn Tool>) {
+        self.inner.borrow_mut().editor.register_tool(name, tool);
+    }
+}
+
+impl CanvasContext {
+    fn configure_surface(&self) {
+        self.state
+            .surface
+            .configure(&self.device, &self.state.config);
+    }
+
+    fn render_offscreen(
+        &mut self,
+        width: u32,
+        height: u32,
+        scale: f64,
+        transparent: bool,
+    ) -> impl std::future::Future<Output = Result<RgbaImage, render::ImageError>> {
+        let scene = self.editor.build_scene();
+
+        let params = ImageParams {
+            width,
+            height,
+            scale,
+            base_color: if transparent {
+                Color::TRANSPARENT
+            } else {
+                self.background
+            },
+            antialiasing_method: self.antialiasing,
+        };
+        let pending = render::render_offscreen(
+            &self.device,
+            &self.queue,
+            &mut self.renderer,
+            &scene,
+            &params,
+        );
+
+        let read = pending.map(|pending| pending.read(&self.device));
+        async move { read?.await }
+    }
+
+    fn render(&mut self) {
+        let width = self.canvas.width();
+        let height = self.canvas.height();
+
+        // the canvas may have been resized by the page since the last frame
+        if (width, height) != (self.state.config.width, self.state.config.height) {
+            self.state.config.width = width;
+            self.state.config.height = height;
+            self.configure_surface();
+        }
+
+        // Build scene
+        let scene = self.editor.build_view_scene(width as f64, height as f64);
+
+        // Render to surface
+        let surface_texture = self
+            .state
+            .surface
+            .get_current_texture()
+            .expect("Failed to get current texture");
+
+        self.renderer
+            .render_to_surface(
+                &self.device,
+                &self.queue,
+                &scene,
+                &surface_texture,
+                &RenderParams {
+                    base_color: self.background,
+                    width,
+                    height,
+                    antialiasing_method: self.antialiasing,
+                },
+            )
+            .expect("Failed to render to surface");
+
+        surface_texture.present();
+
+        // the canvas still holds this frame until the browser composites it
+        if let Some(recorder) = &mut self.recording {
+            let canvas = &self.canvas;
+            let now = js_sys::Date::now() / 1000.0;
+            let captured = recorder.capture(now, |timestamp_us| {
+                let init = web_sys::VideoFrameInit::new();
+                init.set_timestamp(timestamp_us as f64);
+                web_sys::VideoFrame::new_with_html_canvas_element_and_video_frame_init(
+                    canvas, &init,
+                )
+                .map_err(js_error)
+            });
+            if let Err(e) = captured {
+                console_log!("recording: {}", e);
+            }
+        }
+    }
+}
+
+// Forwards frames to a WebCodecs `VideoEncoder`. Chunks arrive through the
+// output callback and are collected until the recording is flushed.
+struct WebCodecsEncoder {
+    encoder: web_sys::VideoEncoder,
+    chunks: Rc<RefCell<Vec<EncodedChunk>>>,
+    error: Rc<RefCell<Option<String>>>,
+    // the encoder calls back into these until it is closed
+    _output: Closure<dyn FnMut(web_sys::EncodedVideoChunk)>,
+    _on_error: Closure<dyn FnMut(JsValue)>,
+}
+
+impl WebCodecsEncoder {
+    fn new(settings: &RecordingSettings, width: u32, height: u32) -> Result<Self, JsValue> {
+        let chunks = Rc::new(RefCell::new(Vec::new()));
+        let error = Rc::new(RefCell::new(None));
+
+        let output = {
+            let chunks = chunks.clone();
+            Closure::wrap(Box::new(move |chunk: web_sys::EncodedVideoChunk| {
+                let data = js_sys::Uint8Array::new_with_length(chunk.byte_length());
+                let _ = chunk.copy_to_with_buffer_source(&data);
+                chunks.borrow_mut().push(EncodedChunk {
+                    data: data.to_vec(),
+                    timestamp_us: chunk.timestamp() as i64,
+                    keyframe: chunk.type_() == web_sys::EncodedVideoChunkType::Key,
+                });
+            }) as Box<dyn FnMut(web_sys::EncodedVideoChunk)>)
+        };
+        let on_error = {
+            let error = error.clone();
+            Closure::wrap(Box::new(move |e: JsValue| {
+                *error.borrow_mut() = Some(js_message(&e));
+            }) as Box<dyn FnMut(JsValue)>)
+        };
+
+        let init = web_sys::VideoEncoderInit::new(
+            on_error.as_ref().unchecked_ref(),
+            output.as_ref().unchecked_ref(),
+        );
+        let encoder = web_sys::VideoEncoder::new(&init)?;
+        let config =
+            web_sys::VideoEncoderConfig::new(settings.codec.webcodecs_name(), height, width);
+        config.set_bitrate(settings.bitrate);
+        config.set_framerate(settings.fps);
+        encoder.configure(&config)?;
+
+        Ok(Self {
+            encoder,
+            chunks,
+            error,
+            _output: output,
+            _on_error: on_error,
+        })
+    }
+}
+
+impl FrameEncoder for WebCodecsEncoder {
+    type Frame = web_sys::VideoFrame;
+
+    // the timestamp was already set when the frame was captured
+    fn encode(
+        &mut self,
+        frame: web_sys::VideoFrame,
+        _timestamp_us: i64,
+        keyframe: bool,
+    ) -> Result<(), RecordingError> {
+        if let Some(e) = self.error.borrow_mut().take() {
+            frame.close();
+            return Err(RecordingError::Encoder(e));
+        }
+        let options = web_sys::VideoEncoderEncodeOptions::new();
+        options.set_key_frame(keyframe);
+        let result = self.encoder.encode_with_options(&frame, &options);
+        // the encoder keeps its own reference; release the canvas copy now
+        frame.close();
+        result.map_err(js_error)
+    }
+
+    fn flush(&mut self) -> FlushFuture {
+        let flushed = wasm_bindgen_futures::JsFuture::from(self.encoder.flush());
+        let encoder = self.encoder.clone();
+        let chunks = self.chunks.clone();
+        let error = self.error.clone();
+        Box::pin(async move {
+            let flushed = flushed.await;
+            let _ = encoder.close();
+            if let Some(e) = error.borrow_mut().take() {
+                return Err(RecordingError::Encoder(e));
+            }
+            flushed.map_err(js_error)?;
+            Ok(chunks.take())
+        })
+    }
+}
+
+fn js_error(value: JsValue) -> RecordingError {
+    RecordingError::Encoder(js_message(&value))
+}
+
+fn js_message(value: &JsValue) -> String {
+    value
+        .as_string()
+        .or_else(|| {
+            value
+                .dyn_ref::<js_sys::Error>()
+                .map(|e| String::from(e.message()))
+        })
+        .unwrap_or_else(|| format!("{:?}", value))
+}
+
+#[wasm_bindgen]
+pub fn add(a: u32, b: u32) -> u32 {
+    a + b
+}
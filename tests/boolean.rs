@@ -0,0 +1,241 @@
+//! Boolean path operations, checked against a rasterized reference: every
+//! sample point must be filled in the result exactly when the operation says
+//! it should be, given which operands cover it.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use proptest::prelude::*;
+use svelte_vello::{boolean, BooleanOp, Editor, IrRectangle};
+use vello::kurbo::{self, BezPath, Circle, Line, ParamCurveNearest, PathEl, Point, Rect, Shape};
+use vello::peniko::{Color, Fill};
+
+// samples closer than this to an outline may fall either way
+const BOUNDARY_MARGIN: f64 = 0.5;
+
+fn is_filled(path: &BezPath, fill: Fill, point: Point) -> bool {
+    let winding = path.winding(point);
+    match fill {
+        Fill::NonZero => winding != 0,
+        Fill::EvenOdd => winding % 2 != 0,
+    }
+}
+
+fn edges(path: &BezPath) -> Vec<Line> {
+    let mut edges = Vec::new();
+    let (mut start, mut last) = (Point::ZERO, Point::ZERO);
+    kurbo::flatten(path.iter(), boolean::FLATTEN_TOLERANCE, |el| match el {
+        PathEl::MoveTo(p) => {
+            start = p;
+            last = p;
+        }
+        PathEl::LineTo(p) => {
+            edges.push(Line::new(last, p));
+            last = p;
+        }
+        PathEl::ClosePath => {
+            edges.push(Line::new(last, start));
+            last = start;
+        }
+        _ => {}
+    });
+    edges
+}
+
+fn near(edges: &[Line], point: Point) -> bool {
+    edges
+        .iter()
+        .any(|edge| edge.nearest(point, 1e-9).distance_sq < BOUNDARY_MARGIN * BOUNDARY_MARGIN)
+}
+
+// a five-pointed star drawn in one stroke, so its middle winds twice
+fn star(cx: f64, cy: f64, radius: f64) -> BezPath {
+    let mut path = BezPath::new();
+    for i in 0..5 {
+        let angle = i as f64 * 4.0 * std::f64::consts::PI / 5.0 - std::f64::consts::FRAC_PI_2;
+        let point = Point::new(cx + radius * angle.cos(), cy + radius * angle.sin());
+        if i == 0 {
+            path.move_to(point);
+        } else {
+            path.line_to(point);
+        }
+    }
+    path.close_path();
+    path
+}
+
+// a square with a square hole, wound the same way so only even-odd sees it
+fn frame(x: f64, y: f64, size: f64) -> BezPath {
+    let mut path = Rect::new(x, y, x + size, y + size).to_path(0.1);
+    path.extend(
+        Rect::new(
+            x + size / 4.0,
+            y + size / 4.0,
+            x + size * 0.75,
+            y + size * 0.75,
+        )
+        .to_path(0.1),
+    );
+    path
+}
+
+fn operand() -> impl Strategy<Value = BezPath> {
+    prop_oneof![
+        (0.0..60.0, 0.0..60.0, 5.0..40.0, 5.0..40.0).prop_map(|(x, y, w, h)| Rect::new(
+            x,
+            y,
+            x + w,
+            y + h
+        )
+        .to_path(0.1)),
+        (10.0..70.0, 10.0..70.0, 5.0..30.0)
+            .prop_map(|(x, y, r)| Circle::new((x, y), r).to_path(0.1)),
+        (20.0..60.0, 20.0..60.0, 10.0..35.0).prop_map(|(x, y, r)| star(x, y, r)),
+        // whole numbers make shared and overlapping edges likely
+        (0u8..40, 0u8..40, 8u8..40).prop_map(|(x, y, size)| frame(x as f64, y as f64, size as f64)),
+    ]
+}
+
+fn fill_rule() -> impl Strategy<Value = Fill> {
+    prop_oneof![Just(Fill::NonZero), Just(Fill::EvenOdd)]
+}
+
+fn operation() -> impl Strategy<Value = BooleanOp> {
+    prop_oneof![
+        Just(BooleanOp::Union),
+        Just(BooleanOp::Intersect),
+        Just(BooleanOp::Subtract),
+        Just(BooleanOp::Xor),
+    ]
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn matches_rasterized_reference(
+        a in operand(),
+        a_fill in fill_rule(),
+        b in operand(),
+        b_fill in fill_rule(),
+        op in operation(),
+    ) {
+        let result = boolean::combine(&a, a_fill, &b, b_fill, op);
+        let boundary: Vec<Line> = edges(&a).into_iter().chain(edges(&b)).collect();
+        for i in 0..50 {
+            for j in 0..50 {
+                let point = Point::new(i as f64 * 2.0 + 0.37, j as f64 * 2.0 + 0.61);
+                if near(&boundary, point) {
+                    continue;
+                }
+                let expected = op.apply(is_filled(&a, a_fill, point), is_filled(&b, b_fill, point));
+                prop_assert_eq!(is_filled(&result, Fill::NonZero, point), expected, "at {:?}", point);
+                prop_assert_eq!(is_filled(&result, Fill::EvenOdd, point), expected, "at {:?}", point);
+            }
+        }
+    }
+}
+
+#[test]
+fn combine_selection_is_one_undo_step() {
+    let mut editor = Editor::new();
+    let square = editor.add_shape(Box::new(IrRectangle::new(
+        0.0,
+        0.0,
+        100.0,
+        100.0,
+        Color::from_rgb8(255, 0, 0),
+    )));
+    let hole = editor.add_shape(Box::new(IrRectangle::new(
+        25.0,
+        25.0,
+        50.0,
+        50.0,
+        Color::from_rgb8(0, 0, 255),
+    )));
+
+    let result = editor
+        .combine(&[hole, square], BooleanOp::Subtract)
+        .unwrap();
+    assert!(editor.shape(square).is_none() && editor.shape(hole).is_none());
+    assert_eq!(editor.selection(), &[result]);
+    let shape = editor.shape(result).unwrap();
+    assert_eq!(shape.bounds(), Rect::new(0.0, 0.0, 100.0, 100.0));
+    assert_eq!(shape.color(), Some(Color::from_rgb8(255, 0, 0)));
+    assert!(shape.contains(10.0, 10.0));
+    assert!(!shape.contains(50.0, 50.0));
+    assert!((shape.outline().area().abs() - 7500.0).abs() < 1e-6);
+
+    assert!(editor.undo());
+    assert!(editor.shape(result).is_none());
+    assert!(editor.shape(square).is_some() && editor.shape(hole).is_some());
+    assert!(editor.redo());
+    assert!(editor.shape(result).is_some());
+
+    // nothing left means nothing changes
+    let apart = editor.add_shape(Box::new(IrRectangle::new(
+        200.0,
+        0.0,
+        10.0,
+        10.0,
+        Color::from_rgb8(0, 0, 0),
+    )));
+    assert_eq!(editor.combine(&[result, apart], BooleanOp::Intersect), None);
+    assert!(editor.shape(apart).is_some());
+}
+
+#[test]
+fn curves_come_through_as_curves() {
+    let a = Circle::new((30.0, 50.0), 25.0).to_path(0.1);
+    let b = Circle::new((60.0, 50.0), 25.0).to_path(0.1);
+    let result = boolean::combine(&a, Fill::NonZero, &b, Fill::NonZero, BooleanOp::Union);
+
+    // each circle's four quarters, at most one of them cut short and one
+    // more split where the circles cross
+    let curves = result
+        .elements()
+        .iter()
+        .filter(|el| matches!(el, PathEl::CurveTo(..)))
+        .count();
+    assert!(curves <= 10, "{} curves", curves);
+    assert_eq!(result.elements().len(), curves + 2);
+
+    // two discs less the lens where they overlap
+    let lens = 2.0 * 625.0 * 0.6f64.acos() - 15.0 * 40.0;
+    let expected = 2.0 * std::f64::consts::PI * 625.0 - lens;
+    assert!((result.area().abs() - expected).abs() < 1.0);
+}
+
+#[test]
+fn result_size_follows_the_inputs() {
+    // many-sided polygons, crossing twice; every vertex of a result should
+    // be one of theirs or a crossing
+    let polygon = |cx: f64| {
+        let mut path = BezPath::new();
+        for i in 0..1000 {
+            let angle = i as f64 * std::f64::consts::TAU / 1000.0;
+            let point = Point::new(cx + 40.0 * angle.cos(), 50.0 + 40.0 * angle.sin());
+            if i == 0 {
+                path.move_to(point);
+            } else {
+                path.line_to(point);
+            }
+        }
+        path.close_path();
+        path
+    };
+    let (a, b) = (polygon(40.0), polygon(70.0));
+    let vertices = |op| {
+        boolean::combine(&a, Fill::NonZero, &b, Fill::NonZero, op)
+            .elements()
+            .iter()
+            .filter(|el| matches!(el, PathEl::MoveTo(_) | PathEl::LineTo(_)))
+            .count()
+    };
+    // the outside and inside parts of both, sharing the crossings
+    assert_eq!(
+        vertices(BooleanOp::Union) + vertices(BooleanOp::Intersect),
+        2000 + 4
+    );
+    assert_eq!(vertices(BooleanOp::Subtract), 1000 + 2);
+    assert_eq!(vertices(BooleanOp::Xor), 2000 + 4);
+}
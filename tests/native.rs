@@ -0,0 +1,1204 @@
+//! Native tests for the scene model and the headless renderer.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use svelte_vello::{
+    recording::FlushFuture, Action, Alignment, Animation, Axis, Codec, Constraint, Easing, Editor,
+    EditorEvent, EncodedChunk, Endpoint, FrameEncoder, Graph, GraphLayout, Guide, HeadlessOptions,
+    HeadlessRenderer, ImageParams, IrCircle, IrConnector, IrPath, IrRectangle, Layout,
+    LayoutAlgorithm, Modifiers, PathPoint, PointKind, PointerEvent, PointerPhase, PointerType,
+    Recorder, RecordingError, RecordingSettings, Routing, SnapBypass, Stack,
+};
+use vello::{
+    kurbo::{BezPath, ParamCurve, Point, Rect, Shape as _, Size, Vec2},
+    peniko::{Color, Fill, Mix},
+    AaConfig,
+};
+
+#[test]
+fn drag_moves_shape_and_followers() {
+    let mut editor = Editor::new();
+    let rect = editor.add_shape(Box::new(IrRectangle::new(
+        10.0,
+        10.0,
+        50.0,
+        50.0,
+        Color::from_rgb8(255, 0, 0),
+    )));
+    let circle = editor.add_shape(Box::new(IrCircle::new(
+        200.0,
+        200.0,
+        20.0,
+        Color::from_rgb8(0, 0, 255),
+    )));
+    assert!(editor.follow(circle, rect));
+
+    editor.handle_mouse_down(20.0, 20.0);
+    assert_eq!(editor.selected_shape(), Some(rect));
+    assert!(editor.handle_mouse_move(30.0, 25.0));
+    editor.handle_mouse_up();
+
+    assert_eq!(editor.shape(rect).unwrap().node().x.get(), 20.0);
+    assert_eq!(editor.shape(rect).unwrap().node().y.get(), 15.0);
+    assert_eq!(editor.shape(circle).unwrap().node().x.get(), 210.0);
+    assert_eq!(editor.shape(circle).unwrap().node().y.get(), 205.0);
+}
+
+#[test]
+fn keyboard_nudge_delete_and_undo() {
+    let mut editor = Editor::new();
+    let rect = editor.add_shape(Box::new(IrRectangle::new(
+        0.0,
+        0.0,
+        10.0,
+        10.0,
+        Color::from_rgb8(255, 0, 0),
+    )));
+    editor.handle_mouse_down(5.0, 5.0);
+    editor.handle_mouse_up();
+    assert_eq!(editor.selection(), &[rect]);
+
+    let shift = Modifiers {
+        shift: true,
+        ..Modifiers::default()
+    };
+    let ctrl = Modifiers {
+        ctrl: true,
+        ..Modifiers::default()
+    };
+
+    // a held key repeats without a key up in between and undoes as one step
+    editor.handle_key_down("ArrowRight", shift);
+    editor.handle_key_down("ArrowRight", shift);
+    editor.handle_key_up("ArrowRight");
+    assert_eq!(editor.shape(rect).unwrap().node().x.get(), 20.0);
+
+    editor.handle_key_down("Delete", Modifiers::default());
+    assert!(editor.shape(rect).is_none());
+    assert!(editor.selection().is_empty());
+
+    editor.handle_key_down("z", ctrl);
+    assert_eq!(editor.shape(rect).unwrap().node().x.get(), 20.0);
+    editor.handle_key_down("z", ctrl);
+    assert_eq!(editor.shape(rect).unwrap().node().x.get(), 0.0);
+    editor.handle_key_down(
+        "Z",
+        Modifiers {
+            shift: true,
+            ..ctrl
+        },
+    );
+    assert_eq!(editor.shape(rect).unwrap().node().x.get(), 20.0);
+
+    // with nothing to move, arrows are left to scroll the page
+    editor.set_selection(Vec::new());
+    assert_eq!(
+        editor.handle_key_down("ArrowDown", Modifiers::default()),
+        None
+    );
+    assert_eq!(Action::parse("tool_input"), None);
+    assert_eq!(
+        Action::parse("nudge:0, 5"),
+        Some(Action::Nudge { dx: 0.0, dy: 5.0 })
+    );
+}
+
+#[test]
+fn group_and_ungroup_keep_shapes_in_place() {
+    let mut editor = Editor::new();
+    let a = editor.add_shape(Box::new(IrRectangle::new(
+        10.0,
+        10.0,
+        20.0,
+        20.0,
+        Color::from_rgb8(255, 0, 0),
+    )));
+    let b = editor.add_shape(Box::new(IrRectangle::new(
+        50.0,
+        40.0,
+        10.0,
+        10.0,
+        Color::from_rgb8(0, 0, 255),
+    )));
+    editor.set_selection(vec![a, b]);
+    let group = editor.group_selection().unwrap();
+    assert_eq!(editor.parent(b), Some(group));
+    assert_eq!(
+        editor.world_bounds(group),
+        Some(Rect::new(10.0, 10.0, 60.0, 50.0))
+    );
+    assert_eq!(
+        editor.world_bounds(b),
+        Some(Rect::new(50.0, 40.0, 60.0, 50.0))
+    );
+
+    // clicking a child picks up the whole group
+    editor.handle_mouse_down(55.0, 45.0);
+    assert_eq!(editor.selected_shape(), Some(group));
+    editor.handle_mouse_move(65.0, 45.0);
+    editor.handle_mouse_up();
+    assert_eq!(
+        editor.world_bounds(b),
+        Some(Rect::new(60.0, 40.0, 70.0, 50.0))
+    );
+
+    // until the group is entered
+    assert!(editor.handle_double_click(65.0, 45.0));
+    editor.handle_mouse_down(65.0, 45.0);
+    assert_eq!(editor.selected_shape(), Some(b));
+    editor.handle_mouse_up();
+
+    editor.set_selection(vec![group]);
+    assert_eq!(editor.ungroup(), vec![a, b]);
+    assert_eq!(editor.parent(b), None);
+    assert_eq!(editor.shape(b).unwrap().node().x.get(), 60.0);
+
+    assert!(editor.undo());
+    assert_eq!(editor.parent(b), Some(group));
+    assert_eq!(
+        editor.world_bounds(b),
+        Some(Rect::new(60.0, 40.0, 70.0, 50.0))
+    );
+}
+
+#[test]
+fn ungrouping_a_rotated_stretched_group_keeps_children_in_place() {
+    let mut editor = Editor::new();
+    let square = |editor: &mut Editor, x| {
+        editor.add_shape(Box::new(IrRectangle::new(x, 0.0, 10.0, 10.0, Color::BLACK)))
+    };
+    // groups inside the outer one, as those draw their own rotation and scale
+    let (a, b) = (square(&mut editor, 0.0), square(&mut editor, 40.0));
+    editor.set_selection(vec![a]);
+    let upright = editor.group_selection().unwrap();
+    editor.set_selection(vec![b]);
+    let turned = editor.group_selection().unwrap();
+    editor
+        .shape(turned)
+        .unwrap()
+        .node()
+        .rotation
+        .set(std::f64::consts::FRAC_PI_2);
+    editor.set_selection(vec![upright, turned]);
+    let outer = editor.group_selection().unwrap();
+    let node = editor.shape(outer).unwrap().node().clone();
+    node.rotation.set(std::f64::consts::FRAC_PI_6);
+    node.scale_x.set(2.0);
+    node.scale_y.set(1.0);
+
+    let world = |editor: &Editor, id| {
+        editor.parent_transform(id) * editor.shape(id).unwrap().as_group().unwrap().transform()
+    };
+    let before = [world(&editor, upright), world(&editor, turned)];
+    editor.set_selection(vec![outer]);
+    assert_eq!(editor.ungroup(), vec![upright, turned]);
+    for (id, before) in [upright, turned].iter().zip(&before) {
+        let after = world(&editor, *id).as_coeffs();
+        for (a, b) in after.iter().zip(&before.as_coeffs()) {
+            assert!((a - b).abs() < 1e-9, "{:?} != {:?}", after, before);
+        }
+    }
+    // the stretch runs along the turned child's y axis now
+    let node = editor.shape(turned).unwrap().node().clone();
+    assert!(close(
+        node.rotation.get(),
+        std::f64::consts::FRAC_PI_6 + std::f64::consts::FRAC_PI_2
+    ));
+    assert!(close(node.scale_x.get(), 1.0) && close(node.scale_y.get(), 2.0));
+
+    // turned part of the way, a stretched child would be skewed
+    assert!(editor.undo());
+    editor
+        .shape(turned)
+        .unwrap()
+        .node()
+        .rotation
+        .set(std::f64::consts::FRAC_PI_4);
+    editor.set_selection(vec![outer]);
+    assert!(editor.ungroup().is_empty());
+    assert_eq!(editor.parent(turned), Some(outer));
+    assert_eq!(editor.selection(), &[outer]);
+}
+
+#[test]
+fn path_points_can_be_inserted_dragged_and_deleted() {
+    let mut editor = Editor::new();
+    let square = Rect::new(0.0, 0.0, 100.0, 100.0).to_path(0.1);
+    let path = editor.add_shape(Box::new(IrPath::new(
+        10.0,
+        10.0,
+        square,
+        Fill::NonZero,
+        Color::from_rgb8(255, 0, 0),
+    )));
+
+    assert!(editor.handle_double_click(60.0, 60.0));
+    assert_eq!(editor.editing_path(), Some(path));
+    assert_eq!(editor.editable_path().unwrap().contours[0].anchors.len(), 4);
+
+    // double-clicking the top edge splits it
+    assert!(editor.handle_double_click(60.0, 10.0));
+    let point = PathPoint {
+        contour: 0,
+        index: 1,
+    };
+    assert_eq!(editor.selected_path_point(), Some(point));
+    assert_eq!(editor.editable_path().unwrap().contours[0].anchors.len(), 5);
+
+    editor.handle_mouse_down(60.0, 10.0);
+    editor.handle_mouse_move(60.0, -10.0);
+    editor.handle_mouse_up();
+    assert_eq!(
+        editor.world_bounds(path),
+        Some(Rect::new(10.0, -10.0, 110.0, 110.0))
+    );
+    assert!(editor.undo());
+    assert_eq!(
+        editor.world_bounds(path),
+        Some(Rect::new(10.0, 10.0, 110.0, 110.0))
+    );
+
+    assert!(editor.select_path_point(Some(point)));
+    assert!(editor.set_path_point_kind(PointKind::Symmetric));
+    let anchor = *editor.editable_path().unwrap().anchor(point).unwrap();
+    assert_eq!(anchor.kind, PointKind::Symmetric);
+    let (handle_in, handle_out) = (anchor.handle_in.unwrap(), anchor.handle_out.unwrap());
+    assert!(((handle_in - anchor.point) + (handle_out - anchor.point)).hypot() < 1e-9);
+
+    let outcome = editor
+        .handle_key_down("Delete", Modifiers::default())
+        .unwrap();
+    assert!(outcome.changed);
+    assert!(editor.shape(path).is_some());
+    assert_eq!(editor.editable_path().unwrap().contours[0].anchors.len(), 4);
+
+    editor.handle_key_down("Escape", Modifiers::default());
+    assert_eq!(editor.editing_path(), None);
+}
+
+#[test]
+fn freehand_stroke_becomes_one_path() {
+    let mut editor = Editor::new();
+    assert!(editor.set_tool("pen"));
+    editor.brush_mut().width = 4.0;
+    editor.brush_mut().smoothing = 0.0;
+
+    editor.handle_mouse_down(10.0, 10.0);
+    for step in 1..=45 {
+        let x = 10.0 + step as f64 * 2.0;
+        editor.handle_mouse_move(x, 10.0 + (x / 10.0).sin());
+    }
+    editor.handle_mouse_up();
+
+    let ids: Vec<_> = editor.shape_ids().collect();
+    assert_eq!(ids.len(), 1);
+    let bounds = editor.world_bounds(ids[0]).unwrap();
+    // round caps reach half the width past both ends
+    assert!((bounds.x0 - 8.0).abs() < 0.5 && (bounds.x1 - 102.0).abs() < 0.5);
+    assert!(bounds.height() < 8.0);
+    assert!(editor
+        .shape(ids[0])
+        .unwrap()
+        .contains(50.0, 10.0 + 5.0f64.sin()));
+
+    assert!(editor.undo());
+    assert_eq!(editor.shape_ids().count(), 0);
+}
+
+#[test]
+fn timeline_seek_is_deterministic() {
+    let mut editor = Editor::new();
+    let rect = editor.add_shape(Box::new(IrRectangle::new(
+        0.0,
+        0.0,
+        10.0,
+        10.0,
+        Color::from_rgb8(255, 0, 0),
+    )));
+    let x = editor.shape(rect).unwrap().node().x;
+    let timeline = editor.timeline_mut();
+    timeline.add(Animation::tween(x, 100.0, 1.0, Easing::Linear), 0.5);
+    assert_eq!(timeline.duration(), Some(1.5));
+    assert_eq!(timeline.frame_times(10.0, 0.0).count(), 16);
+
+    editor.seek_timeline(1.0);
+    assert_eq!(x.get(), 50.0);
+    editor.seek_timeline(1.5);
+    assert_eq!(x.get(), 100.0);
+    // seeking back before the clip starts restores its first value
+    editor.seek_timeline(0.2);
+    assert_eq!(x.get(), 0.0);
+}
+
+// Stands in for WebCodecs: every frame becomes a one byte chunk.
+#[derive(Default)]
+struct StubEncoder {
+    chunks: Vec<EncodedChunk>,
+}
+
+impl FrameEncoder for StubEncoder {
+    type Frame = u8;
+
+    fn encode(
+        &mut self,
+        frame: u8,
+        timestamp_us: i64,
+        keyframe: bool,
+    ) -> Result<(), RecordingError> {
+        self.chunks.push(EncodedChunk {
+            data: vec![frame],
+            timestamp_us,
+            keyframe,
+        });
+        Ok(())
+    }
+
+    fn flush(&mut self) -> FlushFuture {
+        let chunks = std::mem::take(&mut self.chunks);
+        Box::pin(async move { Ok(chunks) })
+    }
+}
+
+#[test]
+fn recording_paces_frames_and_muxes_webm() {
+    let settings = RecordingSettings {
+        fps: 30.0,
+        bitrate: 1_000_000.0,
+        codec: Codec::Vp9,
+    };
+    let mut recorder = Recorder::new(StubEncoder::default(), settings, 64, 48);
+    // three seconds of 60Hz renders, starting at an arbitrary clock time
+    for i in 0..180 {
+        recorder
+            .capture(100.0 + i as f64 / 60.0, |_| Ok(0xab))
+            .unwrap();
+    }
+    assert_eq!(recorder.frames(), 90);
+
+    let webm = pollster::block_on(recorder.finish(103.0)).unwrap();
+    assert!(webm.starts_with(&[0x1a, 0x45, 0xdf, 0xa3]));
+    assert!(webm.windows(5).any(|w| w == b"V_VP9"));
+    // keyframes at 0s and 2s each open a cluster
+    let clusters = webm
+        .windows(4)
+        .filter(|w| *w == [0x1f, 0x43, 0xb6, 0x75])
+        .count();
+    assert_eq!(clusters, 2);
+}
+
+#[test]
+fn headless_render_fills_background() {
+    // machines without any adapter (not even a software one) can't run this
+    let mut renderer = match HeadlessRenderer::new_blocking(HeadlessOptions {
+        use_cpu: true,
+        ..HeadlessOptions::default()
+    }) {
+        Ok(renderer) => renderer,
+        Err(e) => {
+            eprintln!("skipping headless render test: {}", e);
+            return;
+        }
+    };
+
+    let editor = Editor::new();
+    let image = renderer
+        .render_blocking(
+            &editor.build_scene(),
+            &ImageParams {
+                width: 8,
+                height: 4,
+                scale: 1.0,
+                base_color: Color::from_rgb8(240, 240, 240),
+                antialiasing_method: AaConfig::Area,
+            },
+        )
+        .unwrap();
+
+    assert_eq!(image.data.len(), 8 * 4 * 4);
+    assert!(image.data.chunks(4).all(|px| px == [240, 240, 240, 255]));
+}
+
+#[test]
+fn tools_draw_shapes_and_pan() {
+    let mut editor = Editor::new();
+    assert_eq!(editor.active_tool(), "select");
+    assert!(!editor.set_tool("lasso"));
+
+    let outcome = editor.handle_key_down("r", Modifiers::default()).unwrap();
+    assert!(outcome.changed);
+    assert_eq!(editor.active_tool(), "rectangle");
+    editor.handle_mouse_down(10.0, 20.0);
+    editor.handle_mouse_move(60.0, 50.0);
+    editor.handle_mouse_up();
+
+    let ids: Vec<_> = editor.shape_ids().collect();
+    assert_eq!(ids.len(), 1);
+    assert_eq!(editor.selection(), &ids[..]);
+    assert_eq!(
+        editor.world_bounds(ids[0]),
+        Some(Rect::new(10.0, 20.0, 60.0, 50.0))
+    );
+    assert!(editor.undo());
+    assert_eq!(editor.shape_ids().count(), 0);
+
+    // a click without a drag draws nothing
+    editor.handle_mouse_down(10.0, 20.0);
+    editor.handle_mouse_up();
+    assert_eq!(editor.shape_ids().count(), 0);
+
+    assert!(editor.set_tool("hand"));
+    editor.handle_mouse_down(100.0, 100.0);
+    editor.handle_mouse_move(130.0, 90.0);
+    editor.handle_mouse_up();
+    assert_eq!(editor.pan(), vello::kurbo::Vec2::new(30.0, -10.0));
+}
+
+#[test]
+fn connectors_follow_their_shapes() {
+    let mut editor = Editor::new();
+    let a = editor.add_shape(Box::new(IrRectangle::new(
+        0.0,
+        0.0,
+        40.0,
+        20.0,
+        Color::BLACK,
+    )));
+    let b = editor.add_shape(Box::new(IrRectangle::new(
+        100.0,
+        60.0,
+        40.0,
+        20.0,
+        Color::BLACK,
+    )));
+    let connector = editor
+        .connect(IrConnector::new(
+            Endpoint::border(a),
+            Endpoint::port(b, 0.5, 0.0),
+            Routing::Orthogonal,
+        ))
+        .unwrap();
+    let route = |editor: &Editor| {
+        let connector = editor.shape(connector).unwrap().as_connector().unwrap();
+        let segments: Vec<_> = connector.path().segments().collect();
+        (
+            segments[0].start(),
+            segments.last().unwrap().end(),
+            segments.len(),
+        )
+    };
+
+    // out of the right edge, then down into the top of `b`, stopping short
+    // of it for the arrowhead
+    let (start, end, segments) = route(&editor);
+    assert_eq!(start, Point::new(40.0, 10.0));
+    assert_eq!(end.x, 120.0);
+    assert!(end.y < 60.0);
+    assert_eq!(segments, 2);
+
+    editor.translate(&[a], Vec2::new(0.0, 100.0));
+    let (start, _, _) = route(&editor);
+    assert_eq!(start.y, 110.0);
+
+    // a connector to a removed shape disappears, and comes back on undo
+    editor.remove(&[b]);
+    assert!(editor.world_bounds(connector).unwrap().is_zero_area());
+    editor.undo();
+    assert!(!editor.world_bounds(connector).unwrap().is_zero_area());
+}
+
+#[test]
+fn orthogonal_connectors_route_around_shapes() {
+    let mut editor = Editor::new();
+    let a = editor.add_shape(Box::new(IrRectangle::new(
+        0.0,
+        0.0,
+        40.0,
+        20.0,
+        Color::BLACK,
+    )));
+    let b = editor.add_shape(Box::new(IrRectangle::new(
+        200.0,
+        0.0,
+        40.0,
+        20.0,
+        Color::BLACK,
+    )));
+    let wall = editor.add_shape(Box::new(IrRectangle::new(
+        100.0,
+        -50.0,
+        20.0,
+        120.0,
+        Color::BLACK,
+    )));
+    let connector = editor
+        .connect(IrConnector::new(
+            Endpoint::border(a),
+            Endpoint::border(b),
+            Routing::Orthogonal,
+        ))
+        .unwrap();
+    let corners = |editor: &Editor| -> Vec<Point> {
+        let connector = editor.shape(connector).unwrap().as_connector().unwrap();
+        connector.path().segments().map(|s| s.start()).collect()
+    };
+
+    let detour = corners(&editor);
+    assert!(detour.len() > 1);
+    let wall_bounds = editor.world_bounds(wall).unwrap();
+    let connector_path = editor
+        .shape(connector)
+        .unwrap()
+        .as_connector()
+        .unwrap()
+        .path();
+    // every leg of the route is horizontal or vertical and misses the wall
+    for leg in connector_path.segments() {
+        let (start, end) = (leg.start(), leg.end());
+        assert!(start.x == end.x || start.y == end.y, "{:?}", detour);
+        let reach = Rect::from_points(start, end);
+        assert!(
+            reach.x1 <= wall_bounds.x0
+                || reach.x0 >= wall_bounds.x1
+                || reach.y1 <= wall_bounds.y0
+                || reach.y0 >= wall_bounds.y1,
+            "{:?}",
+            detour
+        );
+    }
+    assert!(detour.iter().any(|p| p.y > 70.0 || p.y < -50.0));
+
+    // moving the wall out of the way straightens the route
+    editor.translate(&[wall], Vec2::new(0.0, 500.0));
+    assert_eq!(corners(&editor), vec![Point::new(40.0, 10.0)]);
+}
+
+#[test]
+fn tree_layout_puts_children_below_their_parent() {
+    let mut editor = Editor::new();
+    let ids: Vec<_> = [(0.0, 0.0), (200.0, 0.0), (400.0, 0.0)]
+        .iter()
+        .map(|(x, y)| {
+            editor.add_shape(Box::new(IrRectangle::new(*x, *y, 40.0, 20.0, Color::BLACK)))
+        })
+        .collect();
+    for child in &ids[1..] {
+        editor.connect(IrConnector::new(
+            Endpoint::border(ids[0]),
+            Endpoint::border(*child),
+            Routing::Straight,
+        ));
+    }
+    let center = |editor: &Editor, id| editor.world_bounds(id).unwrap().center();
+    let layout = GraphLayout {
+        algorithm: LayoutAlgorithm::Tree,
+        ..GraphLayout::default()
+    };
+
+    assert!(editor.layout_graph(&ids, &layout, None));
+    let (root, left, right) = (
+        center(&editor, ids[0]),
+        center(&editor, ids[1]),
+        center(&editor, ids[2]),
+    );
+    assert_eq!(left.y, right.y);
+    assert_eq!(left.y - root.y, 20.0 + layout.layer_gap);
+    assert_eq!(right.x - left.x, 40.0 + layout.node_gap);
+    assert_eq!(root.x, (left.x + right.x) / 2.0);
+
+    // one undo step puts everything back
+    assert!(editor.undo());
+    assert_eq!(center(&editor, ids[2]), Point::new(420.0, 10.0));
+}
+
+fn boxes(count: usize) -> Graph {
+    Graph {
+        sizes: vec![Size::new(40.0, 20.0); count],
+        edges: Vec::new(),
+    }
+}
+
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-9
+}
+
+#[test]
+fn layered_layout_stacks_layers_and_centers_parents() {
+    let layout = GraphLayout::default();
+    let graph = Graph {
+        edges: vec![(0, 1), (0, 2), (1, 3), (2, 3)],
+        ..boxes(4)
+    };
+    let current = [Point::new(0.0, 0.0); 4];
+    let centers = layout.apply(&graph, &current);
+    let step = 20.0 + layout.layer_gap;
+    assert!(close(centers[1].y, centers[2].y));
+    assert!(close(centers[1].y - centers[0].y, step));
+    assert!(close(centers[3].y - centers[1].y, step));
+    assert!(close(
+        (centers[2].x - centers[1].x).abs(),
+        40.0 + layout.node_gap
+    ));
+    let middle = (centers[1].x + centers[2].x) / 2.0;
+    assert!(close(centers[0].x, middle) && close(centers[3].x, middle));
+
+    // a cycle is broken rather than looping forever
+    let graph = Graph {
+        edges: vec![(0, 1), (1, 0)],
+        ..boxes(2)
+    };
+    let centers = layout.apply(&graph, &[Point::ZERO; 2]);
+    assert!(close(centers[1].y - centers[0].y, step));
+}
+
+#[test]
+fn force_layout_is_deterministic_and_separates_boxes() {
+    let layout = GraphLayout {
+        algorithm: LayoutAlgorithm::Force,
+        ..GraphLayout::default()
+    };
+    let graph = Graph {
+        edges: vec![(0, 1), (1, 2), (2, 3), (3, 0), (0, 4)],
+        ..boxes(5)
+    };
+    // boxes all on one spot still spread out
+    let current = [Point::new(100.0, 100.0); 5];
+    let centers = layout.apply(&graph, &current);
+    assert_eq!(centers, layout.apply(&graph, &current));
+
+    for (i, a) in centers.iter().enumerate() {
+        for b in &centers[i + 1..] {
+            let d = *b - *a;
+            assert!(d.x.abs() >= 40.0 || d.y.abs() >= 20.0, "{:?}", centers);
+        }
+    }
+    let extent = centers
+        .iter()
+        .map(|center| Rect::from_center_size(*center, Size::new(40.0, 20.0)))
+        .reduce(|a, b| a.union(b))
+        .unwrap();
+    assert!((extent.center() - Point::new(100.0, 100.0)).hypot() < 1e-9);
+}
+
+#[test]
+fn radial_layout_puts_levels_on_rings() {
+    let layout = GraphLayout {
+        algorithm: LayoutAlgorithm::Radial,
+        ..GraphLayout::default()
+    };
+    let graph = Graph {
+        edges: vec![(0, 1), (0, 2), (0, 3), (1, 4)],
+        ..boxes(5)
+    };
+    let current: Vec<Point> = (0..5).map(|i| Point::new(i as f64 * 100.0, 0.0)).collect();
+    let centers = layout.apply(&graph, &current);
+    let ring = 40.0f64.hypot(20.0) + layout.layer_gap;
+
+    let offsets: Vec<Vec2> = centers.iter().map(|center| *center - centers[0]).collect();
+    for offset in &offsets[1..4] {
+        assert!(close(offset.hypot(), ring));
+    }
+    // one wedge per leaf, so the three children are a third of a turn apart
+    assert!(close((offsets[1] - offsets[2]).hypot(), ring * 3f64.sqrt()));
+    assert!(close((offsets[2] - offsets[3]).hypot(), ring * 3f64.sqrt()));
+    // the grandchild is straight out from its parent, one ring further
+    assert!((offsets[4] - offsets[1] * 2.0).hypot() < 1e-9);
+}
+
+#[test]
+fn constraints_hold_whichever_shape_moves() {
+    let mut editor = Editor::new();
+    let a = editor.add_shape(Box::new(IrRectangle::new(
+        0.0,
+        0.0,
+        40.0,
+        20.0,
+        Color::BLACK,
+    )));
+    let b = editor.add_shape(Box::new(IrCircle::new(100.0, 50.0, 10.0, Color::BLACK)));
+    let extents: Vec<_> = [a, b]
+        .iter()
+        .map(|id| editor.extent(*id, Axis::Horizontal).unwrap())
+        .collect();
+    let left = |editor: &Editor, id| editor.world_bounds(id).unwrap().x0;
+
+    // the circle comes to the rectangle
+    let id = editor
+        .add_constraint(Constraint::align_start(&extents))
+        .unwrap();
+    assert_eq!(left(&editor, b), 0.0);
+
+    // and either one drags the other along
+    editor.translate(&[b], Vec2::new(25.0, 0.0));
+    assert_eq!(left(&editor, a), 25.0);
+    editor.translate(&[a], Vec2::new(-5.0, 0.0));
+    assert_eq!(left(&editor, b), 20.0);
+    assert!(editor.undo());
+    assert_eq!(left(&editor, b), 25.0);
+
+    assert!(editor.remove_constraint(id));
+    assert!(!editor.remove_constraint(id));
+    editor.translate(&[a], Vec2::new(100.0, 0.0));
+    assert_eq!(left(&editor, b), 25.0);
+
+    // with the constraint gone, undo takes the circle back to where it was
+    // before it came to the rectangle
+    assert!(editor.undo());
+    assert!(editor.undo());
+    assert_eq!(left(&editor, b), 0.0);
+    assert!(editor.undo());
+    assert_eq!(left(&editor, b), 90.0);
+}
+
+#[test]
+fn align_and_distribute_are_single_undo_steps() {
+    let mut editor = Editor::new();
+    let ids: Vec<_> = [(0.0, 40.0, 20.0), (50.0, 0.0, 10.0), (200.0, 10.0, 30.0)]
+        .iter()
+        .map(|(x, y, width)| {
+            editor.add_shape(Box::new(IrRectangle::new(
+                *x,
+                *y,
+                *width,
+                20.0,
+                Color::BLACK,
+            )))
+        })
+        .collect();
+    let bounds = |editor: &Editor| -> Vec<Rect> {
+        ids.iter()
+            .map(|id| editor.world_bounds(*id).unwrap())
+            .collect()
+    };
+
+    assert!(editor.align(&ids, Alignment::Bottom));
+    assert!(bounds(&editor).iter().all(|rect| rect.y1 == 60.0));
+    assert!(!editor.align(&ids, Alignment::Bottom));
+
+    // the outer two stay put, the middle one splits the space between them
+    assert!(editor.distribute(&ids, Axis::Horizontal, None));
+    let after = bounds(&editor);
+    assert_eq!(after[1].x0, 20.0 + 85.0);
+    assert_eq!(after[2].x0 - after[1].x1, 85.0);
+
+    assert!(editor.distribute(&ids, Axis::Horizontal, Some(5.0)));
+    assert_eq!(bounds(&editor)[2].x0, 20.0 + 5.0 + 10.0 + 5.0);
+
+    assert!(editor.undo());
+    assert!(editor.undo());
+    assert_eq!(bounds(&editor)[1].x0, 50.0);
+    assert!(editor.undo());
+    assert_eq!(bounds(&editor)[0].y0, 40.0);
+}
+
+#[test]
+fn resizing_a_stack_wraps_its_children() {
+    let mut editor = Editor::new();
+    let ids: Vec<_> = [
+        (100.0, 100.0, 30.0),
+        (300.0, 50.0, 40.0),
+        (0.0, 200.0, 50.0),
+    ]
+    .iter()
+    .map(|(x, y, width)| {
+        editor.add_shape(Box::new(IrRectangle::new(
+            *x,
+            *y,
+            *width,
+            20.0,
+            Color::BLACK,
+        )))
+    })
+    .collect();
+    editor.set_selection(ids.clone());
+    let stack = Stack {
+        gap: 5.0,
+        padding: 10.0,
+        wrap: true,
+        ..Stack::new(Axis::Horizontal)
+    };
+    let container = editor.contain_selection(Layout::Stack(stack)).unwrap();
+    let origins = |editor: &Editor| -> Vec<(f64, f64)> {
+        ids.iter()
+            .map(|id| editor.world_bounds(*id).unwrap().origin())
+            .map(|origin| (origin.x, origin.y))
+            .collect()
+    };
+
+    // sized to fit them all in one line, placed where the selection was
+    let bounds = editor.world_bounds(container).unwrap();
+    assert_eq!((bounds.width(), bounds.height()), (150.0, 40.0));
+    assert_eq!(origins(&editor), [(10.0, 60.0), (45.0, 60.0), (90.0, 60.0)]);
+
+    assert!(editor.set_property(container, "width", 100.0));
+    assert_eq!(origins(&editor)[2], (10.0, 85.0));
+    assert!(editor.undo());
+    assert_eq!(origins(&editor)[2], (90.0, 60.0));
+
+    // switching layouts is undoable too
+    let column = Stack {
+        axis: Axis::Vertical,
+        wrap: false,
+        ..stack
+    };
+    assert!(editor.set_layout(container, Layout::Stack(column)));
+    let lefts: Vec<f64> = origins(&editor).iter().map(|(x, _)| *x).collect();
+    assert_eq!(lefts, [10.0, 10.0, 10.0]);
+    assert!(editor.undo());
+    assert_eq!(origins(&editor)[2], (90.0, 60.0));
+}
+
+#[test]
+fn mask_and_blend_mode_are_undoable() {
+    let mut editor = Editor::new();
+    let ids: Vec<_> = [0.0, 20.0]
+        .iter()
+        .map(|x| {
+            editor.add_shape(Box::new(IrRectangle::new(
+                *x,
+                0.0,
+                10.0,
+                10.0,
+                Color::BLACK,
+            )))
+        })
+        .collect();
+    editor.set_selection(ids.clone());
+    let group = editor.group_selection().unwrap();
+    let style = |editor: &Editor| {
+        let group = editor.shape(group).unwrap().as_group().unwrap();
+        (group.mask, group.blend)
+    };
+
+    assert!(editor.set_mask(group, Some(ids[0])));
+    assert!(editor.set_blend_mode(group, Mix::Multiply));
+    assert_eq!(style(&editor), (Some(0), Mix::Multiply));
+    assert!(editor.undo());
+    assert_eq!(style(&editor), (Some(0), Mix::Normal));
+    assert!(editor.undo());
+    assert_eq!(style(&editor), (None, Mix::Normal));
+    assert!(editor.redo());
+    assert_eq!(style(&editor), (Some(0), Mix::Normal));
+
+    // a shape outside the group can't mask it
+    let outside = editor.add_shape(Box::new(IrCircle::new(50.0, 50.0, 5.0, Color::BLACK)));
+    assert!(!editor.set_mask(group, Some(outside)));
+}
+
+#[test]
+fn masking_and_containing_undo_in_one_step() {
+    let mut editor = Editor::new();
+    let ids: Vec<_> = [(0.0, 0.0), (40.0, 30.0)]
+        .iter()
+        .map(|(x, y)| {
+            editor.add_shape(Box::new(IrRectangle::new(*x, *y, 20.0, 10.0, Color::BLACK)))
+        })
+        .collect();
+    let origins = |editor: &Editor| -> Vec<Point> {
+        ids.iter()
+            .map(|id| editor.world_bounds(*id).unwrap().origin())
+            .collect()
+    };
+    let placed = origins(&editor);
+
+    editor.set_selection(ids.clone());
+    let masked = editor.mask_selection().unwrap();
+    let mask = |editor: &Editor| editor.shape(masked).unwrap().as_group().unwrap().mask;
+    assert_eq!(mask(&editor), Some(0));
+    assert!(editor.undo());
+    assert!(editor.shape(masked).is_none());
+    assert_eq!(editor.parent(ids[0]), None);
+    assert_eq!(origins(&editor), placed);
+    assert!(editor.redo());
+    assert_eq!(editor.parent(ids[0]), Some(masked));
+    assert_eq!(mask(&editor), Some(0));
+    assert!(editor.undo());
+
+    editor.set_selection(ids.clone());
+    let stack = Layout::Stack(Stack::new(Axis::Horizontal));
+    let container = editor.contain_selection(stack).unwrap();
+    let stacked = origins(&editor);
+    assert_ne!(stacked, placed);
+    assert!(editor.undo());
+    assert!(editor.shape(container).is_none());
+    assert_eq!(origins(&editor), placed);
+    assert!(editor.redo());
+    let group = editor.shape(container).unwrap().as_group().unwrap();
+    assert!(group.container.is_some());
+    assert_eq!(origins(&editor), stacked);
+}
+
+#[test]
+fn region_and_nearest_shape_queries() {
+    let mut editor = Editor::new();
+    let big = editor.add_shape(Box::new(IrRectangle::new(
+        0.0,
+        0.0,
+        50.0,
+        50.0,
+        Color::BLACK,
+    )));
+    let far = editor.add_shape(Box::new(IrCircle::new(200.0, 200.0, 20.0, Color::BLACK)));
+    let small = editor.add_shape(Box::new(IrRectangle::new(
+        100.0,
+        0.0,
+        20.0,
+        20.0,
+        Color::BLACK,
+    )));
+
+    assert_eq!(
+        editor.shapes_in_rect(Rect::new(-10.0, -10.0, 130.0, 60.0)),
+        [big, small]
+    );
+    assert_eq!(
+        editor.shapes_in_rect(Rect::new(0.0, 0.0, 110.0, 60.0)),
+        [big]
+    );
+
+    // an open stroke crossing only the small rectangle's edge
+    let mut lasso = BezPath::new();
+    lasso.move_to((60.0, 10.0));
+    lasso.line_to((110.0, 10.0));
+    assert_eq!(editor.shapes_intersecting_path(&lasso), [small]);
+    let around = Rect::new(150.0, 150.0, 250.0, 250.0).to_path(0.1);
+    assert_eq!(editor.shapes_intersecting_path(&around), [far]);
+
+    assert_eq!(
+        editor.distance_to_outline(big, Point::new(25.0, 80.0)),
+        Some(30.0)
+    );
+    assert_eq!(
+        editor.nearest_shape(Point::new(90.0, 10.0), 20.0),
+        Some((small, 10.0))
+    );
+    assert_eq!(
+        editor.nearest_shape(Point::new(10.0, 10.0), 5.0),
+        Some((big, 0.0))
+    );
+    assert_eq!(editor.nearest_shape(Point::new(400.0, 400.0), 20.0), None);
+}
+
+#[test]
+fn pointer_input_reports_clicks_drags_selection_and_changes() {
+    let mut editor = Editor::new();
+    let rect = editor.add_shape(Box::new(IrRectangle::new(
+        0.0,
+        0.0,
+        10.0,
+        10.0,
+        Color::BLACK,
+    )));
+    assert!(editor.take_events().is_empty());
+
+    editor.handle_mouse_down(5.0, 5.0);
+    editor.handle_mouse_up();
+    assert_eq!(
+        editor.take_events(),
+        [
+            EditorEvent::Click {
+                shape: rect,
+                position: Point::new(5.0, 5.0),
+            },
+            EditorEvent::Select { shapes: vec![rect] },
+        ]
+    );
+
+    editor.handle_mouse_down(5.0, 5.0);
+    editor.handle_mouse_move(15.0, 5.0);
+    editor.handle_mouse_up();
+    assert_eq!(
+        editor.take_events(),
+        [
+            EditorEvent::DragStart {
+                shapes: vec![rect],
+                position: Point::new(5.0, 5.0),
+            },
+            EditorEvent::DragEnd {
+                shapes: vec![rect],
+                position: Point::new(15.0, 5.0),
+            },
+            EditorEvent::Change,
+        ]
+    );
+
+    assert!(editor.undo());
+    assert_eq!(editor.take_events(), [EditorEvent::Change]);
+    assert!(editor.take_events().is_empty());
+}
+
+fn touch(pointer_id: i32, phase: PointerPhase, x: f64, y: f64) -> PointerEvent {
+    PointerEvent {
+        pointer_id,
+        pointer_type: PointerType::Touch,
+        phase,
+        x,
+        y,
+        buttons: if phase == PointerPhase::Up { 0 } else { 1 },
+        modifiers: Modifiers::default(),
+        pressure: 0.5,
+        time: 0.0,
+    }
+}
+
+#[test]
+fn each_finger_drags_its_own_shape_and_a_cancel_reverts() {
+    let mut editor = Editor::new();
+    editor.snapping_mut().enabled = false;
+    let a = editor.add_shape(Box::new(IrRectangle::new(
+        0.0,
+        0.0,
+        10.0,
+        10.0,
+        Color::BLACK,
+    )));
+    let b = editor.add_shape(Box::new(IrRectangle::new(
+        100.0,
+        0.0,
+        10.0,
+        10.0,
+        Color::BLACK,
+    )));
+
+    editor.handle_pointer_event(&touch(1, PointerPhase::Down, 5.0, 5.0));
+    editor.handle_pointer_event(&touch(2, PointerPhase::Down, 105.0, 5.0));
+    editor.handle_pointer_event(&touch(1, PointerPhase::Move, 25.0, 5.0));
+    editor.handle_pointer_event(&touch(2, PointerPhase::Move, 105.0, 45.0));
+    assert_eq!(
+        editor.world_bounds(a),
+        Some(Rect::new(20.0, 0.0, 30.0, 10.0))
+    );
+    assert_eq!(
+        editor.world_bounds(b),
+        Some(Rect::new(100.0, 40.0, 110.0, 50.0))
+    );
+
+    // the browser taking over the second touch puts its shape back
+    editor.handle_pointer_event(&touch(2, PointerPhase::Cancel, 105.0, 45.0));
+    editor.handle_pointer_event(&touch(1, PointerPhase::Up, 25.0, 5.0));
+    assert_eq!(
+        editor.world_bounds(b),
+        Some(Rect::new(100.0, 0.0, 110.0, 10.0))
+    );
+    assert!(editor.undo());
+    assert_eq!(
+        editor.world_bounds(a),
+        Some(Rect::new(0.0, 0.0, 10.0, 10.0))
+    );
+    assert!(!editor.can_undo());
+
+    // two fingers on empty canvas pinch the view instead
+    editor.handle_pointer_event(&touch(3, PointerPhase::Down, 200.0, 200.0));
+    editor.handle_pointer_event(&touch(4, PointerPhase::Down, 220.0, 200.0));
+    editor.handle_pointer_event(&touch(4, PointerPhase::Move, 240.0, 200.0));
+    assert_eq!(editor.zoom(), 2.0);
+}
+
+#[test]
+fn holding_the_bypass_key_drags_past_guides() {
+    let mut editor = Editor::new();
+    editor.snapping_mut().guides.push(Guide::Vertical(100.0));
+    let rect = editor.add_shape(Box::new(IrRectangle::new(
+        0.0,
+        0.0,
+        10.0,
+        10.0,
+        Color::BLACK,
+    )));
+    let alt = Modifiers {
+        alt: true,
+        ..Modifiers::default()
+    };
+    let mouse = |phase, x, modifiers| PointerEvent {
+        pointer_type: PointerType::Mouse,
+        modifiers,
+        ..touch(1, phase, x, 5.0)
+    };
+
+    editor.handle_pointer_event(&mouse(PointerPhase::Down, 5.0, Modifiers::default()));
+    editor.handle_pointer_event(&mouse(PointerPhase::Move, 93.0, Modifiers::default()));
+    assert_eq!(editor.world_bounds(rect).unwrap().x1, 100.0);
+    editor.handle_pointer_event(&mouse(PointerPhase::Move, 93.0, alt));
+    assert_eq!(editor.world_bounds(rect).unwrap().x1, 98.0);
+    editor.handle_pointer_event(&mouse(PointerPhase::Up, 93.0, alt));
+
+    editor.snapping_mut().bypass = SnapBypass::Shift;
+    editor.handle_pointer_event(&mouse(PointerPhase::Down, 93.0, Modifiers::default()));
+    editor.handle_pointer_event(&mouse(PointerPhase::Move, 94.0, alt));
+    assert_eq!(editor.world_bounds(rect).unwrap().x1, 100.0);
+}
+
+#[test]
+fn shift_click_adds_to_the_selection_without_dragging() {
+    let mut editor = Editor::new();
+    let a = editor.add_shape(Box::new(IrRectangle::new(
+        0.0,
+        0.0,
+        10.0,
+        10.0,
+        Color::BLACK,
+    )));
+    let b = editor.add_shape(Box::new(IrRectangle::new(
+        20.0,
+        0.0,
+        10.0,
+        10.0,
+        Color::BLACK,
+    )));
+    let shift = Modifiers {
+        shift: true,
+        ..Modifiers::default()
+    };
+    let mouse = |phase, x| PointerEvent {
+        pointer_type: PointerType::Mouse,
+        modifiers: shift,
+        ..touch(1, phase, x, 5.0)
+    };
+
+    editor.handle_mouse_down(5.0, 5.0);
+    editor.handle_mouse_up();
+    editor.handle_pointer_event(&mouse(PointerPhase::Down, 25.0));
+    editor.handle_pointer_event(&mouse(PointerPhase::Move, 45.0));
+    editor.handle_pointer_event(&mouse(PointerPhase::Up, 45.0));
+    assert_eq!(editor.selection(), [a, b]);
+    assert_eq!(
+        editor.world_bounds(b),
+        Some(Rect::new(20.0, 0.0, 30.0, 10.0))
+    );
+    assert!(!editor.can_undo());
+}
+
+#[test]
+fn api_edits_report_selection_and_changes() {
+    let mut editor = Editor::new();
+    let a = editor.add_shape(Box::new(IrRectangle::new(
+        0.0,
+        0.0,
+        10.0,
+        10.0,
+        Color::BLACK,
+    )));
+    let b = editor.add_shape(Box::new(IrRectangle::new(
+        20.0,
+        0.0,
+        10.0,
+        10.0,
+        Color::BLACK,
+    )));
+    editor.set_selection(vec![a, b]);
+    assert_eq!(
+        editor.take_events(),
+        [EditorEvent::Select { shapes: vec![a, b] }]
+    );
+
+    let group = editor.group_selection().unwrap();
+    assert_eq!(
+        editor.take_events(),
+        [
+            EditorEvent::Select {
+                shapes: vec![group]
+            },
+            EditorEvent::Change,
+        ]
+    );
+    assert!(editor.take_events().is_empty());
+}
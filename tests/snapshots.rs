@@ -0,0 +1,632 @@
+//! Golden-image tests: each scene is rendered headlessly and compared with a
+//! committed PNG in `tests/snapshots/`.
+//!
+//! Run with `SNAPSHOT_UPDATE=1 cargo test --test snapshots` to (re)write the
+//! references. A missing reference fails the test, as does a mismatch, which
+//! leaves `<name>.actual.png` next to the reference for inspection. Without
+//! a GPU adapter the tests fail too, unless `SNAPSHOT_ALLOW_NO_ADAPTER` is
+//! set, e.g. on CI machines that can't render.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use svelte_vello::{
+    shapes::{load_font, parse_blend_mode},
+    Editor, Endpoint, FreehandStroke, HeadlessOptions, HeadlessRenderer, ImageParams, IrCircle,
+    IrConnector, IrGroup, IrPath, IrRectangle, IrText, Modifiers, PointerEvent, PointerPhase,
+    PointerType, RgbaImage, Routing, Shape,
+};
+use vello::{
+    kurbo::{Affine, BezPath, Circle, Line, Rect, RoundedRect, Shape as _, Stroke},
+    peniko::{Color, Fill, Gradient},
+    AaConfig, Scene,
+};
+
+const WIDTH: u32 = 96;
+const HEIGHT: u32 = 96;
+
+// YIQ distance (0..1) above which two pixels count as different, as in
+// pixelmatch; small enough to catch colour changes, large enough to ignore
+// rounding differences between adapters
+const PIXEL_THRESHOLD: f64 = 0.1;
+// share of pixels allowed to differ, which absorbs antialiasing noise
+const MAX_DIFF_RATIO: f64 = 0.005;
+
+fn snapshot_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots")
+}
+
+fn render(scene: &Scene) -> Option<RgbaImage> {
+    let mut renderer = match HeadlessRenderer::new_blocking(HeadlessOptions::default()) {
+        Ok(renderer) => renderer,
+        Err(e) if env::var_os("SNAPSHOT_ALLOW_NO_ADAPTER").is_some() => {
+            eprintln!("skipping snapshot: {}", e);
+            return None;
+        }
+        Err(e) => panic!(
+            "no adapter to render snapshots with ({}); set SNAPSHOT_ALLOW_NO_ADAPTER to skip",
+            e
+        ),
+    };
+    let image = renderer
+        .render_blocking(
+            scene,
+            &ImageParams {
+                width: WIDTH,
+                height: HEIGHT,
+                scale: 1.0,
+                base_color: Color::WHITE,
+                antialiasing_method: AaConfig::Area,
+            },
+        )
+        .expect("headless render failed");
+    Some(image)
+}
+
+fn decode_png(path: &Path) -> RgbaImage {
+    let decoder = png::Decoder::new(fs::File::open(path).unwrap());
+    let mut reader = decoder.read_info().unwrap();
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data).unwrap();
+    assert_eq!(
+        info.color_type,
+        png::ColorType::Rgba,
+        "{} is not an RGBA png",
+        path.display()
+    );
+    data.truncate(info.buffer_size());
+    RgbaImage {
+        width: info.width,
+        height: info.height,
+        data,
+    }
+}
+
+// Squared YIQ distance between two pixels after blending onto white,
+// normalised to 0..1.
+fn pixel_delta(a: &[u8], b: &[u8]) -> f64 {
+    fn yiq(px: &[u8]) -> (f64, f64, f64) {
+        let alpha = px[3] as f64 / 255.0;
+        let blend = |c: u8| 255.0 + (c as f64 - 255.0) * alpha;
+        let (r, g, b) = (blend(px[0]), blend(px[1]), blend(px[2]));
+        (
+            r * 0.29889531 + g * 0.58662247 + b * 0.11448223,
+            r * 0.59597799 - g * 0.27417610 - b * 0.32180189,
+            r * 0.21147017 - g * 0.52261711 + b * 0.31114694,
+        )
+    }
+    const MAX_DELTA: f64 = 35215.0;
+
+    let (y1, i1, q1) = yiq(a);
+    let (y2, i2, q2) = yiq(b);
+    let (dy, di, dq) = (y1 - y2, i1 - i2, q1 - q2);
+    (0.5053 * dy * dy + 0.299 * di * di + 0.1957 * dq * dq) / MAX_DELTA
+}
+
+fn assert_snapshot(name: &str, scene: &Scene) {
+    if let Err(e) = check_snapshot(name, scene) {
+        panic!("{}", e);
+    }
+}
+
+// Compares `scene` with its reference, or writes the reference under
+// `SNAPSHOT_UPDATE`; the error describes what to look at.
+fn check_snapshot(name: &str, scene: &Scene) -> Result<(), String> {
+    let actual = match render(scene) {
+        Some(image) => image,
+        None => return Ok(()),
+    };
+    let dir = snapshot_dir();
+    let reference = dir.join(format!("{}.png", name));
+    let actual_path = dir.join(format!("{}.actual.png", name));
+
+    if env::var_os("SNAPSHOT_UPDATE").is_some() {
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&reference, actual.to_png().unwrap()).unwrap();
+        let _ = fs::remove_file(&actual_path);
+        eprintln!("wrote snapshot {}", reference.display());
+        return Ok(());
+    }
+    if !reference.exists() {
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&actual_path, actual.to_png().unwrap()).unwrap();
+        return Err(format!(
+            "snapshot `{}` has no reference; check {} and rerun with SNAPSHOT_UPDATE=1",
+            name,
+            actual_path.display()
+        ));
+    }
+
+    let expected = decode_png(&reference);
+    let mismatched = if (expected.width, expected.height) != (actual.width, actual.height) {
+        Some(format!(
+            "size {}x{} != reference {}x{}",
+            actual.width, actual.height, expected.width, expected.height
+        ))
+    } else {
+        let threshold = PIXEL_THRESHOLD * PIXEL_THRESHOLD;
+        let differing = expected
+            .data
+            .chunks(4)
+            .zip(actual.data.chunks(4))
+            .filter(|(a, b)| pixel_delta(a, b) > threshold)
+            .count();
+        let ratio = differing as f64 / (actual.width * actual.height) as f64;
+        if ratio > MAX_DIFF_RATIO {
+            Some(format!(
+                "{} pixels ({:.2}%) differ",
+                differing,
+                ratio * 100.0
+            ))
+        } else {
+            None
+        }
+    };
+
+    if let Some(reason) = mismatched {
+        fs::write(&actual_path, actual.to_png().unwrap()).unwrap();
+        return Err(format!(
+            "snapshot `{}` does not match: {}; see {}",
+            name,
+            reason,
+            actual_path.display()
+        ));
+    }
+    let _ = fs::remove_file(&actual_path);
+    Ok(())
+}
+
+// JSON scenes describe documents in terms of the crate's own shapes, so they
+// exercise `Editor::build_scene` exactly as the canvas does.
+
+#[derive(Deserialize)]
+struct JsonScene {
+    shapes: Vec<JsonShape>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum JsonShape {
+    Rectangle {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        color: [u8; 4],
+    },
+    Circle {
+        x: f64,
+        y: f64,
+        radius: f64,
+        color: [u8; 4],
+    },
+    /// SVG path data in world coordinates.
+    Path {
+        d: String,
+        color: [u8; 4],
+        #[serde(default)]
+        even_odd: bool,
+    },
+    /// Set in the test font, `tests/fonts/DejaVuSansMono-ASCII.ttf`.
+    Text {
+        x: f64,
+        y: f64,
+        text: String,
+        size: f64,
+        color: [u8; 4],
+    },
+    /// Children are relative to the group; `mask` is a child's index.
+    Group {
+        x: f64,
+        y: f64,
+        children: Vec<JsonShape>,
+        #[serde(default = "opaque")]
+        opacity: f64,
+        #[serde(default)]
+        blend: Option<String>,
+        #[serde(default)]
+        mask: Option<usize>,
+    },
+    /// Between two earlier top-level shapes, by their index in `shapes`.
+    Connector {
+        from: usize,
+        to: usize,
+        routing: String,
+    },
+    /// A pen stroke through `[x, y, pressure]` samples, as the pen tool
+    /// finishes it.
+    Freehand {
+        points: Vec<[f64; 3]>,
+        width: f64,
+        color: [u8; 4],
+    },
+}
+
+fn opaque() -> f64 {
+    1.0
+}
+
+fn test_font() -> vello::peniko::Font {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fonts/DejaVuSansMono-ASCII.ttf");
+    load_font(fs::read(path).unwrap()).expect("invalid test font")
+}
+
+fn rgba([r, g, b, a]: [u8; 4]) -> Color {
+    Color::from_rgba8(r, g, b, a)
+}
+
+// every shape but connectors, which need the ids of the shapes they join
+fn shape_from_json(shape: JsonShape) -> Box<dyn Shape> {
+    match shape {
+        JsonShape::Rectangle {
+            x,
+            y,
+            width,
+            height,
+            color,
+        } => Box::new(IrRectangle::new(x, y, width, height, rgba(color))),
+        JsonShape::Circle {
+            x,
+            y,
+            radius,
+            color,
+        } => Box::new(IrCircle::new(x, y, radius, rgba(color))),
+        JsonShape::Path { d, color, even_odd } => {
+            let path =
+                BezPath::from_svg(&d).unwrap_or_else(|e| panic!("invalid path {}: {}", d, e));
+            let fill = if even_odd {
+                Fill::EvenOdd
+            } else {
+                Fill::NonZero
+            };
+            Box::new(IrPath::from_outline(path, fill, rgba(color)))
+        }
+        JsonShape::Text {
+            x,
+            y,
+            text,
+            size,
+            color,
+        } => Box::new(IrText::new(x, y, &text, test_font(), size, rgba(color))),
+        JsonShape::Group {
+            x,
+            y,
+            children,
+            opacity,
+            blend,
+            mask,
+        } => {
+            let children = children.into_iter().map(shape_from_json).collect();
+            let mut group = IrGroup::new(x, y, children);
+            group.opacity.set(opacity);
+            if let Some(blend) = blend {
+                group.blend =
+                    parse_blend_mode(&blend).unwrap_or_else(|| panic!("unknown blend {}", blend));
+            }
+            group.mask = mask;
+            Box::new(group)
+        }
+        JsonShape::Connector { .. } => panic!("connectors can only be top-level shapes"),
+        JsonShape::Freehand {
+            points,
+            width,
+            color,
+        } => {
+            let mut stroke = FreehandStroke::new(0.0, 0.0);
+            for [x, y, pressure] in points {
+                stroke.push((x, y).into(), pressure);
+            }
+            let outline = stroke.finish(width, 0.5).expect("empty stroke");
+            Box::new(IrPath::from_outline(outline, Fill::NonZero, rgba(color)))
+        }
+    }
+}
+
+fn editor_from_json(path: &Path) -> Editor {
+    let json: JsonScene = serde_json::from_str(&fs::read_to_string(path).unwrap())
+        .unwrap_or_else(|e| panic!("invalid scene {}: {}", path.display(), e));
+    let mut editor = Editor::new();
+    // ids of the top-level shapes so far, which groups' children push apart
+    let mut ids = Vec::new();
+    for shape in json.shapes {
+        let id = match shape {
+            JsonShape::Connector { from, to, routing } => {
+                let routing = Routing::parse(&routing)
+                    .unwrap_or_else(|| panic!("unknown routing {}", routing));
+                let connector = IrConnector::new(
+                    Endpoint::border(ids[from]),
+                    Endpoint::border(ids[to]),
+                    routing,
+                );
+                editor.connect(connector).unwrap()
+            }
+            shape => editor.add_shape(shape_from_json(shape)),
+        };
+        ids.push(id);
+    }
+    editor
+}
+
+#[test]
+fn json_scenes() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/scenes");
+    let mut paths: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+    // check every scene before failing, so one run reports all of them
+    let failures: Vec<_> = paths
+        .iter()
+        .filter_map(|path| {
+            let name = path.file_stem().unwrap().to_str().unwrap();
+            check_snapshot(name, &editor_from_json(path).build_scene()).err()
+        })
+        .collect();
+    assert!(
+        failures.is_empty(),
+        "{} of {} scenes failed:\n{}",
+        failures.len(),
+        paths.len(),
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn rectangle() {
+    let mut editor = Editor::new();
+    editor.add_shape(Box::new(IrRectangle::new(
+        16.0,
+        24.0,
+        64.0,
+        48.0,
+        Color::from_rgb8(220, 40, 40),
+    )));
+    assert_snapshot("rectangle", &editor.build_scene());
+}
+
+#[test]
+fn circle() {
+    let mut editor = Editor::new();
+    editor.add_shape(Box::new(IrCircle::new(
+        48.0,
+        48.0,
+        30.0,
+        Color::from_rgba8(40, 40, 220, 200),
+    )));
+    assert_snapshot("circle", &editor.build_scene());
+}
+
+#[test]
+fn follower_after_drag() {
+    let mut editor = Editor::new();
+    let leader = editor.add_shape(Box::new(IrRectangle::new(
+        8.0,
+        8.0,
+        32.0,
+        32.0,
+        Color::from_rgb8(30, 160, 90),
+    )));
+    let follower = editor.add_shape(Box::new(IrCircle::new(
+        60.0,
+        24.0,
+        12.0,
+        Color::from_rgb8(240, 160, 20),
+    )));
+    editor.follow(follower, leader);
+    editor.handle_mouse_down(16.0, 16.0);
+    editor.handle_mouse_move(36.0, 46.0);
+    editor.handle_mouse_up();
+    assert_snapshot("follower_after_drag", &editor.build_scene());
+}
+
+#[test]
+fn group_layers() {
+    let mut editor = Editor::new();
+    editor.add_shape(Box::new(IrRectangle::new(
+        8.0,
+        8.0,
+        80.0,
+        40.0,
+        Color::from_rgb8(240, 200, 40),
+    )));
+    // a circle masking a bar, blended with multiply at half opacity
+    let mask = editor.add_shape(Box::new(IrCircle::new(48.0, 48.0, 28.0, Color::BLACK)));
+    let bar = editor.add_shape(Box::new(IrRectangle::new(
+        0.0,
+        32.0,
+        96.0,
+        32.0,
+        Color::from_rgb8(40, 90, 220),
+    )));
+    editor.set_selection(vec![mask, bar]);
+    let group = editor.mask_selection().unwrap();
+    assert!(editor.set_blend_mode(group, parse_blend_mode("multiply").unwrap()));
+    assert!(editor.set_property(group, "opacity", 0.5));
+    assert_snapshot("group_layers", &editor.build_scene());
+}
+
+#[test]
+fn strokes() {
+    let mut scene = Scene::new();
+    scene.stroke(
+        &Stroke::new(4.0),
+        Affine::IDENTITY,
+        Color::from_rgb8(20, 20, 20),
+        None,
+        &RoundedRect::new(10.0, 10.0, 86.0, 50.0, 8.0),
+    );
+    scene.stroke(
+        &Stroke::new(2.0).with_dashes(0.0, [6.0, 4.0]),
+        Affine::IDENTITY,
+        Color::from_rgb8(200, 30, 120),
+        None,
+        &Line::new((10.0, 70.0), (86.0, 86.0)),
+    );
+    let mut wave = BezPath::new();
+    wave.move_to((10.0, 60.0));
+    wave.curve_to((30.0, 40.0), (60.0, 90.0), (86.0, 60.0));
+    scene.stroke(
+        &Stroke::new(3.0),
+        Affine::IDENTITY,
+        Color::from_rgb8(30, 90, 200),
+        None,
+        &wave,
+    );
+    assert_snapshot("strokes", &scene);
+}
+
+#[test]
+fn gradients() {
+    let mut scene = Scene::new();
+    let linear = Gradient::new_linear((0.0, 0.0), (96.0, 0.0))
+        .with_stops([Color::from_rgb8(255, 0, 0), Color::from_rgb8(0, 0, 255)]);
+    scene.fill(
+        Fill::NonZero,
+        Affine::IDENTITY,
+        &linear,
+        None,
+        &Rect::new(0.0, 0.0, 96.0, 40.0),
+    );
+    let radial = Gradient::new_radial((48.0, 68.0), 24.0)
+        .with_stops([Color::WHITE, Color::from_rgb8(20, 140, 60)]);
+    scene.fill(
+        Fill::NonZero,
+        Affine::IDENTITY,
+        &radial,
+        None,
+        &Circle::new((48.0, 68.0), 24.0),
+    );
+    assert_snapshot("gradients", &scene);
+}
+
+#[test]
+fn transforms() {
+    let mut editor = Editor::new();
+    editor.add_shape(Box::new(IrRectangle::new(
+        -16.0,
+        -8.0,
+        32.0,
+        16.0,
+        Color::from_rgb8(120, 60, 200),
+    )));
+    let shapes = editor.build_scene();
+
+    let mut scene = Scene::new();
+    scene.append(
+        &shapes,
+        Some(Affine::translate((48.0, 48.0)) * Affine::rotate(std::f64::consts::FRAC_PI_4)),
+    );
+    scene.append(
+        &shapes,
+        Some(Affine::translate((24.0, 80.0)) * Affine::scale_non_uniform(0.5, 1.5)),
+    );
+    assert_snapshot("transforms", &scene);
+}
+
+#[test]
+fn text() {
+    let mut editor = Editor::new();
+    editor.add_shape(Box::new(IrText::new(
+        8.0,
+        12.0,
+        "Vello\nSnap",
+        test_font(),
+        24.0,
+        Color::from_rgb8(20, 20, 120),
+    )));
+    assert_snapshot("text", &editor.build_scene());
+}
+
+#[test]
+fn even_odd_path() {
+    let mut ring = BezPath::new();
+    ring.extend(Circle::new((48.0, 48.0), 40.0).path_elements(0.1));
+    ring.extend(Circle::new((48.0, 48.0), 20.0).path_elements(0.1));
+    let mut editor = Editor::new();
+    editor.add_shape(Box::new(IrPath::from_outline(
+        ring,
+        Fill::EvenOdd,
+        Color::from_rgb8(200, 60, 20),
+    )));
+    assert_snapshot("even_odd_path", &editor.build_scene());
+}
+
+#[test]
+fn connectors() {
+    let mut editor = Editor::new();
+    let a = editor.add_shape(Box::new(IrRectangle::new(
+        4.0,
+        4.0,
+        24.0,
+        20.0,
+        Color::from_rgb8(60, 60, 60),
+    )));
+    let b = editor.add_shape(Box::new(IrCircle::new(
+        76.0,
+        76.0,
+        14.0,
+        Color::from_rgb8(60, 60, 60),
+    )));
+    // in the way of the orthogonal route, which bends over it
+    editor.add_shape(Box::new(IrRectangle::new(
+        50.0,
+        36.0,
+        10.0,
+        10.0,
+        Color::from_rgb8(200, 200, 200),
+    )));
+    for routing in [Routing::Straight, Routing::Curved, Routing::Orthogonal] {
+        editor
+            .connect(IrConnector::new(
+                Endpoint::border(a),
+                Endpoint::border(b),
+                routing,
+            ))
+            .unwrap();
+    }
+    assert_snapshot("connectors", &editor.build_scene());
+}
+
+#[test]
+fn freehand_stroke() {
+    let mut editor = Editor::new();
+    assert!(editor.set_tool("pen"));
+    editor.brush_mut().width = 6.0;
+    let points = [
+        (10.0, 70.0),
+        (30.0, 30.0),
+        (50.0, 60.0),
+        (70.0, 20.0),
+        (86.0, 50.0),
+    ];
+    for (index, (x, y)) in points.iter().enumerate() {
+        let phase = match index {
+            0 => PointerPhase::Down,
+            _ => PointerPhase::Move,
+        };
+        editor.handle_pointer_event(&pen(phase, *x, *y, index as f64 * 0.016));
+    }
+    editor.handle_pointer_event(&pen(PointerPhase::Up, 86.0, 50.0, 0.1));
+    assert_eq!(editor.shape_ids().count(), 1);
+    assert_snapshot("freehand_stroke", &editor.build_scene());
+}
+
+fn pen(phase: PointerPhase, x: f64, y: f64, time: f64) -> PointerEvent {
+    PointerEvent {
+        pointer_id: 1,
+        pointer_type: PointerType::Pen,
+        phase,
+        x,
+        y,
+        buttons: 1,
+        modifiers: Modifiers::default(),
+        pressure: 0.5,
+        time,
+    }
+}